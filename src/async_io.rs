@@ -0,0 +1,122 @@
+use std::io::{self, Read, Write};
+use std::{cmp, fmt};
+
+use bytes::Bytes;
+use futures::{Async, AsyncSink, Poll, Sink, Stream};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+use FsReadStream;
+use FsWriteSink;
+
+pub(crate) fn read(stream: FsReadStream) -> FsAsyncRead {
+    FsAsyncRead {
+        inner: stream,
+        chunk: Bytes::new(),
+        eof: false,
+    }
+}
+
+/// A reader of a target file, implementing `AsyncRead`.
+///
+/// Created by `FsReadStream::into_async_read`. Reads are served from the
+/// current chunk of the stream, and fail with `WouldBlock` while the next
+/// chunk hasn't been read yet, so it must be read from within a task, which
+/// is notified once the chunk is ready. The end of the file is a read of 0
+/// bytes.
+pub struct FsAsyncRead {
+    inner: FsReadStream,
+    chunk: Bytes,
+    eof: bool,
+}
+
+impl Read for FsAsyncRead {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while self.chunk.is_empty() {
+            if self.eof {
+                return Ok(0);
+            }
+            match self.inner.poll()? {
+                Async::Ready(Some(chunk)) => self.chunk = chunk,
+                Async::Ready(None) => self.eof = true,
+                Async::NotReady => return Err(io::ErrorKind::WouldBlock.into()),
+            }
+        }
+        let len = cmp::min(buf.len(), self.chunk.len());
+        buf[..len].copy_from_slice(&self.chunk.split_to(len));
+        Ok(len)
+    }
+}
+
+impl AsyncRead for FsAsyncRead {
+    unsafe fn prepare_uninitialized_buffer(&self, _: &mut [u8]) -> bool {
+        false
+    }
+}
+
+impl fmt::Debug for FsAsyncRead {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsAsyncRead")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+pub(crate) fn write(sink: FsWriteSink) -> FsAsyncWrite {
+    FsAsyncWrite { inner: sink }
+}
+
+/// A writer of a target file, implementing `AsyncWrite`.
+///
+/// Created by `FsWriteSink::into_async_write`. Each write sends all of its
+/// bytes to the sink, or none of them with `WouldBlock` while the sink is
+/// full, so it must be written to from within a task. A write or flush fails
+/// with the error of an earlier write that failed on the pool. Shutting it
+/// down closes the sink.
+pub struct FsAsyncWrite {
+    inner: FsWriteSink,
+}
+
+impl FsAsyncWrite {
+    /// The number of bytes written to the file so far, like
+    /// `FsWriteSink::written`.
+    pub fn written(&self) -> u64 {
+        self.inner.written()
+    }
+}
+
+impl Write for FsAsyncWrite {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.poll_failed()?;
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        match self.inner.start_send(Bytes::from(buf))? {
+            AsyncSink::Ready => Ok(buf.len()),
+            AsyncSink::NotReady(_) => Err(io::ErrorKind::WouldBlock.into()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.inner.poll_complete()? {
+            Async::Ready(()) => Ok(()),
+            Async::NotReady => Err(io::ErrorKind::WouldBlock.into()),
+        }
+    }
+}
+
+impl AsyncWrite for FsAsyncWrite {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.inner.close()
+    }
+}
+
+impl fmt::Debug for FsAsyncWrite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsAsyncWrite")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
@@ -0,0 +1,115 @@
+use std::io::{self, Read, Write};
+use std::{cmp, fmt};
+
+use bytes::Bytes;
+use futures::executor::{self, Spawn};
+
+use FsReadStream;
+use FsWriteSink;
+
+pub(crate) fn read(stream: FsReadStream) -> FsBlockingRead {
+    FsBlockingRead {
+        inner: executor::spawn(stream),
+        chunk: Bytes::new(),
+    }
+}
+
+pub(crate) fn write(sink: FsWriteSink) -> FsBlockingWrite {
+    FsBlockingWrite {
+        inner: executor::spawn(sink),
+        closed: false,
+    }
+}
+
+/// A reader of a target file, implementing `std::io::Read`.
+///
+/// Created by `FsReadStream::into_blocking_read`. Each read blocks the
+/// calling thread until the next chunk has been read on the pool, so it must
+/// not be used on a thread that runs futures, like one of the pool.
+pub struct FsBlockingRead {
+    inner: Spawn<FsReadStream>,
+    chunk: Bytes,
+}
+
+impl Read for FsBlockingRead {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while self.chunk.is_empty() {
+            match self.inner.wait_stream() {
+                Some(chunk) => self.chunk = chunk?,
+                None => return Ok(0),
+            }
+        }
+        let len = cmp::min(buf.len(), self.chunk.len());
+        buf[..len].copy_from_slice(&self.chunk.split_to(len));
+        Ok(len)
+    }
+}
+
+impl fmt::Debug for FsBlockingRead {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsBlockingRead")
+            .field("inner", self.inner.get_ref())
+            .finish()
+    }
+}
+
+/// A writer of a target file, implementing `std::io::Write`.
+///
+/// Created by `FsWriteSink::into_blocking_write`. Writes block the calling
+/// thread while the sink is full, and flushing blocks until everything sent
+/// has been written, so it must not be used on a thread that runs futures,
+/// like one of the pool.
+///
+/// Dropping the writer closes the sink, ignoring any error, like
+/// `std::io::BufWriter` does. Use `close` to see the error instead.
+pub struct FsBlockingWrite {
+    inner: Spawn<FsWriteSink>,
+    closed: bool,
+}
+
+impl FsBlockingWrite {
+    /// The number of bytes written to the file so far, like
+    /// `FsWriteSink::written`.
+    pub fn written(&self) -> u64 {
+        self.inner.get_ref().written()
+    }
+
+    /// Closes the sink, blocking until the file has been finished.
+    pub fn close(mut self) -> io::Result<()> {
+        self.closed = true;
+        self.inner.wait_close()
+    }
+}
+
+impl Write for FsBlockingWrite {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.inner.wait_send(Bytes::from(buf))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.wait_flush()
+    }
+}
+
+impl Drop for FsBlockingWrite {
+    fn drop(&mut self) {
+        if !self.closed {
+            let _ = self.inner.wait_close();
+        }
+    }
+}
+
+impl fmt::Debug for FsBlockingWrite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsBlockingWrite")
+            .field("inner", self.inner.get_ref())
+            .finish()
+    }
+}
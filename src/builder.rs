@@ -0,0 +1,160 @@
+use std::sync::Arc;
+use std::{cmp, env, fmt, thread};
+
+use futures_cpupool::Builder;
+
+use rate::RateLimit;
+use FsPool;
+
+/// The environment variable that overrides the default number of threads.
+const THREADS_VAR: &str = "FUTURES_FS_THREADS";
+/// The fewest threads by default, since they spend most of their time
+/// blocked on IO rather than using a CPU.
+const MIN_THREADS: usize = 4;
+
+/// Builds an `FsPool` with its own threads, from `FsPool::builder`.
+///
+/// The default number of threads is the number of CPUs, but at least 4. It
+/// can be overridden with the `FUTURES_FS_THREADS` environment variable. The
+/// threads have the stack size and names of `std::thread` by default.
+pub struct FsPoolBuilder {
+    inner: Builder,
+    threads: usize,
+    max_bytes_per_sec: Option<u64>,
+}
+
+impl FsPoolBuilder {
+    pub(crate) fn new() -> FsPoolBuilder {
+        FsPoolBuilder {
+            inner: Builder::new(),
+            threads: default_threads(),
+            max_bytes_per_sec: None,
+        }
+    }
+
+    /// The number of threads of the pool.
+    ///
+    /// # Panic
+    ///
+    /// The passed argument must be larger than 0.
+    pub fn pool_size(mut self, threads: usize) -> Self {
+        assert!(threads > 0, "pool size must be larger than 0");
+        self.threads = threads;
+        self
+    }
+
+    /// The prefix of the names of the threads, which are numbered after it.
+    ///
+    /// With a prefix of `fs-`, the threads are named `fs-0`, `fs-1` and so
+    /// on.
+    ///
+    /// Default is to leave the threads unnamed.
+    pub fn name_prefix(mut self, name_prefix: &str) -> Self {
+        self.inner.name_prefix(name_prefix);
+        self
+    }
+
+    /// The size of the stack of each thread, in bytes.
+    ///
+    /// Default is the stack size of `std::thread`.
+    pub fn stack_size(mut self, stack_size: usize) -> Self {
+        self.inner.stack_size(stack_size);
+        self
+    }
+
+    /// Calls `f` on each thread once it has started, before it runs any
+    /// work.
+    pub fn after_start<F>(mut self, f: F) -> Self
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.inner.after_start(f);
+        self
+    }
+
+    /// Calls `f` on each thread before it stops, once every handle of the
+    /// pool has been dropped.
+    pub fn before_stop<F>(mut self, f: F) -> Self
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.inner.before_stop(f);
+        self
+    }
+
+    /// The most bytes per second that all operations of the pool read and
+    /// write together.
+    ///
+    /// Once the budget is used up, the pool threads sleep before the next
+    /// chunk, so a limited pool should have enough threads for the other
+    /// operations. It can be set for a single stream or sink with its
+    /// options instead, like `ReadOptions::max_bytes_per_sec`.
+    ///
+    /// Default is no limit.
+    ///
+    /// # Panic
+    ///
+    /// The passed argument must be larger than 0.
+    pub fn max_bytes_per_sec(mut self, max_bytes_per_sec: u64) -> Self {
+        assert!(max_bytes_per_sec > 0, "rate limit must be larger than 0");
+        self.max_bytes_per_sec = Some(max_bytes_per_sec);
+        self
+    }
+
+    /// Creates the pool, and starts its threads.
+    pub fn build(mut self) -> FsPool {
+        let pool = self.inner.pool_size(self.threads).create();
+        let mut pool = FsPool::from_parts(Arc::new(pool), Some(self.threads));
+        pool.rate = self
+            .max_bytes_per_sec
+            .map(|rate| Arc::new(RateLimit::new(rate)));
+        pool
+    }
+}
+
+/// The number of threads from `FUTURES_FS_THREADS`, or the number of CPUs,
+/// but at least `MIN_THREADS`.
+fn default_threads() -> usize {
+    threads_from_env(env::var(THREADS_VAR).ok().as_deref())
+}
+
+/// The number of threads from the value of `FUTURES_FS_THREADS`, if it is
+/// set to a positive number, or the number of CPUs, but at least
+/// `MIN_THREADS`.
+fn threads_from_env(var: Option<&str>) -> usize {
+    let threads = var
+        .and_then(|threads| threads.trim().parse().ok())
+        .filter(|&threads| threads > 0);
+    threads.unwrap_or_else(|| {
+        let cpus = thread::available_parallelism().map_or(1, |cpus| cpus.get());
+        cmp::max(MIN_THREADS, cpus)
+    })
+}
+
+impl fmt::Debug for FsPoolBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsPoolBuilder")
+            .field("inner", &self.inner)
+            .field("threads", &self.threads)
+            .field("max_bytes_per_sec", &self.max_bytes_per_sec)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{threads_from_env, MIN_THREADS};
+
+    #[test]
+    fn test_threads_from_env() {
+        assert_eq!(threads_from_env(Some("3")), 3);
+        assert_eq!(threads_from_env(Some(" 12\n")), 12);
+
+        // anything but a positive number is ignored
+        let default = threads_from_env(None);
+        assert!(default >= MIN_THREADS);
+        for var in &["", "0", "-2", "many"] {
+            assert_eq!(threads_from_env(Some(var)), default, "{:?}", var);
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use futures::task::AtomicTask;
+
+/// A handle to stop the work of a stream or sink on the pool.
+///
+/// Once canceled, the task on the pool stops before its next read or write,
+/// and the stream or sink fails with an error. A read or write that is in
+/// progress can't be interrupted, and is finished first.
+///
+/// Dropping the stream or sink cancels it as well.
+#[derive(Clone)]
+pub struct FsCancelHandle {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    canceled: AtomicBool,
+    task: AtomicTask,
+}
+
+impl FsCancelHandle {
+    pub(crate) fn new() -> FsCancelHandle {
+        FsCancelHandle {
+            inner: Arc::new(Inner {
+                canceled: AtomicBool::new(false),
+                task: AtomicTask::new(),
+            }),
+        }
+    }
+
+    /// Cancels the work on the pool.
+    pub fn cancel(&self) {
+        self.inner.canceled.store(true, Ordering::SeqCst);
+        // the task may be waiting for the stream or sink to be polled
+        self.inner.task.notify();
+    }
+
+    /// Whether the work has been canceled.
+    pub fn is_canceled(&self) -> bool {
+        self.inner.canceled.load(Ordering::SeqCst)
+    }
+
+    /// Registers the current task to be notified once canceled.
+    pub(crate) fn register(&self) {
+        self.inner.task.register();
+    }
+}
+
+impl fmt::Debug for FsCancelHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsCancelHandle")
+            .field("canceled", &self.is_canceled())
+            .finish()
+    }
+}
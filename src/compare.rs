@@ -0,0 +1,73 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use metrics::Metrics;
+use rate::RateLimit;
+
+/// The size of the blocks the files are compared in.
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// Finds the offset of the first byte that differs between the files at `a`
+/// and `b`, or where the shorter one ends.
+///
+/// With `by_len`, files of different lengths return the shorter length
+/// right away, without reading them.
+pub(crate) fn first_difference(
+    a: &Path,
+    b: &Path,
+    by_len: bool,
+    metrics: &Metrics,
+    rate: Option<&RateLimit>,
+) -> io::Result<Option<u64>> {
+    let mut a = File::open(a)?;
+    let mut b = File::open(b)?;
+    let (a_len, b_len) = (a.metadata()?.len(), b.metadata()?.len());
+    if by_len && a_len != b_len {
+        return Ok(Some(a_len.min(b_len)));
+    }
+
+    let mut a_buf = vec![0; BLOCK_SIZE];
+    let mut b_buf = vec![0; BLOCK_SIZE];
+    let mut offset = 0;
+    loop {
+        let a_n = read_block(&mut a, &mut a_buf)?;
+        let b_n = read_block(&mut b, &mut b_buf)?;
+        metrics.read((a_n + b_n) as u64);
+        if let Some(rate) = rate {
+            rate.take((a_n + b_n) as u64);
+        }
+
+        let len = a_n.min(b_n);
+        let differs = a_buf[..len]
+            .iter()
+            .zip(&b_buf[..len])
+            .position(|(a, b)| a != b);
+        if let Some(i) = differs {
+            return Ok(Some(offset + i as u64));
+        }
+        if a_n != b_n {
+            // one of the files ended
+            return Ok(Some(offset + len as u64));
+        }
+        if a_n == 0 {
+            return Ok(None);
+        }
+        offset += len as u64;
+    }
+}
+
+/// Reads until `buf` is full or the file ends, so both files are compared
+/// at the same offsets.
+fn read_block(file: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
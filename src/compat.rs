@@ -0,0 +1,186 @@
+use std::fmt;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+
+use futures::executor::{self, Notify, Spawn};
+use futures::{Async, AsyncSink, Future, Sink, Stream};
+use futures_core;
+use futures_sink;
+
+/// An adapter of a futures 0.1 future or stream of this crate, implementing
+/// `std::future::Future` or the futures 0.3 `Stream`.
+///
+/// An `FsFuture` becomes a future of a `Result`, and a stream like
+/// `FsReadStream` becomes a stream of results, so they can be awaited.
+/// Requires the `std-future` feature.
+pub struct FsCompat<T> {
+    inner: Spawn<T>,
+}
+
+/// An adapter of a futures 0.1 sink of this crate, like `FsWriteSink`,
+/// implementing the futures 0.3 `Sink`.
+///
+/// An item is taken by `start_send` once `poll_ready` found room for it, and
+/// held by the adapter until the wrapped sink has taken it as well. Requires
+/// the `std-future` feature.
+pub struct FsCompatSink<T: Sink> {
+    inner: Spawn<T>,
+    /// The item that the sink didn't have room for yet.
+    item: Option<T::SinkItem>,
+}
+
+/// Wakes the task of a futures 0.3 `Context`, when the futures 0.1 type
+/// being polled within it is notified.
+struct WakerNotify(Waker);
+
+impl Notify for WakerNotify {
+    fn notify(&self, _: usize) {
+        self.0.wake_by_ref();
+    }
+}
+
+/// Sends the item waiting for room in `sink`, if there is one.
+fn send_item<T: Sink>(
+    sink: &mut T,
+    item: &mut Option<T::SinkItem>,
+) -> ::futures::Poll<(), T::SinkError> {
+    if let Some(next) = item.take() {
+        if let AsyncSink::NotReady(next) = sink.start_send(next)? {
+            *item = Some(next);
+            return Ok(Async::NotReady);
+        }
+    }
+    Ok(Async::Ready(()))
+}
+
+/// Runs `f` on `inner` in a futures 0.1 task, which wakes the task of `cx`.
+fn in_task<T, F, R>(inner: &mut Spawn<T>, cx: &mut Context, f: F) -> R
+where
+    F: FnOnce(&mut T) -> R,
+{
+    let notify = Arc::new(WakerNotify(cx.waker().clone()));
+    inner.poll_fn_notify(&notify, 0, f)
+}
+
+fn to_std<T, E>(res: Result<Async<T>, E>) -> Poll<Result<T, E>> {
+    match res {
+        Ok(Async::Ready(value)) => Poll::Ready(Ok(value)),
+        Ok(Async::NotReady) => Poll::Pending,
+        Err(e) => Poll::Ready(Err(e)),
+    }
+}
+
+impl<T> FsCompat<T> {
+    /// Wraps `inner`, to use it with `std::future` and futures 0.3.
+    pub fn new(inner: T) -> FsCompat<T> {
+        FsCompat {
+            inner: executor::spawn(inner),
+        }
+    }
+
+    /// Returns the wrapped future or stream.
+    pub fn get_ref(&self) -> &T {
+        self.inner.get_ref()
+    }
+
+    /// Returns the wrapped future or stream.
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
+
+impl<T: Future + Unpin> ::std::future::Future for FsCompat<T> {
+    type Output = Result<T::Item, T::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        to_std(in_task(&mut self.get_mut().inner, cx, |inner| inner.poll()))
+    }
+}
+
+impl<T: Stream + Unpin> futures_core::Stream for FsCompat<T> {
+    type Item = Result<T::Item, T::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        match to_std(in_task(&mut self.get_mut().inner, cx, |inner| inner.poll())) {
+            Poll::Ready(Ok(item)) => Poll::Ready(item.map(Ok)),
+            Poll::Ready(Err(e)) => Poll::Ready(Some(Err(e))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for FsCompat<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsCompat")
+            .field("inner", self.inner.get_ref())
+            .finish()
+    }
+}
+
+impl<T: Sink> FsCompatSink<T> {
+    /// Wraps `inner`, to use it with futures 0.3.
+    pub fn new(inner: T) -> FsCompatSink<T> {
+        FsCompatSink {
+            inner: executor::spawn(inner),
+            item: None,
+        }
+    }
+
+    /// Returns the wrapped sink.
+    pub fn get_ref(&self) -> &T {
+        self.inner.get_ref()
+    }
+
+    /// Returns the wrapped sink, dropping an item it didn't take yet.
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
+
+impl<T> futures_sink::Sink<T::SinkItem> for FsCompatSink<T>
+where
+    T: Sink + Unpin,
+    T::SinkItem: Unpin,
+{
+    type Error = T::SinkError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        let item = &mut this.item;
+        to_std(in_task(&mut this.inner, cx, |sink| send_item(sink, item)))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: T::SinkItem) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        assert!(this.item.is_none(), "start_send called without poll_ready");
+        this.item = Some(item);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        let item = &mut this.item;
+        to_std(in_task(&mut this.inner, cx, |sink| {
+            try_ready!(send_item(sink, item));
+            sink.poll_complete()
+        }))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        let item = &mut this.item;
+        to_std(in_task(&mut this.inner, cx, |sink| {
+            try_ready!(send_item(sink, item));
+            sink.close()
+        }))
+    }
+}
+
+impl<T: Sink + fmt::Debug> fmt::Debug for FsCompatSink<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsCompatSink")
+            .field("inner", self.inner.get_ref())
+            .finish()
+    }
+}
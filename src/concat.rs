@@ -0,0 +1,166 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::path::PathBuf;
+use std::{fmt, io, mem};
+
+use bytes::Bytes;
+use futures::{Async, Future, Poll, Stream};
+
+use FsFuture;
+use FsPool;
+use FsReadStream;
+use ReadOptions;
+
+pub(crate) fn new<I>(pool: &FsPool, paths: I, opts: ReadOptions) -> FsConcatStream
+where
+    I: IntoIterator<Item = PathBuf>,
+{
+    FsConcatStream {
+        pool: pool.clone(),
+        paths: paths.into_iter().collect(),
+        opts,
+        eager_open: false,
+        offset: 0,
+        state: State::Init,
+    }
+}
+
+/// A `Stream` of the contents of several files, one after the other.
+///
+/// Created by `FsPool::read_concat`. Each file is read like with
+/// `FsPool::read` and the same options, once the file before it has ended,
+/// so a file that can't be opened fails the stream only once it is reached.
+pub struct FsConcatStream {
+    pool: FsPool,
+    paths: VecDeque<PathBuf>,
+    opts: ReadOptions,
+    eager_open: bool,
+    /// The number of bytes yielded so far, over all files.
+    offset: u64,
+    state: State,
+}
+
+enum State {
+    Init,
+    Opening(FsFuture<()>),
+    Reading(FsReadStream),
+    Next,
+    Done,
+}
+
+impl FsConcatStream {
+    /// Whether to check that every file can be opened before reading any
+    /// of them, failing the stream right away otherwise.
+    ///
+    /// Default is `false`.
+    pub fn eager_open(mut self, eager_open: bool) -> Self {
+        self.eager_open = eager_open;
+        self
+    }
+
+    /// Returns a `Stream` that yields each chunk along with its offset, as
+    /// if the files were a single file.
+    pub fn with_offsets(self) -> FsOffsetConcatStream {
+        FsOffsetConcatStream { inner: self }
+    }
+
+    fn poll_chunk(&mut self) -> Poll<Option<(u64, Bytes)>, io::Error> {
+        loop {
+            match mem::replace(&mut self.state, State::Next) {
+                State::Init if self.eager_open => {
+                    let paths = self.paths.clone();
+                    let opening = self.pool.exec(move || {
+                        for path in paths {
+                            File::open(&path).map_err(|e| ::path_error(&path, e))?;
+                        }
+                        Ok(())
+                    });
+                    self.state = State::Opening(opening);
+                }
+                State::Init | State::Next => match self.paths.pop_front() {
+                    Some(path) => {
+                        let stream = self.pool.read(path, self.opts.clone());
+                        self.state = State::Reading(stream);
+                    }
+                    None => self.state = State::Done,
+                },
+                State::Opening(mut opening) => match opening.poll() {
+                    Ok(Async::Ready(())) => (),
+                    Ok(Async::NotReady) => {
+                        self.state = State::Opening(opening);
+                        return Ok(Async::NotReady);
+                    }
+                    Err(e) => {
+                        self.state = State::Done;
+                        return Err(e);
+                    }
+                },
+                State::Reading(mut stream) => match stream.poll() {
+                    Ok(Async::Ready(Some(chunk))) => {
+                        self.state = State::Reading(stream);
+                        let offset = self.offset;
+                        self.offset += chunk.len() as u64;
+                        return Ok(Async::Ready(Some((offset, chunk))));
+                    }
+                    Ok(Async::Ready(None)) => (),
+                    Ok(Async::NotReady) => {
+                        self.state = State::Reading(stream);
+                        return Ok(Async::NotReady);
+                    }
+                    Err(e) => {
+                        self.state = State::Done;
+                        return Err(e);
+                    }
+                },
+                State::Done => {
+                    self.state = State::Done;
+                    return Ok(Async::Ready(None));
+                }
+            }
+        }
+    }
+}
+
+impl Stream for FsConcatStream {
+    type Item = Bytes;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        let chunk = try_ready!(self.poll_chunk());
+        Ok(Async::Ready(chunk.map(|(_, bytes)| bytes)))
+    }
+}
+
+impl fmt::Debug for FsConcatStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsConcatStream")
+            .field("paths", &self.paths)
+            .field("offset", &self.offset)
+            .finish()
+    }
+}
+
+/// A `Stream` of the contents of several files, along with their offset in
+/// the concatenation of the files.
+///
+/// Created by `FsConcatStream::with_offsets`.
+pub struct FsOffsetConcatStream {
+    inner: FsConcatStream,
+}
+
+impl Stream for FsOffsetConcatStream {
+    type Item = (u64, Bytes);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        self.inner.poll_chunk()
+    }
+}
+
+impl fmt::Debug for FsOffsetConcatStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsOffsetConcatStream")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
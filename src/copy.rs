@@ -0,0 +1,824 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::{cmp, fmt, process};
+
+use futures::future::join_all;
+use futures::sync::oneshot;
+use futures::task::{self, Task};
+use futures::{Async, Future, Poll, Stream};
+
+use metrics::Metrics;
+use rate::RateLimit;
+use sys;
+use FsFuture;
+use FsPool;
+
+const BUF_SIZE: usize = 64 * 1024;
+/// The most bytes the kernel copies at once, between counting and charging
+/// them.
+const CHUNK_SIZE: u64 = 1024 * 1024;
+const SEGMENT_BUF_SIZE: usize = 1024 * 1024;
+const DEFAULT_SEGMENTS: usize = 4;
+const PROGRESS_BYTES: u64 = 1024 * 1024;
+const PROGRESS_STEP: u64 = 1024 * 1024;
+
+/// Options for how to copy a file.
+///
+/// The default is to copy all bytes of the file, offloading the copy to the
+/// kernel where possible.
+#[derive(Clone, Debug)]
+pub struct CopyOptions {
+    sparse: bool,
+    allow_reflink: bool,
+    segments: Option<usize>,
+    keep_partial: bool,
+    progress_bytes: u64,
+    progress_interval: Option<Duration>,
+}
+
+impl Default for CopyOptions {
+    fn default() -> CopyOptions {
+        CopyOptions {
+            sparse: false,
+            allow_reflink: false,
+            segments: None,
+            keep_partial: false,
+            progress_bytes: PROGRESS_BYTES,
+            progress_interval: None,
+        }
+    }
+}
+
+impl CopyOptions {
+    /// Whether to keep the holes of a sparse file, instead of writing them
+    /// out as zeros.
+    ///
+    /// Only the ranges of the file that contain data are copied, found with
+    /// `SEEK_DATA` and `SEEK_HOLE` on Linux, and the holes between them are
+    /// recreated by extending the copy to the same length. Where finding the
+    /// holes isn't supported, the whole file is copied.
+    ///
+    /// Default is `false`.
+    pub fn sparse(mut self, sparse: bool) -> Self {
+        self.sparse = sparse;
+        self
+    }
+
+    /// Whether to first try to copy the file as a reflink, sharing its
+    /// blocks with the source until either of them is modified.
+    ///
+    /// This uses `FICLONE` on Linux, which is supported by filesystems such
+    /// as Btrfs and XFS. If the file can't be cloned, like across
+    /// filesystems, it is copied as usual. This only has an effect on Linux.
+    ///
+    /// Default is `false`.
+    pub fn allow_reflink(mut self, allow_reflink: bool) -> Self {
+        self.allow_reflink = allow_reflink;
+        self
+    }
+
+    /// The number of segments to split the file into, each copied on its own
+    /// thread of the pool by `FsPool::copy_parallel`.
+    ///
+    /// Default is the number of threads of the pool, or 4 for a pool created
+    /// from an `Executor`.
+    ///
+    /// # Panic
+    ///
+    /// The passed argument must be larger than 0.
+    pub fn segments(mut self, segments: usize) -> Self {
+        assert!(segments > 0, "segments must be larger than 0");
+        self.segments = Some(segments);
+        self
+    }
+
+    /// Whether to keep the partially written copy when `FsPool::copy_parallel`
+    /// fails, instead of removing it.
+    ///
+    /// Default is `false`.
+    pub fn keep_partial(mut self, keep_partial: bool) -> Self {
+        self.keep_partial = keep_partial;
+        self
+    }
+
+    /// The number of bytes to copy before reporting the progress of
+    /// `FsPool::copy_with_progress` again.
+    ///
+    /// Default is 1 MiB.
+    ///
+    /// # Panic
+    ///
+    /// The passed argument must be larger than 0.
+    pub fn progress_bytes(mut self, progress_bytes: u64) -> Self {
+        assert!(progress_bytes > 0, "progress bytes must be larger than 0");
+        self.progress_bytes = progress_bytes;
+        self
+    }
+
+    /// How long to copy before reporting the progress of
+    /// `FsPool::copy_with_progress` again, even if fewer than
+    /// `progress_bytes` have been copied since.
+    ///
+    /// Default is to only report progress by the number of bytes.
+    pub fn progress_interval(mut self, interval: Duration) -> Self {
+        self.progress_interval = Some(interval);
+        self
+    }
+}
+
+/// Counts the bytes a copy copies in the metrics of the pool, and charges
+/// them to its rate limit, if any.
+#[derive(Clone, Copy)]
+pub(crate) struct Meter<'a> {
+    metrics: &'a Metrics,
+    rate: Option<&'a RateLimit>,
+}
+
+impl<'a> Meter<'a> {
+    pub(crate) fn new(metrics: &'a Metrics, rate: Option<&'a RateLimit>) -> Meter<'a> {
+        Meter { metrics, rate }
+    }
+
+    fn copied(&self, n: u64) {
+        self.metrics.read(n);
+        self.metrics.written(n);
+        if let Some(rate) = self.rate {
+            rate.take(n);
+        }
+    }
+}
+
+pub(crate) fn copy(
+    from: &Path,
+    to: &Path,
+    opts: &CopyOptions,
+    reporter: &mut Reporter,
+    meter: Meter,
+) -> io::Result<u64> {
+    let src = File::open(from)?;
+    let metadata = src.metadata()?;
+    if !metadata.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the source path is not a file",
+        ));
+    }
+    let dst = File::create(to)?;
+    dst.set_permissions(metadata.permissions())?;
+    let len = metadata.len();
+    reporter.total = Some(len);
+
+    // any failure to clone just means copying instead, while a clone shares
+    // the blocks without copying any bytes
+    if opts.allow_reflink && sys::reflink(&src, &dst).is_ok() {
+        let len = dst.metadata()?.len();
+        reporter.finish(len);
+        return Ok(len);
+    }
+    if !opts.sparse {
+        let copied = copy_reported(&src, &dst, 0, u64::MAX, reporter, meter)?;
+        reporter.finish(copied);
+        return Ok(copied);
+    }
+
+    let mut pos = 0;
+    while pos < len {
+        let (start, end) = match sys::next_data(&src, pos)? {
+            Some(range) => range,
+            None => break,
+        };
+        let n = copy_reported(&src, &dst, start, end - start, reporter, meter)?;
+        if n < end - start {
+            // the file shrank while copying it
+            break;
+        }
+        pos = end;
+    }
+    // a hole at the end isn't covered by any data
+    dst.set_len(len)?;
+    reporter.finish(len);
+    Ok(len)
+}
+
+/// Copies like `copy_range`, in steps between which the progress is
+/// reported as the position reached in the file, and the bytes are charged.
+fn copy_reported(
+    src: &File,
+    dst: &File,
+    offset: u64,
+    len: u64,
+    reporter: &mut Reporter,
+    meter: Meter,
+) -> io::Result<u64> {
+    let step = cmp::min(reporter.step(), CHUNK_SIZE);
+    let mut copied = 0;
+    while copied < len {
+        let max = cmp::min(step, len - copied);
+        let n = copy_range(src, dst, offset + copied, max)?;
+        meter.copied(n);
+        copied += n;
+        reporter.update(offset + copied);
+        if n < max {
+            break;
+        }
+    }
+    Ok(copied)
+}
+
+/// Copies up to `len` bytes from `offset` of `src` to the same offset of
+/// `dst`, returning how many were copied before the end of `src`.
+///
+/// The copy is offloaded to the kernel where possible, and otherwise goes
+/// through a buffer.
+fn copy_range(src: &File, dst: &File, offset: u64, len: u64) -> io::Result<u64> {
+    let mut copied = 0;
+    if let Some(n) = sys::copy_range(src, dst, offset, len)? {
+        if n == len {
+            return Ok(n);
+        }
+        // the kernel copies nothing from files like those of procfs, so the
+        // rest goes through the buffer, which also finds a real end of file
+        copied = n;
+    }
+
+    let (mut src, mut dst) = (src, dst);
+    src.seek(SeekFrom::Start(offset + copied))?;
+    dst.seek(SeekFrom::Start(offset + copied))?;
+    let mut buf = vec![0; BUF_SIZE];
+    while copied < len {
+        let max = (len - copied).min(BUF_SIZE as u64) as usize;
+        let n = match src.read(&mut buf[..max]) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        dst.write_all(&buf[..n])?;
+        copied += n as u64;
+    }
+    Ok(copied)
+}
+
+pub(crate) fn copy_parallel<P, Q>(pool: &FsPool, from: P, to: Q, opts: CopyOptions) -> FsFuture<u64>
+where
+    P: AsRef<Path> + Send + 'static,
+    Q: AsRef<Path> + Send + 'static,
+{
+    let segments = opts.segments.or(pool.threads).unwrap_or(DEFAULT_SEGMENTS) as u64;
+    let to = to.as_ref().to_owned();
+    let (tx, rx) = oneshot::channel();
+
+    let pool2 = pool.clone();
+    let (metrics, rate) = (pool.metrics.clone(), pool.rate.clone());
+    let target = to.clone();
+    let fut = pool
+        .exec(move || open_parallel(from.as_ref(), &target))
+        .and_then(move |(src, dst, len)| {
+            let size = cmp::max(len.div_ceil(segments), 1);
+            let stop = Arc::new(AtomicBool::new(false));
+            let copies = (0..segments)
+                .map(|i| i * size)
+                .take_while(|&start| start < len)
+                .map(|start| {
+                    let (src, dst) = (src.clone(), dst.clone());
+                    let (stop, stop_others) = (stop.clone(), stop.clone());
+                    let (metrics, rate) = (metrics.clone(), rate.clone());
+                    let end = cmp::min(start + size, len);
+                    pool2
+                        .exec(move || {
+                            let meter = Meter::new(&metrics, rate.as_deref());
+                            copy_segment(&src, &dst, start, end, &stop, meter)
+                        })
+                        .then(move |res| {
+                            if res.is_err() {
+                                stop_others.store(true, Ordering::SeqCst);
+                            }
+                            Ok::<_, ()>(res)
+                        })
+                })
+                .collect::<Vec<_>>();
+
+            // wait for every segment, so that none still writes to the copy
+            // once it is removed
+            join_all(copies).then(move |results| {
+                let mut copied = 0;
+                for res in results.unwrap() {
+                    match res {
+                        Ok(n) => copied += n,
+                        Err(e) => {
+                            if !opts.keep_partial {
+                                remove_partial(&dst, &to);
+                            }
+                            return Err(e);
+                        }
+                    }
+                }
+                Ok(copied)
+            })
+        })
+        .then(|res| tx.send(res).map_err(|_| ()));
+
+    if let Err(e) = pool.execute(Box::new(fut)) {
+        return ::failed(e);
+    }
+
+    ::fs(rx)
+}
+
+fn open_parallel(from: &Path, to: &Path) -> io::Result<(Arc<File>, Arc<File>, u64)> {
+    let src = File::open(from)?;
+    let metadata = src.metadata()?;
+    if !metadata.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the source path is not a file",
+        ));
+    }
+    let dst = File::create(to)?;
+    dst.set_permissions(metadata.permissions())?;
+    let len = metadata.len();
+    let res = sys::preallocate(&dst, len).and_then(|()| dst.set_len(len));
+    if let Err(e) = res {
+        remove_partial(&dst, to);
+        return Err(e);
+    }
+    Ok((Arc::new(src), Arc::new(dst), len))
+}
+
+/// Removes a partial copy, unless the path is something other than a file,
+/// like a device.
+fn remove_partial(dst: &File, to: &Path) {
+    if dst
+        .metadata()
+        .map(|metadata| metadata.is_file())
+        .unwrap_or(false)
+    {
+        let _ = fs::remove_file(to);
+    }
+}
+
+/// Copies the bytes from `start` to `end` of `src` to `dst`, until `stop` is
+/// set by another segment failing.
+fn copy_segment(
+    src: &File,
+    dst: &File,
+    start: u64,
+    end: u64,
+    stop: &AtomicBool,
+    meter: Meter,
+) -> io::Result<u64> {
+    let mut buf = vec![0; cmp::min(SEGMENT_BUF_SIZE as u64, end - start) as usize];
+    let mut pos = start;
+    while pos < end && !stop.load(Ordering::SeqCst) {
+        let max = cmp::min(buf.len() as u64, end - pos) as usize;
+        let n = match sys::read_at(src, &mut buf[..max], pos) {
+            // the file shrank while copying it
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        meter.copied(n as u64);
+        sys::write_all_at(dst, &buf[..n], pos)?;
+        pos += n as u64;
+    }
+    Ok(pos - start)
+}
+
+/// The progress of a copy, yielded by `FsProgress`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CopyProgress {
+    copied: u64,
+    total: Option<u64>,
+}
+
+impl CopyProgress {
+    /// The number of bytes of the file copied so far.
+    ///
+    /// For a sparse copy, this includes the holes that were skipped.
+    pub fn copied(&self) -> u64 {
+        self.copied
+    }
+
+    /// The length of the file being copied, once it is known.
+    pub fn total(&self) -> Option<u64> {
+        self.total
+    }
+}
+
+/// A `Stream` of the progress of a copy.
+///
+/// Created by `FsPool::copy_with_progress`. Only the latest progress is kept
+/// until the stream is polled, so the copy never waits for it. The stream
+/// ends once the copy has finished, whether it succeeded or not.
+pub struct FsProgress {
+    shared: Arc<Mutex<Shared>>,
+}
+
+struct Shared {
+    latest: Option<CopyProgress>,
+    done: bool,
+    task: Option<Task>,
+}
+
+impl Stream for FsProgress {
+    type Item = CopyProgress;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        let mut shared = self.shared.lock().unwrap();
+        if let Some(progress) = shared.latest.take() {
+            return Ok(Async::Ready(Some(progress)));
+        }
+        if shared.done {
+            return Ok(Async::Ready(None));
+        }
+        shared.task = Some(task::current());
+        Ok(Async::NotReady)
+    }
+}
+
+impl fmt::Debug for FsProgress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsProgress").finish()
+    }
+}
+
+/// Reports the progress of a copy to an `FsProgress`, if there is one.
+pub(crate) struct Reporter {
+    shared: Option<Arc<Mutex<Shared>>>,
+    total: Option<u64>,
+    bytes: u64,
+    interval: Option<Duration>,
+    last: u64,
+    last_time: Instant,
+}
+
+impl Reporter {
+    pub(crate) fn none() -> Reporter {
+        Reporter {
+            shared: None,
+            total: None,
+            bytes: u64::MAX,
+            interval: None,
+            last: 0,
+            last_time: Instant::now(),
+        }
+    }
+
+    /// How many bytes to copy between checking whether to report progress.
+    fn step(&self) -> u64 {
+        match self.shared {
+            Some(_) => cmp::min(self.bytes, PROGRESS_STEP),
+            None => u64::MAX,
+        }
+    }
+
+    fn update(&mut self, copied: u64) {
+        if self.shared.is_none() {
+            return;
+        }
+        let due = copied.saturating_sub(self.last) >= self.bytes
+            || self
+                .interval
+                .is_some_and(|interval| self.last_time.elapsed() >= interval);
+        if due {
+            self.report(copied);
+        }
+    }
+
+    fn finish(&mut self, copied: u64) {
+        if self.shared.is_some() {
+            self.report(copied);
+        }
+    }
+
+    fn report(&mut self, copied: u64) {
+        self.last = copied;
+        self.last_time = Instant::now();
+        let progress = CopyProgress {
+            copied,
+            total: self.total,
+        };
+        self.with_shared(|shared| shared.latest = Some(progress));
+    }
+
+    fn with_shared<F: FnOnce(&mut Shared)>(&self, f: F) {
+        if let Some(ref shared) = self.shared {
+            let mut shared = shared.lock().unwrap();
+            f(&mut shared);
+            if let Some(task) = shared.task.take() {
+                task.notify();
+            }
+        }
+    }
+}
+
+impl Drop for Reporter {
+    fn drop(&mut self) {
+        self.with_shared(|shared| shared.done = true);
+    }
+}
+
+pub(crate) fn copy_with_progress<P, Q>(
+    pool: &FsPool,
+    from: P,
+    to: Q,
+    opts: CopyOptions,
+) -> (FsFuture<u64>, FsProgress)
+where
+    P: AsRef<Path> + Send + 'static,
+    Q: AsRef<Path> + Send + 'static,
+{
+    let shared = Arc::new(Mutex::new(Shared {
+        latest: None,
+        done: false,
+        task: None,
+    }));
+    let mut reporter = Reporter {
+        shared: Some(shared.clone()),
+        bytes: opts.progress_bytes,
+        interval: opts.progress_interval,
+        ..Reporter::none()
+    };
+
+    let (metrics, rate) = (pool.metrics.clone(), pool.rate.clone());
+    let fut = pool.exec(move || {
+        let meter = Meter::new(&metrics, rate.as_deref());
+        copy(from.as_ref(), to.as_ref(), &opts, &mut reporter, meter)
+    });
+    (fut, FsProgress { shared })
+}
+
+/// Options for how to copy a directory tree.
+///
+/// The default is to recreate symbolic links, and to stop at the first error.
+#[derive(Clone, Debug, Default)]
+pub struct CopyDirOptions {
+    copy: CopyOptions,
+    follow_symlinks: bool,
+    continue_on_error: bool,
+}
+
+impl CopyDirOptions {
+    /// The `CopyOptions` to copy each file with.
+    ///
+    /// Default is `CopyOptions::default()`.
+    pub fn with_copy_options(mut self, copy: CopyOptions) -> Self {
+        self.copy = copy;
+        self
+    }
+
+    /// Whether to copy whatever symbolic links point to, instead of
+    /// recreating the links themselves.
+    ///
+    /// A link to a directory that contains it is an error, instead of being
+    /// followed forever.
+    ///
+    /// Default is `false`.
+    pub fn follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Whether to keep copying the rest of the tree when copying an entry
+    /// fails, collecting the failures in the summary.
+    ///
+    /// Default is `false`, resolving with the first error.
+    pub fn continue_on_error(mut self, continue_on_error: bool) -> Self {
+        self.continue_on_error = continue_on_error;
+        self
+    }
+}
+
+/// What was copied by `FsPool::copy_dir_all`.
+#[derive(Debug, Default)]
+pub struct CopyDirSummary {
+    files: u64,
+    bytes: u64,
+    dirs: u64,
+    failures: Vec<(PathBuf, io::Error)>,
+}
+
+impl CopyDirSummary {
+    /// The number of files copied.
+    pub fn files_copied(&self) -> u64 {
+        self.files
+    }
+
+    /// The number of bytes copied, over all files.
+    pub fn bytes_copied(&self) -> u64 {
+        self.bytes
+    }
+
+    /// The number of directories created, including the target directory if
+    /// it didn't exist yet.
+    pub fn dirs_created(&self) -> u64 {
+        self.dirs
+    }
+
+    /// The paths that failed to be copied along with their errors, with
+    /// `CopyDirOptions::continue_on_error`.
+    pub fn failures(&self) -> &[(PathBuf, io::Error)] {
+        &self.failures
+    }
+}
+
+pub(crate) fn copy_dir_all(
+    from: &Path,
+    to: &Path,
+    opts: &CopyDirOptions,
+    meter: Meter,
+) -> io::Result<CopyDirSummary> {
+    let mut summary = CopyDirSummary::default();
+    let from_dir = fs::canonicalize(from).map_err(|e| ::path_error(from, e))?;
+    create_dir(to, &mut summary).map_err(|e| ::path_error(to, e))?;
+    let to_dir = fs::canonicalize(to).map_err(|e| ::path_error(to, e))?;
+    if to_dir.starts_with(&from_dir) {
+        if summary.dirs > 0 {
+            let _ = fs::remove_dir(to);
+        }
+        return Err(::path_error(
+            to,
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot copy a directory into itself",
+            ),
+        ));
+    }
+
+    let mut copier = DirCopier {
+        opts,
+        meter,
+        summary,
+        ancestors: vec![from_dir],
+    };
+    copier.copy_dir(from, to)?;
+    Ok(copier.summary)
+}
+
+struct DirCopier<'a> {
+    opts: &'a CopyDirOptions,
+    meter: Meter<'a>,
+    summary: CopyDirSummary,
+    /// The canonical paths of the directories being copied, to notice a
+    /// followed link back into one of them.
+    ancestors: Vec<PathBuf>,
+}
+
+impl<'a> DirCopier<'a> {
+    fn copy_dir(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        let entries = match fs::read_dir(from) {
+            Ok(entries) => entries,
+            Err(e) => return self.fail(from, e),
+        };
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    self.fail(from, e)?;
+                    continue;
+                }
+            };
+            let (src, dst) = (entry.path(), to.join(entry.file_name()));
+            let res = entry
+                .file_type()
+                .and_then(|file_type| self.copy_entry(&src, &dst, file_type));
+            match res {
+                Ok(Some(dir)) => {
+                    // any error from within was already handled
+                    self.ancestors.push(dir);
+                    let res = self.copy_dir(&src, &dst);
+                    self.ancestors.pop();
+                    res?;
+                }
+                Ok(None) => (),
+                Err(e) => self.fail(&src, e)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies a single entry, returning the canonical path of a directory
+    /// that was created for it and is to be copied next.
+    fn copy_entry(
+        &mut self,
+        src: &Path,
+        dst: &Path,
+        file_type: fs::FileType,
+    ) -> io::Result<Option<PathBuf>> {
+        let file_type = if file_type.is_symlink() {
+            if !self.opts.follow_symlinks {
+                ::symlink(&fs::read_link(src)?, dst)?;
+                return Ok(None);
+            }
+            fs::metadata(src)?.file_type()
+        } else {
+            file_type
+        };
+
+        if file_type.is_dir() {
+            let dir = fs::canonicalize(src)?;
+            if self.ancestors.contains(&dir) {
+                return Err(io::Error::other(
+                    "symbolic link points to a directory that contains it",
+                ));
+            }
+            create_dir(dst, &mut self.summary)?;
+            return Ok(Some(dir));
+        }
+        if file_type.is_file() {
+            let n = copy(src, dst, &self.opts.copy, &mut Reporter::none(), self.meter)?;
+            self.summary.files += 1;
+            self.summary.bytes += n;
+        }
+        // anything else, like a socket, can't be copied as a file
+        Ok(None)
+    }
+
+    /// Handles the failure to copy `path`, only returning an error if the
+    /// whole copy is to stop.
+    fn fail(&mut self, path: &Path, e: io::Error) -> io::Result<()> {
+        if self.opts.continue_on_error {
+            self.summary.failures.push((path.to_owned(), e));
+            Ok(())
+        } else {
+            Err(::path_error(path, e))
+        }
+    }
+}
+
+/// Creates a directory, unless it already exists.
+fn create_dir(path: &Path, summary: &mut CopyDirSummary) -> io::Result<()> {
+    match fs::create_dir(path) {
+        Ok(()) => {
+            summary.dirs += 1;
+            Ok(())
+        }
+        Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists && path.is_dir() => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Options for how to move a file.
+///
+/// The default is to copy the file when it can't be renamed across devices.
+#[derive(Clone, Debug, Default)]
+pub struct MoveOptions {
+    rename_only: bool,
+}
+
+impl MoveOptions {
+    /// Whether to only ever rename the file, failing across devices instead
+    /// of falling back to a copy.
+    ///
+    /// A rename either fully happens or not at all, while a copy leaves both
+    /// files in place for a moment after the destination appears.
+    ///
+    /// Default is `false`.
+    pub fn rename_only(mut self, rename_only: bool) -> Self {
+        self.rename_only = rename_only;
+        self
+    }
+}
+
+pub(crate) fn move_file(
+    from: &Path,
+    to: &Path,
+    opts: &MoveOptions,
+    meter: Meter,
+) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(ref e) if !opts.rename_only && sys::is_cross_device(e) => (),
+        res => return res,
+    }
+
+    // copy next to the destination, so that it only appears when complete
+    let tmp = temp_name(to);
+    let opts = CopyOptions::default();
+    let res = copy(from, &tmp, &opts, &mut Reporter::none(), meter)
+        .and_then(|_| ::open_for_sync(&tmp)?.sync_all())
+        .and_then(|()| fs::rename(&tmp, to));
+    if let Err(e) = res {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    fs::remove_file(from)
+}
+
+/// Returns a hidden path in the same directory as `path`, that another move
+/// isn't using.
+fn temp_name(path: &Path) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    path.with_file_name(format!(".{}.{}-{}.tmp", name, process::id(), n))
+}
@@ -0,0 +1,134 @@
+use std::cmp;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use futures::future::join_all;
+use futures::sync::oneshot;
+use futures::Future;
+
+use FsFuture;
+use FsPool;
+
+const MIN_CHUNK: usize = 64;
+const DEFAULT_CHUNKS: usize = 4;
+
+/// Options for how to delete files.
+///
+/// The default is to report a file that doesn't exist as an error.
+#[derive(Clone, Debug, Default)]
+pub struct DeleteOptions {
+    ignore_missing: bool,
+}
+
+impl DeleteOptions {
+    /// Whether a file that doesn't exist counts as deleted, instead of
+    /// failing with an error of kind `NotFound`.
+    ///
+    /// Default is `false`.
+    pub fn ignore_missing(mut self, ignore_missing: bool) -> Self {
+        self.ignore_missing = ignore_missing;
+        self
+    }
+}
+
+/// What happened when deleting many files, from `FsPool::delete_all`.
+#[derive(Debug, Default)]
+pub struct DeleteSummary {
+    deleted: u64,
+    missing: u64,
+    errors: Vec<(PathBuf, io::Error)>,
+}
+
+impl DeleteSummary {
+    /// The number of files that were deleted.
+    pub fn deleted(&self) -> u64 {
+        self.deleted
+    }
+
+    /// The number of files that didn't exist.
+    ///
+    /// Unless `DeleteOptions::ignore_missing` is set, these are also in
+    /// `errors`.
+    pub fn missing(&self) -> u64 {
+        self.missing
+    }
+
+    /// The paths that failed to be deleted along with their errors, in the
+    /// order they were passed.
+    pub fn errors(&self) -> &[(PathBuf, io::Error)] {
+        &self.errors
+    }
+
+    fn merge(&mut self, other: DeleteSummary) {
+        self.deleted += other.deleted;
+        self.missing += other.missing;
+        self.errors.extend(other.errors);
+    }
+}
+
+pub(crate) fn delete_all(
+    pool: &FsPool,
+    paths: Vec<PathBuf>,
+    opts: DeleteOptions,
+) -> FsFuture<DeleteSummary> {
+    let threads = pool.threads.unwrap_or(DEFAULT_CHUNKS);
+    let size = cmp::max(paths.len().div_ceil(threads), MIN_CHUNK);
+
+    let mut paths = paths.into_iter();
+    let mut chunks = Vec::new();
+    loop {
+        let chunk = paths.by_ref().take(size).collect::<Vec<_>>();
+        if chunk.is_empty() {
+            break;
+        }
+        let ignore_missing = opts.ignore_missing;
+        chunks.push(pool.exec(move || Ok(delete_chunk(chunk, ignore_missing))));
+    }
+
+    let (tx, rx) = oneshot::channel();
+    let fut = join_all(chunks)
+        .map(|summaries| {
+            let mut summary = DeleteSummary::default();
+            for chunk in summaries {
+                summary.merge(chunk);
+            }
+            summary
+        })
+        .then(|res| tx.send(res).map_err(|_| ()));
+    if let Err(e) = pool.execute(Box::new(fut)) {
+        return ::failed(e);
+    }
+
+    ::fs(rx)
+}
+
+fn delete_chunk(paths: Vec<PathBuf>, ignore_missing: bool) -> DeleteSummary {
+    let mut summary = DeleteSummary::default();
+    for path in paths {
+        match fs::remove_file(&path) {
+            Ok(()) => summary.deleted += 1,
+            Err(e) => {
+                if e.kind() == io::ErrorKind::NotFound {
+                    summary.missing += 1;
+                    if ignore_missing {
+                        continue;
+                    }
+                }
+                summary.errors.push((path, e));
+            }
+        }
+    }
+    summary
+}
+
+pub(crate) fn collect<I>(paths: I) -> Vec<PathBuf>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    paths
+        .into_iter()
+        .map(|path| path.as_ref().to_owned())
+        .collect()
+}
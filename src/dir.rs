@@ -0,0 +1,270 @@
+use std::collections::VecDeque;
+use std::ffi::OsString;
+use std::fs::{self, FileType, Metadata, ReadDir};
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::{fmt, mem};
+
+use futures::future::lazy;
+use futures::sync::oneshot;
+use futures::{Async, Future, Poll, Stream};
+
+use FsFuture;
+use FsPool;
+
+const BATCH_SIZE: usize = 64;
+/// The most entries a batch skips with a filter, before returning.
+const MAX_SCANNED: usize = BATCH_SIZE * 64;
+
+type Filter = Arc<dyn Fn(&FsDirEntry) -> bool + Send + Sync>;
+
+/// Options for how to read a directory.
+///
+/// The default is to only read the names and file types of the entries.
+#[derive(Clone, Default)]
+pub struct ReadDirOptions {
+    metadata: bool,
+    filter: Option<Filter>,
+}
+
+impl ReadDirOptions {
+    /// Whether to also get the metadata of each entry on the pool, along
+    /// with the batch it is read in.
+    ///
+    /// An entry whose metadata can't be read, like one that was removed
+    /// right after being listed, is still yielded, with the error available
+    /// from `FsDirEntry::metadata_error`.
+    ///
+    /// Default is `false`.
+    pub fn with_metadata(mut self, metadata: bool) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Only yield the entries for which `filter` returns `true`.
+    ///
+    /// The filter runs on the pool as the entries are read, so skipped
+    /// entries are never sent to the stream. It can look at the path, file
+    /// name and file type of an entry, while its metadata isn't read yet. If
+    /// the filter panics, the stream fails with an error instead.
+    ///
+    /// Default is to yield every entry.
+    pub fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&FsDirEntry) -> bool + Send + Sync + 'static,
+    {
+        self.filter = Some(Arc::new(filter));
+        self
+    }
+}
+
+impl fmt::Debug for ReadDirOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ReadDirOptions")
+            .field("metadata", &self.metadata)
+            .field("filter", &self.filter.is_some())
+            .finish()
+    }
+}
+
+pub(crate) fn new<P>(pool: &FsPool, path: P, opts: ReadDirOptions) -> FsDirStream
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    FsDirStream {
+        path: path.as_ref().to_owned(),
+        opts,
+        entries: VecDeque::new(),
+        error: None,
+        pool: pool.clone(),
+        state: State::Init,
+    }
+}
+
+/// A `Stream` of the entries in a directory.
+///
+/// Entries are fetched from the pool in batches.
+pub struct FsDirStream {
+    path: PathBuf,
+    opts: ReadDirOptions,
+    entries: VecDeque<FsDirEntry>,
+    error: Option<io::Error>,
+    pool: FsPool,
+    state: State,
+}
+
+/// An entry in a directory, yielded by `FsDirStream`.
+#[derive(Debug)]
+pub struct FsDirEntry {
+    path: PathBuf,
+    file_name: OsString,
+    file_type: FileType,
+    metadata: Option<io::Result<Metadata>>,
+}
+
+impl FsDirEntry {
+    /// The full path of this entry.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The bare file name of this entry, without any leading path.
+    pub fn file_name(&self) -> &OsString {
+        &self.file_name
+    }
+
+    /// The file type of this entry.
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    /// The metadata of this entry, if it was read with
+    /// `ReadDirOptions::with_metadata`.
+    ///
+    /// Like `std::fs::DirEntry::metadata`, symbolic links aren't followed.
+    pub fn metadata(&self) -> Option<&Metadata> {
+        match self.metadata {
+            Some(Ok(ref metadata)) => Some(metadata),
+            _ => None,
+        }
+    }
+
+    /// The error reading the metadata of this entry, if it failed with
+    /// `ReadDirOptions::with_metadata`.
+    pub fn metadata_error(&self) -> Option<&io::Error> {
+        match self.metadata {
+            Some(Err(ref e)) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// The entries read, along with an error that ends the stream, and whether
+/// the end of the directory has been reached.
+type Batch = (ReadDir, Vec<FsDirEntry>, Option<io::Error>, bool);
+
+enum State {
+    Init,
+    Working(FsFuture<Batch>),
+    Ready(ReadDir),
+    Eof,
+    Swapping,
+}
+
+impl FsDirStream {
+    fn spawn<F>(&mut self, f: F)
+    where
+        F: FnOnce() -> io::Result<Batch> + Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+
+        let fut = Box::new(lazy(move || {
+            let res = super::refused().and_then(|()| f());
+            tx.send(res).map_err(|_| ())
+        }));
+
+        self.state = match self.pool.execute(fut) {
+            Ok(()) => State::Working(super::fs(rx)),
+            Err(e) => State::Working(super::failed(e)),
+        };
+    }
+}
+
+impl Stream for FsDirStream {
+    type Item = FsDirEntry;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            if let Some(entry) = self.entries.pop_front() {
+                return Ok(Async::Ready(Some(entry)));
+            }
+            if let Some(e) = self.error.take() {
+                self.state = State::Eof;
+                return Err(e);
+            }
+
+            match mem::replace(&mut self.state, State::Swapping) {
+                State::Init => {
+                    let path = self.path.clone();
+                    let opts = self.opts.clone();
+                    self.spawn(move || read_batch(fs::read_dir(path)?, &opts));
+                }
+                State::Working(mut rx) => {
+                    let polled = rx.poll();
+                    self.state = State::Working(rx);
+                    if polled.is_err() {
+                        // the directory was lost along with the task
+                        self.state = State::Eof;
+                    }
+                    let (dir, entries, error, eof) = try_ready!(polled);
+
+                    if eof && error.is_none() {
+                        self.state = State::Eof;
+                    } else {
+                        self.state = State::Ready(dir);
+                    }
+                    self.entries.extend(entries);
+                    self.error = error;
+                }
+                State::Ready(dir) => {
+                    let opts = self.opts.clone();
+                    self.spawn(move || read_batch(dir, &opts));
+                }
+                State::Eof => {
+                    self.state = State::Eof;
+                    return Ok(Async::Ready(None));
+                }
+                State::Swapping => unreachable!(),
+            }
+        }
+    }
+}
+
+impl fmt::Debug for FsDirStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsDirStream")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+fn read_batch(mut dir: ReadDir, opts: &ReadDirOptions) -> io::Result<Batch> {
+    let mut entries = Vec::with_capacity(BATCH_SIZE);
+    let mut scanned = 0;
+    while entries.len() < BATCH_SIZE && scanned < MAX_SCANNED {
+        let entry = match dir.next() {
+            Some(Ok(entry)) => entry,
+            Some(Err(e)) => return Ok((dir, entries, Some(e), false)),
+            None => return Ok((dir, entries, None, true)),
+        };
+        scanned += 1;
+        let file_type = match entry.file_type() {
+            Ok(file_type) => file_type,
+            Err(e) => return Ok((dir, entries, Some(e), false)),
+        };
+        let mut dir_entry = FsDirEntry {
+            path: entry.path(),
+            file_name: entry.file_name(),
+            file_type,
+            metadata: None,
+        };
+        if let Some(ref filter) = opts.filter {
+            match panic::catch_unwind(AssertUnwindSafe(|| filter(&dir_entry))) {
+                Ok(true) => (),
+                Ok(false) => continue,
+                Err(_) => {
+                    let e = io::Error::other("read_dir filter panicked");
+                    return Ok((dir, entries, Some(e), false));
+                }
+            }
+        }
+        if opts.metadata {
+            dir_entry.metadata = Some(entry.metadata());
+        }
+        entries.push(dir_entry);
+    }
+    Ok((dir, entries, None, false))
+}
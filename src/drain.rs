@@ -0,0 +1,109 @@
+use std::cell::Cell;
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use futures::sync::oneshot;
+use futures::{Future, Poll};
+
+use FsFuture;
+
+thread_local! {
+    /// Whether a task of a pool is being polled on this thread.
+    static IN_TASK: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Keeps count of the tasks of a pool, and its clones, for `FsPool::close`.
+#[derive(Default)]
+pub(crate) struct Tasks {
+    closed: AtomicBool,
+    active: AtomicUsize,
+    /// The tasks that haven't been polled yet.
+    queued: AtomicUsize,
+    drains: Mutex<Vec<oneshot::Sender<io::Result<()>>>>,
+}
+
+impl Tasks {
+    /// Whether a new task may be spawned.
+    ///
+    /// Once closed, a task that is already running may still spawn the work
+    /// it is made of, like the files of a directory being copied.
+    pub(crate) fn accepts(&self) -> bool {
+        !self.closed.load(Ordering::SeqCst) || IN_TASK.with(Cell::get)
+    }
+
+    /// Stops accepting new tasks, and returns a future that resolves once
+    /// every task has finished.
+    pub(crate) fn close(&self) -> FsFuture<()> {
+        let (tx, rx) = oneshot::channel();
+        self.closed.store(true, Ordering::SeqCst);
+
+        let mut drains = self.drains.lock().unwrap();
+        if self.active.load(Ordering::SeqCst) == 0 {
+            let _ = tx.send(Ok(()));
+        } else {
+            drains.push(tx);
+        }
+        ::fs(rx)
+    }
+
+    /// The number of tasks waiting to be polled for the first time.
+    pub(crate) fn queued(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    fn finish(&self) {
+        if self.active.fetch_sub(1, Ordering::SeqCst) == 1 && self.closed.load(Ordering::SeqCst) {
+            for tx in self.drains.lock().unwrap().drain(..) {
+                let _ = tx.send(Ok(()));
+            }
+        }
+    }
+}
+
+/// Counts `fut` as an active task of the pool, until it is dropped.
+pub(crate) fn track<F>(tasks: &Arc<Tasks>, fut: F) -> Tracked<F> {
+    tasks.active.fetch_add(1, Ordering::SeqCst);
+    tasks.queued.fetch_add(1, Ordering::Relaxed);
+    Tracked {
+        fut: Some(fut),
+        tasks: tasks.clone(),
+        polled: false,
+    }
+}
+
+/// A task on the pool, which is finished once dropped, whether it has
+/// completed or the executor dropped it.
+pub(crate) struct Tracked<F> {
+    fut: Option<F>,
+    tasks: Arc<Tasks>,
+    polled: bool,
+}
+
+impl<F: Future> Future for Tracked<F> {
+    type Item = F::Item;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<F::Item, F::Error> {
+        if !self.polled {
+            self.polled = true;
+            self.tasks.queued.fetch_sub(1, Ordering::Relaxed);
+        }
+        let outer = IN_TASK.with(|in_task| in_task.replace(true));
+        let polled = self.fut.as_mut().expect("polled after drop").poll();
+        IN_TASK.with(|in_task| in_task.set(outer));
+        polled
+    }
+}
+
+impl<F> Drop for Tracked<F> {
+    fn drop(&mut self) {
+        // the task may clean up after itself when dropped, like removing a
+        // temporary file
+        drop(self.fut.take());
+        if !self.polled {
+            self.tasks.queued.fetch_sub(1, Ordering::Relaxed);
+        }
+        self.tasks.finish();
+    }
+}
@@ -0,0 +1,131 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::{fmt, io};
+
+/// The operation that failed, in an `FsError`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FsOperation {
+    /// Reading a file, including opening and seeking it.
+    Read,
+    /// Writing a file, including opening, syncing and closing it.
+    Write,
+    /// Deleting a file or directory.
+    Delete,
+    /// Getting or setting the metadata of a file, or creating, renaming or
+    /// resolving the names and links that lead to it.
+    Metadata,
+}
+
+impl fmt::Display for FsOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            FsOperation::Read => "read",
+            FsOperation::Write => "write",
+            FsOperation::Delete => "delete",
+            FsOperation::Metadata => "metadata",
+        })
+    }
+}
+
+/// An error of an operation on a file, along with the path of the file.
+///
+/// Streams and sinks fail with an `io::Error` that wraps this, if created
+/// with `ReadOptions::contextual_errors` or `WriteOptions::contextual_errors`,
+/// as does an `FsFuture` with `FsFuture::with_context`. The `io::Error` keeps
+/// the kind of the original error, and `from_io` gets this back out of it.
+#[derive(Debug)]
+pub struct FsError {
+    operation: FsOperation,
+    path: PathBuf,
+    inner: io::Error,
+}
+
+impl FsError {
+    /// Creates an error of `operation` on the file at `path`.
+    pub fn new<P>(operation: FsOperation, path: P, inner: io::Error) -> FsError
+    where
+        P: Into<PathBuf>,
+    {
+        FsError {
+            operation,
+            path: path.into(),
+            inner,
+        }
+    }
+
+    /// Returns the `FsError` that `e` wraps, if any.
+    pub fn from_io(e: &io::Error) -> Option<&FsError> {
+        e.get_ref().and_then(|inner| inner.downcast_ref())
+    }
+
+    /// The operation that failed.
+    pub fn operation(&self) -> FsOperation {
+        self.operation
+    }
+
+    /// The path of the file the operation failed on.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The kind of the original error.
+    pub fn kind(&self) -> io::ErrorKind {
+        self.inner.kind()
+    }
+
+    /// The original error.
+    pub fn get_ref(&self) -> &io::Error {
+        &self.inner
+    }
+
+    /// Returns the original error.
+    pub fn into_inner(self) -> io::Error {
+        self.inner
+    }
+}
+
+impl fmt::Display for FsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {}: {}",
+            self.operation,
+            self.path.display(),
+            self.inner
+        )
+    }
+}
+
+impl Error for FsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.inner)
+    }
+}
+
+impl From<FsError> for io::Error {
+    fn from(e: FsError) -> io::Error {
+        io::Error::new(e.kind(), e)
+    }
+}
+
+/// The operation and path to wrap the errors of a stream, sink or future in.
+#[derive(Clone, Debug)]
+pub(crate) struct Context {
+    operation: FsOperation,
+    path: Arc<PathBuf>,
+}
+
+impl Context {
+    pub(crate) fn new(operation: FsOperation, path: Arc<PathBuf>) -> Context {
+        Context { operation, path }
+    }
+
+    /// Wraps `e` in an `FsError`, unless it already is one.
+    pub(crate) fn wrap(&self, e: io::Error) -> io::Error {
+        if FsError::from_io(&e).is_some() {
+            return e;
+        }
+        FsError::new(self.operation, &**self.path, e).into()
+    }
+}
@@ -0,0 +1,105 @@
+use std::fs::File;
+use std::sync::Arc;
+use std::{fmt, io};
+
+use bytes::Bytes;
+
+use sys;
+use FsFuture;
+use FsOperation;
+use FsPool;
+
+/// A handle to an open file, to read and write at given offsets on the pool.
+///
+/// Created by `FsPool::open`. Clones share the same file, and since reads
+/// and writes at an offset don't move a shared cursor, any number of them
+/// may run at once. The file is closed once every clone has been dropped.
+#[derive(Clone)]
+pub struct FsFile {
+    file: Arc<File>,
+    pool: FsPool,
+}
+
+impl FsFile {
+    pub(crate) fn new(pool: &FsPool, file: File) -> FsFile {
+        FsFile {
+            file: Arc::new(file),
+            pool: pool.clone(),
+        }
+    }
+
+    /// The opened file.
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// Returns a `Future` that resolves with `len` bytes of the file, starting
+    /// at the byte `offset`.
+    ///
+    /// Fewer bytes are read only if the end of the file is reached first.
+    pub fn read_at(&self, offset: u64, len: usize) -> FsFuture<Bytes> {
+        let file = self.file.clone();
+        let metrics = self.pool.metrics.clone();
+        let rate = self.pool.rate.clone();
+        self.pool.exec_as(Some(FsOperation::Read), move || {
+            let mut buf = vec![0; len];
+            let mut filled = 0;
+            while filled < len {
+                let offset = offset + filled as u64;
+                match sys::read_at(&file, &mut buf[filled..], offset) {
+                    Ok(0) => break,
+                    Ok(n) => filled += n,
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                    Err(e) => return Err(e),
+                }
+            }
+            buf.truncate(filled);
+            metrics.read(filled as u64);
+            if let Some(rate) = rate {
+                rate.take(filled as u64);
+            }
+            Ok(Bytes::from(buf))
+        })
+    }
+
+    /// Returns a `Future` that resolves with the number of bytes written,
+    /// once all of `data` has been written at the byte `offset` of the file.
+    ///
+    /// Opening the file in append mode makes writes go to the end of the
+    /// file instead, on some platforms.
+    pub fn write_at(&self, offset: u64, data: Bytes) -> FsFuture<usize> {
+        let file = self.file.clone();
+        let metrics = self.pool.metrics.clone();
+        let rate = self.pool.rate.clone();
+        self.pool.exec_as(Some(FsOperation::Write), move || {
+            if let Some(rate) = rate {
+                rate.take(data.len() as u64);
+            }
+            sys::write_all_at(&file, &data, offset)?;
+            metrics.written(data.len() as u64);
+            Ok(data.len())
+        })
+    }
+
+    /// Returns a `Future` that resolves with the current length of the file.
+    pub fn len(&self) -> FsFuture<u64> {
+        let file = self.file.clone();
+        self.pool.exec_as(Some(FsOperation::Metadata), move || {
+            Ok(file.metadata()?.len())
+        })
+    }
+
+    /// Returns a `Future` that resolves once the contents and metadata of the
+    /// file have been flushed to disk.
+    pub fn sync_all(&self) -> FsFuture<()> {
+        let file = self.file.clone();
+        self.pool
+            .exec_as(Some(FsOperation::Write), move || file.sync_all())
+    }
+}
+
+impl fmt::Debug for FsFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsFile").field("file", &self.file).finish()
+    }
+}
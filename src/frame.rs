@@ -0,0 +1,237 @@
+use std::{cmp, fmt, io, str};
+
+use bytes::{Bytes, BytesMut};
+use futures::{Async, Poll, Stream};
+
+use FsReadStream;
+
+pub(crate) fn split(stream: FsReadStream, delimiter: u8) -> FsSplit {
+    FsSplit {
+        inner: stream,
+        delimiter,
+        max_frame_len: None,
+        buffer: BytesMut::new(),
+        scanned: 0,
+        eof: false,
+        done: false,
+    }
+}
+
+pub(crate) fn chunks_exact(stream: FsReadStream, size: usize) -> FsChunksExact {
+    assert!(size > 0, "chunk size must be larger than 0");
+    FsChunksExact {
+        inner: stream,
+        size,
+        error_on_partial: false,
+        chunk: Bytes::new(),
+        partial: BytesMut::new(),
+        eof: false,
+    }
+}
+
+pub(crate) fn lines(stream: FsReadStream) -> FsLines {
+    FsLines {
+        inner: split(stream, b'\n'),
+    }
+}
+
+/// A `Stream` of the frames between a delimiter in a target file.
+///
+/// Created by `FsReadStream::split`.
+pub struct FsSplit {
+    inner: FsReadStream,
+    delimiter: u8,
+    max_frame_len: Option<usize>,
+    buffer: BytesMut,
+    scanned: usize,
+    eof: bool,
+    done: bool,
+}
+
+impl FsSplit {
+    /// The maximum length of a frame, not counting the delimiter.
+    ///
+    /// A longer frame is an error of kind `InvalidData`, instead of being
+    /// buffered until its end is found. The stream ends after that error.
+    ///
+    /// Default is no maximum.
+    pub fn max_frame_len(mut self, max_frame_len: usize) -> Self {
+        self.max_frame_len = Some(max_frame_len);
+        self
+    }
+
+    fn check_len(&mut self, len: usize) -> io::Result<()> {
+        match self.max_frame_len {
+            Some(max) if len > max => {
+                self.done = true;
+                self.buffer = BytesMut::new();
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("frame is longer than the maximum of {} bytes", max),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Stream for FsSplit {
+    type Item = Bytes;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        if self.done {
+            return Ok(Async::Ready(None));
+        }
+        loop {
+            let delimiter = self.delimiter;
+            let found = self.buffer[self.scanned..]
+                .iter()
+                .position(|&b| b == delimiter);
+            if let Some(i) = found {
+                let len = self.scanned + i;
+                self.check_len(len)?;
+
+                let frame = self.buffer.split_to(len).freeze();
+                self.buffer.advance(1);
+                self.scanned = 0;
+
+                return Ok(Async::Ready(Some(frame)));
+            }
+            self.scanned = self.buffer.len();
+            self.check_len(self.scanned)?;
+
+            if self.eof {
+                if self.buffer.is_empty() {
+                    return Ok(Async::Ready(None));
+                }
+                // the last frame isn't followed by a delimiter
+                self.scanned = 0;
+                return Ok(Async::Ready(Some(self.buffer.take().freeze())));
+            }
+
+            match try_ready!(self.inner.poll()) {
+                Some(chunk) => self.buffer.extend_from_slice(&chunk),
+                None => self.eof = true,
+            }
+        }
+    }
+}
+
+impl fmt::Debug for FsSplit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsSplit")
+            .field("inner", &self.inner)
+            .field("delimiter", &self.delimiter)
+            .finish()
+    }
+}
+
+/// A `Stream` of equally sized chunks of a target file.
+///
+/// Created by `FsReadStream::chunks_exact`.
+pub struct FsChunksExact {
+    inner: FsReadStream,
+    size: usize,
+    error_on_partial: bool,
+    chunk: Bytes,
+    partial: BytesMut,
+    eof: bool,
+}
+
+impl FsChunksExact {
+    /// Whether a shorter, final chunk at the end of the file is an error of
+    /// kind `UnexpectedEof`.
+    ///
+    /// Default is `false`, yielding the shorter chunk.
+    pub fn error_on_partial(mut self, error_on_partial: bool) -> Self {
+        self.error_on_partial = error_on_partial;
+        self
+    }
+}
+
+impl Stream for FsChunksExact {
+    type Item = Bytes;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            // when a whole chunk is in a single read, it isn't copied
+            if self.partial.is_empty() && self.chunk.len() >= self.size {
+                return Ok(Async::Ready(Some(self.chunk.split_to(self.size))));
+            }
+
+            if !self.chunk.is_empty() {
+                let len = cmp::min(self.size - self.partial.len(), self.chunk.len());
+                self.partial.extend_from_slice(&self.chunk.split_to(len));
+                if self.partial.len() == self.size {
+                    return Ok(Async::Ready(Some(self.partial.take().freeze())));
+                }
+                continue;
+            }
+
+            if self.eof {
+                if self.partial.is_empty() {
+                    return Ok(Async::Ready(None));
+                }
+                if self.error_on_partial {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "file ended within a chunk",
+                    ));
+                }
+                return Ok(Async::Ready(Some(self.partial.take().freeze())));
+            }
+
+            match try_ready!(self.inner.poll()) {
+                Some(chunk) => self.chunk = chunk,
+                None => self.eof = true,
+            }
+        }
+    }
+}
+
+impl fmt::Debug for FsChunksExact {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsChunksExact")
+            .field("inner", &self.inner)
+            .field("size", &self.size)
+            .finish()
+    }
+}
+
+/// A `Stream` of the lines of a target file.
+///
+/// Created by `FsReadStream::lines`.
+pub struct FsLines {
+    inner: FsSplit,
+}
+
+impl Stream for FsLines {
+    type Item = String;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        let mut line = match try_ready!(self.inner.poll()) {
+            Some(line) => line,
+            None => return Ok(Async::Ready(None)),
+        };
+
+        if line.last() == Some(&b'\r') {
+            let len = line.len() - 1;
+            line.truncate(len);
+        }
+
+        str::from_utf8(&line)
+            .map(|line| Async::Ready(Some(line.to_owned())))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl fmt::Debug for FsLines {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsLines")
+            .field("inner", &self.inner.inner)
+            .finish()
+    }
+}
@@ -0,0 +1,436 @@
+use std::collections::{HashSet, VecDeque};
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Component as PathComponent, Path, PathBuf};
+use std::{fmt, mem};
+
+use futures::{Async, Future, Poll, Stream};
+
+use FsFuture;
+use FsPool;
+
+const BATCH_SIZE: usize = 64;
+
+pub(crate) fn new(pool: &FsPool, pattern: &str) -> FsGlobStream {
+    FsGlobStream {
+        pattern: pattern.to_owned(),
+        skip_errors: false,
+        matches: VecDeque::new(),
+        pool: pool.clone(),
+        state: State::Ready(Globber::new(pattern)),
+    }
+}
+
+/// A `Stream` of the paths matching a glob pattern.
+///
+/// Created by `FsPool::glob`. Matches are fetched from the pool in batches,
+/// and yielded in the order of their names within each directory. An error
+/// reading a directory doesn't end the stream, so polling it again continues
+/// with the next directory.
+pub struct FsGlobStream {
+    pattern: String,
+    skip_errors: bool,
+    matches: VecDeque<io::Result<PathBuf>>,
+    pool: FsPool,
+    state: State,
+}
+
+impl FsGlobStream {
+    /// Whether to skip directories that can't be read, instead of yielding
+    /// their errors.
+    ///
+    /// Default is `false`.
+    pub fn skip_errors(mut self, skip_errors: bool) -> Self {
+        self.skip_errors = skip_errors;
+        self
+    }
+}
+
+type Batch = (Globber, Vec<io::Result<PathBuf>>);
+
+enum State {
+    Working(FsFuture<Batch>),
+    Ready(Globber),
+    Eof,
+    Swapping,
+}
+
+impl Stream for FsGlobStream {
+    type Item = PathBuf;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            while let Some(res) = self.matches.pop_front() {
+                match res {
+                    Ok(path) => return Ok(Async::Ready(Some(path))),
+                    Err(_) if self.skip_errors => (),
+                    Err(e) => return Err(e),
+                }
+            }
+
+            match mem::replace(&mut self.state, State::Swapping) {
+                State::Working(mut rx) => {
+                    let polled = rx.poll();
+                    self.state = State::Working(rx);
+                    if polled.is_err() {
+                        // the globber was lost along with the task
+                        self.state = State::Eof;
+                    }
+                    let (globber, matches) = try_ready!(polled);
+
+                    if globber.tasks.is_empty() {
+                        self.state = State::Eof;
+                    } else {
+                        self.state = State::Ready(globber);
+                    }
+                    self.matches.extend(matches);
+                }
+                State::Ready(mut globber) => {
+                    let rx = self.pool.exec(move || {
+                        let matches = globber.batch();
+                        Ok((globber, matches))
+                    });
+                    self.state = State::Working(rx);
+                }
+                State::Eof => {
+                    self.state = State::Eof;
+                    return Ok(Async::Ready(None));
+                }
+                State::Swapping => unreachable!(),
+            }
+        }
+    }
+}
+
+impl fmt::Debug for FsGlobStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsGlobStream")
+            .field("pattern", &self.pattern)
+            .finish()
+    }
+}
+
+/// A component of a glob pattern, between separators.
+enum Component {
+    Literal(OsString),
+    /// `**`, matching any number of directories.
+    Recursive,
+    Pattern(Vec<Token>),
+}
+
+enum Token {
+    Char(char),
+    /// `?`, matching any single character.
+    Any,
+    /// `*`, matching any number of characters.
+    Star,
+    /// `[...]`, matching a single character in or out of the ranges.
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+/// What is left to do for a glob, in the order it is to be done.
+enum Task {
+    /// Matches the entries of a directory against the components from an
+    /// index.
+    Expand(PathBuf, usize),
+    /// Yields every entry below a directory, for a trailing `**`.
+    All(PathBuf),
+    Yield(PathBuf),
+}
+
+/// The blocking state of a glob, moved to the pool for every batch.
+struct Globber {
+    components: Vec<Component>,
+    /// The tasks to be done, with the next one last.
+    tasks: Vec<Task>,
+    /// The targets of the links a `**` went into, along with the index of
+    /// the `**`, so that a loop of links isn't followed forever.
+    links: HashSet<(PathBuf, usize)>,
+}
+
+impl Globber {
+    fn new(pattern: &str) -> Globber {
+        let mut base = PathBuf::new();
+        let mut components = Vec::new();
+        for component in Path::new(pattern).components() {
+            match component {
+                PathComponent::Prefix(_) | PathComponent::RootDir if components.is_empty() => {
+                    base.push(component.as_os_str())
+                }
+                PathComponent::CurDir => (),
+                component => components.push(compile(component.as_os_str().to_string_lossy())),
+            }
+        }
+
+        // the start of the pattern without any wildcards doesn't need to be
+        // matched against directory entries
+        let literal = components
+            .iter()
+            .take_while(|component| matches!(component, Component::Literal(_)))
+            .count();
+        let components = components
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, component)| match component {
+                Component::Literal(name) if i < literal => {
+                    base.push(name);
+                    None
+                }
+                component => Some(component),
+            })
+            .collect();
+
+        Globber {
+            components,
+            tasks: vec![Task::Expand(base, 0)],
+            links: HashSet::new(),
+        }
+    }
+
+    fn batch(&mut self) -> Vec<io::Result<PathBuf>> {
+        let mut matches = Vec::with_capacity(BATCH_SIZE);
+        while matches.len() < BATCH_SIZE {
+            let task = match self.tasks.pop() {
+                Some(task) => task,
+                None => break,
+            };
+            match task {
+                Task::Yield(path) => matches.push(Ok(path)),
+                Task::Expand(dir, i) => {
+                    if let Err(e) = self.expand(dir, i) {
+                        matches.push(Err(e));
+                    }
+                }
+                Task::All(dir) => {
+                    if let Err(e) = self.all(dir) {
+                        matches.push(Err(e));
+                    }
+                }
+            }
+        }
+        matches
+    }
+
+    fn expand(&mut self, dir: PathBuf, i: usize) -> io::Result<()> {
+        let last = i + 1 == self.components.len();
+        let mut tasks = Vec::new();
+        match self.components.get(i) {
+            None => {
+                // the whole pattern was a path without any wildcards
+                if fs::symlink_metadata(&dir).is_ok() {
+                    tasks.push(Task::Yield(dir));
+                }
+            }
+            Some(Component::Literal(name)) => {
+                let path = dir.join(name);
+                if !last {
+                    tasks.push(Task::Expand(path, i + 1));
+                } else if fs::symlink_metadata(&path).is_ok() {
+                    tasks.push(Task::Yield(path));
+                }
+            }
+            Some(Component::Recursive) if last => tasks.push(Task::All(dir)),
+            Some(Component::Recursive) => {
+                // matching no directories at all, then matching each one
+                tasks.push(Task::Expand(dir.clone(), i + 1));
+                for entry in read_sorted(&dir)? {
+                    if entry.is_dir && self.recurse(&dir, &entry, i) {
+                        tasks.push(Task::Expand(entry.path, i));
+                    }
+                }
+            }
+            Some(Component::Pattern(tokens)) => {
+                for entry in read_sorted(&dir)? {
+                    let name = entry.path.file_name().unwrap().to_string_lossy();
+                    if !matches(tokens, &name) {
+                        continue;
+                    }
+                    if last {
+                        tasks.push(Task::Yield(entry.path));
+                    } else if entry.is_dir {
+                        tasks.push(Task::Expand(entry.path, i + 1));
+                    }
+                }
+            }
+        }
+        self.tasks.extend(tasks.into_iter().rev());
+        Ok(())
+    }
+
+    fn all(&mut self, dir: PathBuf) -> io::Result<()> {
+        let mut tasks = Vec::new();
+        // a trailing `**` is the last component
+        let i = self.components.len() - 1;
+        for entry in read_sorted(&dir)? {
+            tasks.push(Task::Yield(entry.path.clone()));
+            if entry.is_dir && self.recurse(&dir, &entry, i) {
+                tasks.push(Task::All(entry.path));
+            }
+        }
+        self.tasks.extend(tasks.into_iter().rev());
+        Ok(())
+    }
+
+    /// Whether the `**` at an index goes into a directory found in `dir`.
+    ///
+    /// A link is only followed the first time the `**` finds its target, and
+    /// never when it points to a directory that contains it.
+    fn recurse(&mut self, dir: &Path, entry: &Entry, i: usize) -> bool {
+        if !entry.is_link {
+            return true;
+        }
+        let target = match fs::canonicalize(&entry.path) {
+            Ok(target) => target,
+            Err(_) => return false,
+        };
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        let inside = fs::canonicalize(dir).is_ok_and(|dir| dir.starts_with(&target));
+        !inside && self.links.insert((target, i))
+    }
+}
+
+/// An entry of a directory.
+struct Entry {
+    path: PathBuf,
+    /// Whether this is a directory, or a link to one.
+    is_dir: bool,
+    is_link: bool,
+}
+
+/// Reads the entries of a directory, sorted by name.
+///
+/// A path that doesn't exist or isn't a directory has no entries.
+fn read_sorted(dir: &Path) -> io::Result<Vec<Entry>> {
+    let read = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let entries = match fs::read_dir(read) {
+        Ok(entries) => entries,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(_) if !read.is_dir() => return Ok(Vec::new()),
+        Err(e) => return Err(::path_error(read, e)),
+    };
+
+    let mut paths = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| ::path_error(read, e))?;
+        let path = dir.join(entry.file_name());
+        let file_type = entry.file_type().map_err(|e| ::path_error(&path, e))?;
+        let is_link = file_type.is_symlink();
+        // a link to a directory is matched into as well
+        let is_dir = file_type.is_dir() || (is_link && path.is_dir());
+        paths.push(Entry {
+            path,
+            is_dir,
+            is_link,
+        });
+    }
+    paths.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(paths)
+}
+
+fn compile(component: ::std::borrow::Cow<str>) -> Component {
+    if component == "**" {
+        return Component::Recursive;
+    }
+    if !component.contains(['*', '?', '[']) {
+        return Component::Literal(component.into_owned().into());
+    }
+
+    let chars = component.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '?' => tokens.push(Token::Any),
+            '*' => tokens.push(Token::Star),
+            '[' => {
+                if let Some((class, len)) = compile_class(&chars[i + 1..]) {
+                    tokens.push(class);
+                    i += len + 1;
+                    continue;
+                }
+                // an unclosed class is matched literally
+                tokens.push(Token::Char('['));
+            }
+            c => tokens.push(Token::Char(c)),
+        }
+        i += 1;
+    }
+    Component::Pattern(tokens)
+}
+
+/// Compiles the class after a `[`, returning it along with the number of
+/// characters it took up, including the closing `]`.
+fn compile_class(chars: &[char]) -> Option<(Token, usize)> {
+    let negated = chars.first() == Some(&'!');
+    let mut i = if negated { 1 } else { 0 };
+    let mut ranges = Vec::new();
+    // a `]` right at the start is part of the class
+    let start = i;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == ']' && i > start {
+            return Some((Token::Class { negated, ranges }, i + 1));
+        }
+        if i + 2 < chars.len() && chars[i + 1] == '-' && chars[i + 2] != ']' {
+            ranges.push((c, chars[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((c, c));
+            i += 1;
+        }
+    }
+    None
+}
+
+fn matches(tokens: &[Token], name: &str) -> bool {
+    let name = name.chars().collect::<Vec<_>>();
+    let (mut t, mut n) = (0, 0);
+    // where to retry from when a later token doesn't match, after a `*`
+    let mut retry = None;
+    while n < name.len() {
+        let matched = match tokens.get(t) {
+            Some(&Token::Star) => {
+                retry = Some((t, n));
+                t += 1;
+                continue;
+            }
+            Some(&Token::Char(c)) => c == name[n],
+            Some(&Token::Any) => true,
+            Some(&Token::Class {
+                negated,
+                ref ranges,
+            }) => {
+                let c = name[n];
+                ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != negated
+            }
+            None => false,
+        };
+        if matched {
+            t += 1;
+            n += 1;
+        } else if let Some((star, from)) = retry {
+            // let the `*` take up one more character
+            t = star + 1;
+            n = from + 1;
+            retry = Some((star, from + 1));
+        } else {
+            return false;
+        }
+    }
+    tokens[t..]
+        .iter()
+        .all(|token| matches!(*token, Token::Star))
+}
@@ -0,0 +1,91 @@
+use std::cmp;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::Path;
+
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use metrics::Metrics;
+use rate::RateLimit;
+
+/// The size of the buffer a file is read into, to be hashed.
+const BUF_SIZE: usize = 64 * 1024;
+
+/// A hash function to compute the digest of a file with.
+///
+/// Requires the `hash` feature.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    /// SHA-1, with a digest of 20 bytes.
+    Sha1,
+    /// SHA-256, with a digest of 32 bytes.
+    Sha256,
+}
+
+/// A hash being computed, with one of the algorithms.
+pub(crate) enum Hasher {
+    Sha1(Sha1),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    pub(crate) fn new(algorithm: HashAlgorithm) -> Hasher {
+        match algorithm {
+            HashAlgorithm::Sha1 => Hasher::Sha1(Sha1::new()),
+            HashAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+        }
+    }
+
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        match *self {
+            Hasher::Sha1(ref mut hasher) => hasher.update(bytes),
+            Hasher::Sha256(ref mut hasher) => hasher.update(bytes),
+        }
+    }
+
+    pub(crate) fn finish(self) -> Vec<u8> {
+        match self {
+            Hasher::Sha1(hasher) => hasher.finalize().to_vec(),
+            Hasher::Sha256(hasher) => hasher.finalize().to_vec(),
+        }
+    }
+}
+
+/// Hashes the file at `path`, or only the bytes of `range` within it.
+pub(crate) fn hash_file(
+    path: &Path,
+    algorithm: HashAlgorithm,
+    range: Option<Range<u64>>,
+    metrics: &Metrics,
+    rate: Option<&RateLimit>,
+) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let mut remaining = match range {
+        Some(range) => {
+            file.seek(SeekFrom::Start(range.start))?;
+            range.end.saturating_sub(range.start)
+        }
+        None => u64::MAX,
+    };
+
+    let mut hasher = Hasher::new(algorithm);
+    let mut buf = vec![0; BUF_SIZE];
+    while remaining > 0 {
+        let len = cmp::min(BUF_SIZE as u64, remaining) as usize;
+        let n = match file.read(&mut buf[..len]) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        metrics.read(n as u64);
+        if let Some(rate) = rate {
+            rate.take(n as u64);
+        }
+        hasher.update(&buf[..n]);
+        remaining -= n as u64;
+    }
+    Ok(hasher.finish())
+}
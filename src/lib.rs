@@ -27,6 +27,10 @@
 //! // the reading and writing however will happen off-thread
 //! read.forward(write).wait()
 //!     .expect("IO error piping foo.txt to out.txt");
+//!
+//! // or, without bouncing each chunk through this thread
+//! fs.copy("/home/sean/foo.txt", "/home/sean/out.txt").wait()
+//!     .expect("IO error copying foo.txt to out.txt");
 //! # }
 //! # fn main() {}
 //! ```
@@ -34,37 +38,151 @@
 extern crate bytes;
 #[macro_use]
 extern crate futures;
+#[cfg(feature = "std-future")]
+extern crate futures_core;
 extern crate futures_cpupool;
+#[cfg(feature = "std-future")]
+extern crate futures_sink;
+#[cfg(unix)]
+extern crate libc;
+#[cfg(feature = "mmap")]
+extern crate memmap2;
+#[cfg(feature = "notify")]
+extern crate notify;
+#[cfg(feature = "hash")]
+extern crate sha1;
+#[cfg(feature = "hash")]
+extern crate sha2;
+#[cfg(feature = "tokio-blocking")]
+extern crate tokio_executor;
+#[cfg(feature = "tokio-io")]
+extern crate tokio_io;
+#[cfg(feature = "tokio-blocking")]
+extern crate tokio_threadpool;
 
-use std::path::Path;
+#[cfg(unix)]
+use std::ffi::OsString;
+#[cfg(feature = "hash")]
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::{fmt, fs, io};
+use std::time::{Duration, SystemTime};
+use std::{env, fmt, fs, io, thread};
 
-use futures::future::{lazy, Executor};
+use bytes::Bytes;
+use futures::future::{lazy, ExecuteErrorKind, Executor};
 use futures::sync::oneshot::{self, Receiver};
-use futures::{Async, Future, Poll};
-use futures_cpupool::CpuPool;
+use futures::{Async, Future, Poll, Stream};
 
-pub use self::read::{FsReadStream, ReadOptions};
-pub use self::write::{FsWriteSink, WriteOptions};
+#[cfg(feature = "tokio-io")]
+pub use self::async_io::{FsAsyncRead, FsAsyncWrite};
+pub use self::blocking::{FsBlockingRead, FsBlockingWrite};
+pub use self::builder::FsPoolBuilder;
+pub use self::cancel::FsCancelHandle;
+#[cfg(feature = "std-future")]
+pub use self::compat::{FsCompat, FsCompatSink};
+pub use self::concat::{FsConcatStream, FsOffsetConcatStream};
+pub use self::copy::{
+    CopyDirOptions, CopyDirSummary, CopyOptions, CopyProgress, FsProgress, MoveOptions,
+};
+pub use self::delete::{DeleteOptions, DeleteSummary};
+pub use self::dir::{FsDirEntry, FsDirStream, ReadDirOptions};
+pub use self::error::{FsError, FsOperation};
+pub use self::file::FsFile;
+pub use self::frame::{FsChunksExact, FsLines, FsSplit};
+pub use self::glob::FsGlobStream;
+#[cfg(feature = "hash")]
+pub use self::hash::HashAlgorithm;
+pub use self::lock::FsFileLock;
+pub use self::many::FsManyReadStream;
+pub use self::metrics::{FsOpCounts, FsPoolMetrics};
+#[cfg(feature = "mmap")]
+pub use self::mmap::{FsMmap, FsMmapMut};
+pub use self::pipe::PipeOptions;
+pub use self::priority::OpPriority;
+pub use self::read::{Advice, FsOffsetReadStream, FsReadStream, ReadOptions};
+pub use self::statfs::FsStats;
+pub use self::temp::FsTempDir;
+pub use self::walk::{DirStats, DirStatsOptions, FsWalkEntry, FsWalkStream, WalkOptions};
+#[cfg(feature = "notify")]
+pub use self::watch::WatchOptions;
+pub use self::watch::{FsWatchEvent, FsWatchStream};
+pub use self::write::{FsPositionedWriteSink, FsWriteSink, WriteOptions};
 
+#[cfg(feature = "tokio-io")]
+mod async_io;
+mod blocking;
+mod builder;
+mod cancel;
+mod compare;
+#[cfg(feature = "std-future")]
+mod compat;
+mod concat;
+mod copy;
+mod delete;
+mod dir;
+mod drain;
+mod error;
+mod file;
+mod frame;
+mod glob;
+#[cfg(feature = "hash")]
+mod hash;
+mod lock;
+mod many;
+mod metrics;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod pipe;
+mod priority;
+mod rate;
 mod read;
+mod statfs;
+mod sys;
+mod temp;
+mod timeout;
+#[cfg(feature = "tokio-blocking")]
+mod tokio_blocking;
+mod walk;
+mod watch;
 mod write;
 
+type BoxExecutor = dyn Executor<Box<dyn Future<Item = (), Error = ()> + Send>> + Send + Sync;
+
 /// A pool of threads to handle file IO.
 #[derive(Clone)]
 pub struct FsPool {
-    executor: Arc<dyn Executor<Box<dyn Future<Item = (), Error = ()> + Send>> + Send + Sync>,
+    executor: Arc<BoxExecutor>,
+    /// The number of threads, if known.
+    threads: Option<usize>,
+    tasks: Arc<::drain::Tasks>,
+    metrics: Arc<::metrics::Metrics>,
+    queues: Arc<::priority::Queues>,
+    priority: OpPriority,
+    rate: Option<Arc<::rate::RateLimit>>,
+    timer: Option<::timeout::Timer>,
 }
 
 // ===== impl FsPool ======
 
 impl FsPool {
     /// Creates a new `FsPool`, with the supplied number of threads.
+    ///
+    /// `FsPool::default` picks the number of threads from the number of
+    /// CPUs instead, like `FsPool::builder`.
+    ///
+    /// # Panic
+    ///
+    /// The passed argument must be larger than 0.
     pub fn new(threads: usize) -> Self {
-        FsPool {
-            executor: Arc::new(CpuPool::new(threads)),
-        }
+        FsPool::builder().pool_size(threads).build()
+    }
+
+    /// Returns a builder for an `FsPool` with its own threads, which can be
+    /// named and given a stack size.
+    pub fn builder() -> FsPoolBuilder {
+        FsPoolBuilder::new()
     }
 
     /// Creates a new `FsPool`, from an existing `Executor`.
@@ -82,9 +200,22 @@ impl FsPool {
     where
         E: Executor<Box<dyn Future<Item = (), Error = ()> + Send>> + Send + Sync + 'static,
     {
-        FsPool {
-            executor: Arc::new(executor),
-        }
+        FsPool::from_parts(Arc::new(executor), None)
+    }
+
+    /// Creates a new `FsPool`, that runs its work on the tokio threadpool it
+    /// is used from, within `tokio_threadpool::blocking`.
+    ///
+    /// Instead of having threads of its own, the work is spawned on the
+    /// default tokio executor when it is started, and only runs once a
+    /// worker polls it, handing off the other tasks of that worker. Work
+    /// polled anywhere but on a worker of a tokio threadpool fails with an
+    /// error rather than blocking that thread, and starting it without a
+    /// default executor fails as well. Requires the `tokio-blocking`
+    /// feature.
+    #[cfg(feature = "tokio-blocking")]
+    pub fn with_tokio_blocking() -> Self {
+        FsPool::from_parts(Arc::new(::tokio_blocking::TokioBlocking), None)
     }
 
     #[doc(hidden)]
@@ -93,8 +224,89 @@ impl FsPool {
     where
         E: Executor<Box<dyn Future<Item = (), Error = ()> + Send>> + Send + Sync + 'static,
     {
+        FsPool::from_parts(Arc::new(executor), None)
+    }
+
+    fn from_parts(executor: Arc<BoxExecutor>, threads: Option<usize>) -> Self {
         FsPool {
-            executor: Arc::new(executor),
+            executor,
+            threads,
+            tasks: Arc::default(),
+            metrics: Arc::default(),
+            queues: Arc::default(),
+            priority: OpPriority::Normal,
+            rate: None,
+            timer: None,
+        }
+    }
+
+    /// Returns a handle to this pool, whose operations run with `priority`.
+    ///
+    /// The handle shares the threads of this pool, and the operations of
+    /// all handles wait for them together, in the order of their priority.
+    /// Streams and sinks are only queued by their priority until they start,
+    /// after which they keep their thread while busy.
+    pub fn with_priority(&self, priority: OpPriority) -> FsPool {
+        let mut pool = self.clone();
+        pool.priority = priority;
+        pool
+    }
+
+    /// Returns a handle to this pool, that waits with `timer` whenever it
+    /// checks something again later on its own.
+    ///
+    /// That is in between the checks of a followed file, the tries of
+    /// `lock_exclusive_timeout` and `lock_shared_timeout`, and the stats of
+    /// `poll_watch`. `timer` is called to get a future that resolves after
+    /// the supplied duration, like a delay of the runtime of the caller. A
+    /// timer that fails counts as expired.
+    ///
+    /// Default is to sleep on a thread of the pool for each wait.
+    pub fn with_timer<F, T>(&self, timer: F) -> FsPool
+    where
+        F: Fn(Duration) -> T + Send + Sync + 'static,
+        T: Future<Item = ()> + Send + 'static,
+    {
+        let mut pool = self.clone();
+        pool.timer = Some(::timeout::Timer::new(timer));
+        pool
+    }
+
+    /// The timer of this pool, from `with_timer`, or else one that sleeps
+    /// on a thread of the pool.
+    fn timer(&self) -> ::timeout::Timer {
+        if let Some(ref timer) = self.timer {
+            return timer.clone();
+        }
+        let pool = self.clone();
+        ::timeout::Timer::new(move |duration| {
+            let (tx, rx) = oneshot::channel();
+            let sleep = Box::new(lazy(move || {
+                thread::sleep(duration);
+                tx.send(()).map_err(|_| ())
+            }));
+            // expiring right away would spin instead of waiting
+            if pool.spawn_cleanup(sleep).is_err() {
+                thread::sleep(duration);
+            }
+            rx
+        })
+    }
+
+    /// This pool, or a handle with `priority` if one is set.
+    fn prioritized(&self, priority: Option<OpPriority>) -> FsPool {
+        match priority {
+            Some(priority) => self.with_priority(priority),
+            None => self.clone(),
+        }
+    }
+
+    /// The rate limit of an operation, from its own `max_bytes_per_sec` or
+    /// else that of the pool.
+    fn rate_limit(&self, max_bytes_per_sec: Option<u64>) -> Option<Arc<::rate::RateLimit>> {
+        match max_bytes_per_sec {
+            Some(rate) => Some(Arc::new(::rate::RateLimit::new(rate))),
+            None => self.rate.clone(),
         }
     }
 
@@ -106,11 +318,192 @@ impl FsPool {
         ::read::new(self, path, opts)
     }
 
+    /// Returns a `Stream` of the contents of the files at the supplied paths,
+    /// one after the other.
+    ///
+    /// Each file is opened once the one before it has been read, and read
+    /// with `opts`, so a range or following applies to every file.
+    pub fn read_concat<I>(&self, paths: I, opts: ReadOptions) -> FsConcatStream
+    where
+        I: IntoIterator<Item = PathBuf>,
+    {
+        ::concat::new(self, paths, opts)
+    }
+
+    /// Returns a `Stream` of the whole contents of the files at the supplied
+    /// paths, each along with its path.
+    ///
+    /// The files are read at once, up to the number of threads in the pool,
+    /// and yielded as they are done. An error reading one file is yielded
+    /// with its path, and doesn't stop the others.
+    pub fn read_many<I>(&self, paths: I) -> FsManyReadStream
+    where
+        I: IntoIterator<Item = PathBuf>,
+    {
+        ::many::new(self, paths)
+    }
+
     /// Returns a `Stream` of the contents of the supplied file.
     pub fn read_file(&self, file: fs::File, opts: ReadOptions) -> FsReadStream {
         ::read::new_from_file(self, file, opts)
     }
 
+    /// Returns a futures 0.3 `Stream` of the contents of the file at the
+    /// supplied path, like `read`.
+    ///
+    /// Requires the `std-future` feature.
+    #[cfg(feature = "std-future")]
+    pub fn read03<P>(&self, path: P, opts: ReadOptions) -> FsCompat<FsReadStream>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        FsCompat::new(self.read(path, opts))
+    }
+
+    /// Returns a `Future` that resolves with the whole contents of the file at
+    /// the supplied path.
+    ///
+    /// If `max_size` is set, the future resolves with an error instead if the
+    /// file is larger than `max_size` bytes.
+    pub fn read_to_end<P>(&self, path: P, max_size: Option<u64>) -> FsFuture<Bytes>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        let metrics = self.metrics.clone();
+        let rate = self.rate.clone();
+        self.exec_as(Some(FsOperation::Read), move || {
+            let bytes = ::read::read_to_end(path.as_ref(), max_size)?;
+            metrics.read(bytes.len() as u64);
+            if let Some(rate) = rate {
+                rate.take(bytes.len() as u64);
+            }
+            Ok(bytes)
+        })
+    }
+
+    /// Returns a `Future` that resolves with the whole contents of the file at
+    /// the supplied path, as a `String`.
+    ///
+    /// If the contents are not valid UTF-8, the future resolves with an error
+    /// of kind `InvalidData`.
+    pub fn read_to_string<P>(&self, path: P) -> FsFuture<String>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        let metrics = self.metrics.clone();
+        let rate = self.rate.clone();
+        self.exec_as(Some(FsOperation::Read), move || {
+            let string = fs::read_to_string(path)?;
+            metrics.read(string.len() as u64);
+            if let Some(rate) = rate {
+                rate.take(string.len() as u64);
+            }
+            Ok(string)
+        })
+    }
+
+    /// Returns a `Future` that resolves with the contents of the file at the
+    /// supplied path, mapped into memory.
+    ///
+    /// The file is mapped on the pool, which also faults in its pages, since
+    /// that blocks like reading would. See
+    /// `FsMmap` for why the file must not be changed while mapped. Requires
+    /// the `mmap` feature.
+    #[cfg(feature = "mmap")]
+    pub fn mmap_read<P>(&self, path: P) -> FsFuture<FsMmap>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        let pool = self.clone();
+        self.exec_as(Some(FsOperation::Read), move || {
+            ::mmap::map(&pool, path.as_ref())
+        })
+    }
+
+    /// Returns a `Future` that resolves with the file at the supplied path,
+    /// mapped into memory to be written.
+    ///
+    /// The file is created if it doesn't exist, and its length is set to
+    /// `len`, truncating any contents it had. Requires the `mmap` feature.
+    #[cfg(feature = "mmap")]
+    pub fn mmap_write<P>(&self, path: P, len: u64) -> FsFuture<FsMmapMut>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        let pool = self.clone();
+        self.exec_as(Some(FsOperation::Write), move || {
+            ::mmap::map_mut(&pool, path.as_ref(), len)
+        })
+    }
+
+    /// Returns a `Future` that resolves with the digest of the contents of
+    /// the file at the supplied path, computed with `algorithm`.
+    ///
+    /// The file is read and hashed on a single thread of the pool. If
+    /// `range` is set, only the bytes within it are hashed, up to the end of
+    /// the file. Requires the `hash` feature.
+    #[cfg(feature = "hash")]
+    pub fn hash<P>(
+        &self,
+        path: P,
+        algorithm: HashAlgorithm,
+        range: Option<Range<u64>>,
+    ) -> FsFuture<Vec<u8>>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        let metrics = self.metrics.clone();
+        let rate = self.rate.clone();
+        self.exec_as(Some(FsOperation::Read), move || {
+            ::hash::hash_file(path.as_ref(), algorithm, range, &metrics, rate.as_deref())
+        })
+    }
+
+    /// Returns a `Future` that resolves with whether the files at `a` and
+    /// `b` have the same contents.
+    ///
+    /// Files of different lengths are unequal without reading them.
+    /// Otherwise, both are read in blocks on a single thread of the pool,
+    /// until the first block that differs. Either file not existing is an
+    /// error.
+    pub fn compare<P, Q>(&self, a: P, b: Q) -> FsFuture<bool>
+    where
+        P: AsRef<Path> + Send + 'static,
+        Q: AsRef<Path> + Send + 'static,
+    {
+        let metrics = self.metrics.clone();
+        let rate = self.rate.clone();
+        self.exec_as(Some(FsOperation::Read), move || {
+            let diff = ::compare::first_difference(
+                a.as_ref(),
+                b.as_ref(),
+                true,
+                &metrics,
+                rate.as_deref(),
+            )?;
+            Ok(diff.is_none())
+        })
+    }
+
+    /// Returns a `Future` that resolves with the offset of the first byte
+    /// that differs between the files at `a` and `b`, or `None` if their
+    /// contents are the same.
+    ///
+    /// If one file is a prefix of the other, the offset is the length of
+    /// the shorter one. Like `compare`, both are read on a single thread of
+    /// the pool.
+    pub fn first_difference<P, Q>(&self, a: P, b: Q) -> FsFuture<Option<u64>>
+    where
+        P: AsRef<Path> + Send + 'static,
+        Q: AsRef<Path> + Send + 'static,
+    {
+        let metrics = self.metrics.clone();
+        let rate = self.rate.clone();
+        self.exec_as(Some(FsOperation::Read), move || {
+            ::compare::first_difference(a.as_ref(), b.as_ref(), false, &metrics, rate.as_deref())
+        })
+    }
+
     /// Returns a `Sink` to send bytes to be written to the file at the supplied path.
     pub fn write<P>(&self, path: P, opts: WriteOptions) -> FsWriteSink
     where
@@ -119,79 +512,1254 @@ impl FsPool {
         ::write::new(self, path, opts)
     }
 
+    /// Returns a futures 0.3 `Sink` to send bytes to be written to the file at
+    /// the supplied path, like `write`.
+    ///
+    /// Requires the `std-future` feature.
+    #[cfg(feature = "std-future")]
+    pub fn write03<P>(&self, path: P, opts: WriteOptions) -> FsCompatSink<FsWriteSink>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        FsCompatSink::new(self.write(path, opts))
+    }
+
+    /// Returns a `Future` that resolves with a handle to the file at the
+    /// supplied path, opened with `opts`, to read and write at given offsets.
+    ///
+    /// Opening the file counts as a write in the metrics of the pool if
+    /// `opts` open it for writing or appending, and as a read otherwise.
+    pub fn open<P>(&self, path: P, opts: fs::OpenOptions) -> FsFuture<FsFile>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        let pool = self.clone();
+        let kind = if opens_for_writing(&opts) {
+            FsOperation::Write
+        } else {
+            FsOperation::Read
+        };
+        self.exec_as(Some(kind), move || {
+            let file = opts.open(path)?;
+            Ok(FsFile::new(&pool, file))
+        })
+    }
+
+    /// Returns a `Sink` to send bytes to be written at given offsets of the
+    /// file at the supplied path.
+    ///
+    /// Each item is written at its offset without moving a shared cursor,
+    /// so the pieces of a file may be sent in any order. Opening the file in
+    /// append mode makes all writes go to the end of the file instead, on
+    /// some platforms.
+    pub fn write_at<P>(&self, path: P, opts: WriteOptions) -> FsPositionedWriteSink
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        ::write::new_positioned(self, path, opts)
+    }
+
+    /// Returns a `Sink` to send bytes to be appended to the file at the
+    /// supplied path, creating the file if it doesn't exist.
+    pub fn append<P>(&self, path: P) -> FsWriteSink
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.write(path, WriteOptions::default().append(true))
+    }
+
+    /// Returns a `Sink` to send bytes to be appended to the supplied file.
+    ///
+    /// Writes start at the current end of the file. To keep appending at the
+    /// end while other writers are also appending, the file must have been
+    /// opened in append mode.
+    pub fn append_file(&self, file: fs::File) -> FsWriteSink {
+        ::write::new_from_file_append(self, file)
+    }
+
+    /// Returns a `Future` that resolves when all of `data` has been written to
+    /// the file at the supplied path.
+    pub fn write_all<P>(&self, path: P, data: Bytes, opts: WriteOptions) -> FsFuture<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        let metrics = self.metrics.clone();
+        let rate = self.rate_limit(opts.max_bytes_per_sec);
+        self.exec_as(Some(FsOperation::Write), move || {
+            if let Some(rate) = rate {
+                rate.take(data.len() as u64);
+            }
+            ::write::write_all(path.as_ref(), &data, &opts)?;
+            metrics.written(data.len() as u64);
+            Ok(())
+        })
+    }
+
+    /// Returns a `Future` that resolves with the number of bytes written,
+    /// once every chunk of `stream` has been written to the file at the
+    /// supplied path.
+    ///
+    /// The stream is polled by a task on the pool, which writes the chunks
+    /// as they come, without waking the task of the caller for each one.
+    /// An error of the file is wrapped in an `FsError` of `FsOperation::Write`
+    /// for the path, while an error of the stream is passed through as is,
+    /// so `FsError::from_io` tells them apart. Either one stops writing,
+    /// leaving what was written so far, unless writing `atomic`ally.
+    pub fn write_stream<P, S>(&self, path: P, stream: S, opts: WriteOptions) -> FsFuture<u64>
+    where
+        P: AsRef<Path> + Send + 'static,
+        S: Stream<Item = Bytes, Error = io::Error> + Send + 'static,
+    {
+        ::write::write_stream(self, path, stream, opts)
+    }
+
+    /// Returns a `Future` that resolves with the number of bytes piped, once
+    /// the file at `from` has been written to the file at `to`.
+    ///
+    /// Unlike forwarding a `read` stream into a `write` sink, each chunk is
+    /// read and written by a single task on the pool, through one buffer,
+    /// without passing through the task of the caller.
+    pub fn pipe<P, Q>(&self, from: P, to: Q, opts: PipeOptions) -> FsFuture<u64>
+    where
+        P: AsRef<Path> + Send + 'static,
+        Q: AsRef<Path> + Send + 'static,
+    {
+        let metrics = self.metrics.clone();
+        let rate = self.rate.clone();
+        self.exec_as(Some(FsOperation::Write), move || {
+            ::pipe::pipe(from.as_ref(), to.as_ref(), &opts, &metrics, rate.as_deref())
+        })
+    }
+
+    /// Returns a `Future` that resolves with the number of bytes piped, once
+    /// `from` has been written to `to`, like `pipe`.
+    ///
+    /// Both files are used from their current positions, until the end of
+    /// `from`.
+    pub fn pipe_files(&self, mut from: fs::File, mut to: fs::File) -> FsFuture<u64> {
+        let metrics = self.metrics.clone();
+        let rate = self.rate.clone();
+        self.exec_as(Some(FsOperation::Write), move || {
+            let buf_size = ::read::buffer_size(&from);
+            ::pipe::copy(
+                &mut from,
+                &mut to,
+                None,
+                buf_size,
+                &metrics,
+                rate.as_deref(),
+            )
+        })
+    }
+
+    /// Returns a `Future` that resolves with the number of bytes copied and
+    /// the writer, once the whole file at `path` has been written to
+    /// `writer`.
+    ///
+    /// The file is read and written to the writer on a single thread of the
+    /// pool, so `writer` can be any synchronous writer, like an encoder.
+    /// Errors of the file are wrapped in an `FsError` of the read, while
+    /// errors of the writer say that writing to the writer failed.
+    pub fn copy_to_writer<P, W>(&self, path: P, writer: W) -> FsFuture<(u64, W)>
+    where
+        P: AsRef<Path> + Send + 'static,
+        W: io::Write + Send + 'static,
+    {
+        let metrics = self.metrics.clone();
+        let rate = self.rate.clone();
+        self.exec_as(Some(FsOperation::Read), move || {
+            let mut writer = writer;
+            let copied =
+                ::pipe::copy_to_writer(path.as_ref(), &mut writer, &metrics, rate.as_deref())?;
+            Ok((copied, writer))
+        })
+    }
+
     /// Returns a `Sink` to send bytes to be written to the supplied file.
     pub fn write_file(&self, file: fs::File) -> FsWriteSink {
         ::write::new_from_file(self, file)
     }
 
-    /// Returns a `Future` that resolves when the target file is deleted.
-    pub fn delete<P>(&self, path: P) -> FsFuture<()>
+    /// Returns a `Stream` of the changes to the file or directory at `path`,
+    /// as reported by the OS.
+    ///
+    /// The watcher is set up on the pool, and then runs on a thread of its
+    /// own until the stream is dropped. Requires the `notify` feature.
+    #[cfg(feature = "notify")]
+    pub fn watch<P>(&self, path: P, opts: WatchOptions) -> FsWatchStream
+    where
+        P: AsRef<Path>,
+    {
+        ::watch::watch(self, path.as_ref().to_owned(), opts)
+    }
+
+    /// Returns a `Stream` of the changes to the file or directory at `path`,
+    /// found by looking at it on the pool every `interval`.
+    ///
+    /// This works wherever the path can be read, unlike `watch`, but only
+    /// notices the path being created, removed, or changing its size or
+    /// modification time. For a directory, that is when entries are added or
+    /// removed. The first event is always a `FsWatchEvent::Snapshot`.
+    pub fn poll_watch<P>(&self, path: P, interval: Duration) -> FsWatchStream
+    where
+        P: AsRef<Path>,
+    {
+        ::watch::poll_watch(self, path.as_ref().to_owned(), interval)
+    }
+
+    /// Returns a `Future` that resolves with the value of the extended
+    /// attribute `name` of the file at `path`, or `None` if it isn't set.
+    ///
+    /// Extended attributes are only available on Unix. Where the OS has none,
+    /// this fails with an error of kind `Unsupported`, and so do the other
+    /// `xattr` methods.
+    #[cfg(unix)]
+    pub fn get_xattr<P>(&self, path: P, name: OsString) -> FsFuture<Option<Vec<u8>>>
     where
         P: AsRef<Path> + Send + 'static,
     {
-        let (tx, rx) = oneshot::channel();
+        self.exec_as(Some(FsOperation::Metadata), move || {
+            ::sys::get_xattr(path.as_ref(), &name)
+        })
+    }
 
-        let fut = Box::new(lazy(move || {
-            tx.send(fs::remove_file(path).map_err(From::from))
-                .map_err(|_| ())
-        }));
+    /// Returns a `Future` that resolves when the extended attribute `name` of
+    /// the file at `path` is set to `value`.
+    #[cfg(unix)]
+    pub fn set_xattr<P>(&self, path: P, name: OsString, value: Vec<u8>) -> FsFuture<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.exec_as(Some(FsOperation::Metadata), move || {
+            ::sys::set_xattr(path.as_ref(), &name, &value)
+        })
+    }
 
-        self.executor.execute(fut).unwrap();
+    /// Returns a `Future` that resolves when the extended attribute `name` of
+    /// the file at `path` is removed.
+    ///
+    /// Removing an attribute that isn't set is an error.
+    #[cfg(unix)]
+    pub fn remove_xattr<P>(&self, path: P, name: OsString) -> FsFuture<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.exec_as(Some(FsOperation::Metadata), move || {
+            ::sys::remove_xattr(path.as_ref(), &name)
+        })
+    }
 
-        fs(rx)
+    /// Returns a `Future` that resolves with the names of the extended
+    /// attributes of the file at `path`.
+    #[cfg(unix)]
+    pub fn list_xattrs<P>(&self, path: P) -> FsFuture<Vec<OsString>>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.exec_as(Some(FsOperation::Metadata), move || {
+            ::sys::list_xattrs(path.as_ref())
+        })
     }
-}
 
-impl Default for FsPool {
-    fn default() -> FsPool {
-        FsPool::new(4)
+    /// Returns a `Future` that resolves when the owner of the file at `path`
+    /// is changed to the user `uid` and the group `gid`.
+    ///
+    /// `None` leaves the user or group unchanged, and a symbolic link is
+    /// followed. A change that isn't permitted fails with an error of kind
+    /// `PermissionDenied`. This is only available on Unix.
+    #[cfg(unix)]
+    pub fn chown<P>(&self, path: P, uid: Option<u32>, gid: Option<u32>) -> FsFuture<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.exec_as(Some(FsOperation::Metadata), move || {
+            ::std::os::unix::fs::chown(path, uid, gid)
+        })
     }
-}
 
-impl fmt::Debug for FsPool {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.debug_struct("FsPool").finish()
+    /// Returns a `Future` that resolves when the owner of the file at `path`
+    /// is changed, without following a symbolic link.
+    ///
+    /// See `chown` for how the owner is changed.
+    #[cfg(unix)]
+    pub fn lchown<P>(&self, path: P, uid: Option<u32>, gid: Option<u32>) -> FsFuture<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.exec_as(Some(FsOperation::Metadata), move || {
+            ::std::os::unix::fs::lchown(path, uid, gid)
+        })
     }
-}
 
-// ===== impl FsFuture =====
+    /// Returns a `Future` that resolves with the path and handle of a new,
+    /// uniquely named file in `dir`.
+    ///
+    /// The file is opened for reading and writing, and on Unix only its owner
+    /// can access it. It isn't deleted automatically.
+    pub fn tempfile_in<P>(&self, dir: P) -> FsFuture<(PathBuf, fs::File)>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.exec(move || ::temp::create_file(dir.as_ref()))
+    }
 
-/// A future representing work in the `FsPool`.
-pub struct FsFuture<T> {
-    inner: Receiver<io::Result<T>>,
-}
+    /// Returns a `Future` that resolves with the path and handle of a new,
+    /// uniquely named file in the system's temporary directory.
+    ///
+    /// See `tempfile_in` for how the file is created.
+    pub fn tempfile(&self) -> FsFuture<(PathBuf, fs::File)> {
+        self.tempfile_in(env::temp_dir())
+    }
 
-fn fs<T: Send>(rx: Receiver<io::Result<T>>) -> FsFuture<T> {
-    FsFuture { inner: rx }
-}
+    /// Returns a `Future` that resolves with a new, uniquely named directory
+    /// in `dir`, that is removed again when dropped.
+    ///
+    /// On Unix, only its owner can access the directory.
+    pub fn tempdir_in<P>(&self, dir: P) -> FsFuture<FsTempDir>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        let pool = self.clone();
+        self.exec(move || ::temp::create_dir(&pool, dir.as_ref()))
+    }
 
-impl<T: Send + 'static> Future for FsFuture<T> {
-    type Item = T;
-    type Error = io::Error;
+    /// Returns a `Future` that resolves with a new, uniquely named directory
+    /// in the system's temporary directory, that is removed again when
+    /// dropped.
+    pub fn tempdir(&self) -> FsFuture<FsTempDir> {
+        self.tempdir_in(env::temp_dir())
+    }
 
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        match self.inner.poll().unwrap() {
-            Async::Ready(Ok(item)) => Ok(Async::Ready(item)),
-            Async::Ready(Err(e)) => Err(e),
-            Async::NotReady => Ok(Async::NotReady),
-        }
+    /// Returns a `Future` that resolves with an exclusive lock of the file at
+    /// `path`, once no other process holds a lock of it.
+    ///
+    /// The file is created if it doesn't exist. Locks are advisory, so they
+    /// only keep out others that lock the file as well.
+    ///
+    /// A thread of the pool is blocked until the lock is available, which
+    /// might be forever. Size the pool for every lock that might be waited
+    /// for at once, or use `lock_exclusive_timeout` instead.
+    pub fn lock_exclusive<P>(&self, path: P) -> FsFuture<FsFileLock>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.lock(path, ::lock::Mode::Exclusive)
     }
-}
 
-impl<T> fmt::Debug for FsFuture<T> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.debug_struct("FsFuture").finish()
+    /// Returns a `Future` that resolves with a shared lock of the file at
+    /// `path`, once no other process holds an exclusive lock of it.
+    ///
+    /// See `lock_exclusive` for how the file is locked. The file is only
+    /// opened for reading, so a file that can't be written can be locked
+    /// as well, though a missing file is still created.
+    pub fn lock_shared<P>(&self, path: P) -> FsFuture<FsFileLock>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.lock(path, ::lock::Mode::Shared)
     }
-}
 
-fn _assert_kinds() {
-    fn assert_send<T: Send>() {}
-    fn assert_sync<T: Sync>() {}
-    fn assert_clone<T: Clone>() {}
+    /// Returns a `Future` that resolves with an exclusive lock of the file at
+    /// `path`, or fails with an error of kind `TimedOut` if it isn't
+    /// available within `timeout`.
+    ///
+    /// Unlike `lock_exclusive`, no thread of the pool waits for the lock.
+    /// Instead, the lock is tried on the pool every so often.
+    pub fn lock_exclusive_timeout<P>(&self, path: P, timeout: Duration) -> FsFuture<FsFileLock>
+    where
+        P: AsRef<Path>,
+    {
+        ::lock::lock_timeout(
+            self,
+            path.as_ref().to_owned(),
+            ::lock::Mode::Exclusive,
+            timeout,
+        )
+    }
 
-    assert_send::<FsPool>();
-    assert_sync::<FsPool>();
-    assert_clone::<FsPool>();
+    /// Returns a `Future` that resolves with a shared lock of the file at
+    /// `path`, or fails with an error of kind `TimedOut` if it isn't
+    /// available within `timeout`.
+    ///
+    /// See `lock_exclusive_timeout` for how the lock is waited for.
+    pub fn lock_shared_timeout<P>(&self, path: P, timeout: Duration) -> FsFuture<FsFileLock>
+    where
+        P: AsRef<Path>,
+    {
+        ::lock::lock_timeout(
+            self,
+            path.as_ref().to_owned(),
+            ::lock::Mode::Shared,
+            timeout,
+        )
+    }
 
-    assert_send::<FsFuture<()>>();
+    /// Returns a `Future` that resolves with an exclusive lock of the file at
+    /// `path`, or `None` if another process holds a lock of it.
+    pub fn try_lock_exclusive<P>(&self, path: P) -> FsFuture<Option<FsFileLock>>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.try_lock(path, ::lock::Mode::Exclusive)
+    }
+
+    /// Returns a `Future` that resolves with a shared lock of the file at
+    /// `path`, or `None` if another process holds an exclusive lock of it.
+    pub fn try_lock_shared<P>(&self, path: P) -> FsFuture<Option<FsFileLock>>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.try_lock(path, ::lock::Mode::Shared)
+    }
+
+    /// Returns a `Future` that resolves when the target file is deleted.
+    pub fn delete<P>(&self, path: P) -> FsFuture<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.exec_as(Some(FsOperation::Delete), move || fs::remove_file(path))
+    }
+
+    /// Returns a `std::future::Future` that resolves once the target file is
+    /// deleted, like `delete`.
+    ///
+    /// Requires the `std-future` feature.
+    #[cfg(feature = "std-future")]
+    pub fn delete03<P>(&self, path: P) -> FsCompat<FsFuture<()>>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        FsCompat::new(self.delete(path))
+    }
+
+    /// Returns a `Future` that resolves with whether the target file existed,
+    /// once it is deleted.
+    ///
+    /// Unlike `delete`, a file that doesn't exist isn't an error.
+    pub fn delete_if_exists<P>(&self, path: P) -> FsFuture<bool>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.exec_as(Some(FsOperation::Delete), move || {
+            match fs::remove_file(path) {
+                Ok(()) => Ok(true),
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+                Err(e) => Err(e),
+            }
+        })
+    }
+
+    /// Returns a `Future` that resolves with a summary of deleting all of the
+    /// supplied files.
+    ///
+    /// The files are deleted in chunks, spread over the threads of the pool.
+    /// A failure to delete one file doesn't stop the others from being
+    /// deleted, and is reported in the summary instead. Like `delete`, a
+    /// directory fails to be deleted.
+    pub fn delete_all<I>(&self, paths: I, opts: DeleteOptions) -> FsFuture<DeleteSummary>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<Path>,
+    {
+        ::delete::delete_all(self, ::delete::collect(paths), opts)
+    }
+
+    /// Returns a `Future` that resolves with the metadata of the file at the
+    /// supplied path.
+    pub fn metadata<P>(&self, path: P) -> FsFuture<fs::Metadata>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.exec_as(Some(FsOperation::Metadata), move || fs::metadata(path))
+    }
+
+    /// Returns a `Future` that resolves with the metadata of the supplied file.
+    pub fn metadata_file(&self, file: &fs::File) -> FsFuture<fs::Metadata> {
+        match file.try_clone() {
+            Ok(file) => self.exec_as(Some(FsOperation::Metadata), move || file.metadata()),
+            Err(e) => failed(e),
+        }
+    }
+
+    /// Returns a `Future` that resolves when the file at `from` has been
+    /// renamed to `to`, replacing `to` if it already exists.
+    ///
+    /// Errors such as renaming across devices are returned untouched, so that
+    /// callers can decide to fall back to a copy.
+    pub fn rename<P, Q>(&self, from: P, to: Q) -> FsFuture<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+        Q: AsRef<Path> + Send + 'static,
+    {
+        self.exec_as(Some(FsOperation::Metadata), move || fs::rename(from, to))
+    }
+
+    /// Returns a `Future` that resolves when the file at `from` is moved to
+    /// `to`.
+    ///
+    /// The file is renamed if possible. Across filesystems, it is copied to a
+    /// temporary file next to `to` instead, which is synced and renamed into
+    /// place before `from` is deleted, so that a failed copy never leaves a
+    /// partial file at `to`. Only files, not directories, can be copied.
+    pub fn move_file<P, Q>(&self, from: P, to: Q) -> FsFuture<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+        Q: AsRef<Path> + Send + 'static,
+    {
+        self.move_file_with(from, to, Default::default())
+    }
+
+    /// Returns a `Future` that resolves when the file at `from` is moved to
+    /// `to`, as configured by `opts`.
+    ///
+    /// See `move_file` for how a file is moved.
+    pub fn move_file_with<P, Q>(&self, from: P, to: Q, opts: MoveOptions) -> FsFuture<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+        Q: AsRef<Path> + Send + 'static,
+    {
+        let (metrics, rate) = (self.metrics.clone(), self.rate.clone());
+        self.exec(move || {
+            let meter = ::copy::Meter::new(&metrics, rate.as_deref());
+            ::copy::move_file(from.as_ref(), to.as_ref(), &opts, meter)
+        })
+    }
+
+    /// Returns a `Future` that resolves with the number of bytes copied from
+    /// `from` to `to`.
+    ///
+    /// The whole copy happens on the pool, with the same semantics as
+    /// `std::fs::copy`, including copying the permission bits. This is
+    /// `copy_with` with the default options.
+    pub fn copy<P, Q>(&self, from: P, to: Q) -> FsFuture<u64>
+    where
+        P: AsRef<Path> + Send + 'static,
+        Q: AsRef<Path> + Send + 'static,
+    {
+        self.copy_with(from, to, Default::default())
+    }
+
+    /// Returns a `Future` that resolves with the number of bytes copied from
+    /// `from` to `to`, as configured by `opts`.
+    ///
+    /// The whole copy happens on a single thread of the pool. On Linux, the
+    /// bytes are copied within the kernel with `copy_file_range` where
+    /// possible, and otherwise through a buffer. Like `copy`, the permission
+    /// bits are copied as well.
+    pub fn copy_with<P, Q>(&self, from: P, to: Q, opts: CopyOptions) -> FsFuture<u64>
+    where
+        P: AsRef<Path> + Send + 'static,
+        Q: AsRef<Path> + Send + 'static,
+    {
+        let (metrics, rate) = (self.metrics.clone(), self.rate.clone());
+        self.exec(move || {
+            ::copy::copy(
+                from.as_ref(),
+                to.as_ref(),
+                &opts,
+                &mut ::copy::Reporter::none(),
+                ::copy::Meter::new(&metrics, rate.as_deref()),
+            )
+        })
+    }
+
+    /// Returns a `Future` that resolves with the number of bytes copied from
+    /// `from` to `to`, along with a `Stream` of the progress of the copy.
+    ///
+    /// The file is copied like `copy_with`, reporting progress as configured
+    /// by `opts`. The last progress of a successful copy is the length of the
+    /// file.
+    pub fn copy_with_progress<P, Q>(
+        &self,
+        from: P,
+        to: Q,
+        opts: CopyOptions,
+    ) -> (FsFuture<u64>, FsProgress)
+    where
+        P: AsRef<Path> + Send + 'static,
+        Q: AsRef<Path> + Send + 'static,
+    {
+        ::copy::copy_with_progress(self, from, to, opts)
+    }
+
+    /// Returns a `Future` that resolves with the number of bytes copied from
+    /// `from` to `to`, copying segments of the file on several threads of the
+    /// pool at once.
+    ///
+    /// The space for the copy is reserved up front, and each segment is
+    /// copied with positioned reads and writes. If copying a segment fails,
+    /// the other segments stop as well, and the future resolves with that
+    /// error once they have. The number of segments and what happens to a
+    /// partial copy are configured by `opts`, while `CopyOptions::sparse` and
+    /// `CopyOptions::allow_reflink` have no effect here.
+    pub fn copy_parallel<P, Q>(&self, from: P, to: Q, opts: CopyOptions) -> FsFuture<u64>
+    where
+        P: AsRef<Path> + Send + 'static,
+        Q: AsRef<Path> + Send + 'static,
+    {
+        ::copy::copy_parallel(self, from, to, opts)
+    }
+
+    /// Returns a `Future` that resolves with a summary of copying the
+    /// directory at `from`, and everything in it, to `to`.
+    ///
+    /// The whole tree is walked and copied on a single thread of the pool.
+    /// Directories are created as needed, with existing ones at `to` being
+    /// copied into, and regular files are copied like `copy_with`. Other
+    /// entries, like sockets, are skipped. An error names the path that
+    /// failed to be copied.
+    pub fn copy_dir_all<P, Q>(
+        &self,
+        from: P,
+        to: Q,
+        opts: CopyDirOptions,
+    ) -> FsFuture<CopyDirSummary>
+    where
+        P: AsRef<Path> + Send + 'static,
+        Q: AsRef<Path> + Send + 'static,
+    {
+        let (metrics, rate) = (self.metrics.clone(), self.rate.clone());
+        self.exec(move || {
+            let meter = ::copy::Meter::new(&metrics, rate.as_deref());
+            ::copy::copy_dir_all(from.as_ref(), to.as_ref(), &opts, meter)
+        })
+    }
+
+    /// Returns a `Future` that resolves when a new directory is created at
+    /// the supplied path.
+    pub fn create_dir<P>(&self, path: P) -> FsFuture<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.exec_as(Some(FsOperation::Metadata), move || fs::create_dir(path))
+    }
+
+    /// Returns a `Future` that resolves when the directory at the supplied
+    /// path, and all of its missing parents, have been created.
+    pub fn create_dir_all<P>(&self, path: P) -> FsFuture<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.exec_as(Some(FsOperation::Metadata), move || {
+            fs::create_dir_all(path)
+        })
+    }
+
+    /// Returns a `Future` that resolves when the empty directory at the
+    /// supplied path is removed.
+    pub fn remove_dir<P>(&self, path: P) -> FsFuture<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.exec_as(Some(FsOperation::Delete), move || fs::remove_dir(path))
+    }
+
+    /// Returns a `Future` that resolves when the directory at the supplied
+    /// path, and all of its contents, are removed.
+    ///
+    /// Like `std::fs::remove_dir_all`, symlinks inside the tree are removed
+    /// rather than followed.
+    pub fn remove_dir_all<P>(&self, path: P) -> FsFuture<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.exec_as(Some(FsOperation::Delete), move || fs::remove_dir_all(path))
+    }
+
+    /// Returns a `Stream` of the entries in the directory at the supplied path.
+    pub fn read_dir<P>(&self, path: P) -> FsDirStream
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        ::dir::new(self, path, ReadDirOptions::default())
+    }
+
+    /// Returns a `Stream` of the entries in the directory at the supplied
+    /// path, as configured by `opts`.
+    pub fn read_dir_with<P>(&self, path: P, opts: ReadDirOptions) -> FsDirStream
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        ::dir::new(self, path, opts)
+    }
+
+    /// Returns a `Stream` of every entry in the directory tree at the
+    /// supplied path, as configured by `opts`.
+    pub fn walk<P>(&self, path: P, opts: WalkOptions) -> FsWalkStream
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        ::walk::new(self, path, opts)
+    }
+
+    /// Returns a `Future` that resolves with the total length of all files in
+    /// the directory tree at the supplied path.
+    ///
+    /// This is `dir_stats` with the default options.
+    pub fn dir_size<P>(&self, path: P) -> FsFuture<u64>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.exec(move || {
+            let stats = ::walk::dir_stats(path.as_ref(), &DirStatsOptions::default())?;
+            Ok(stats.bytes())
+        })
+    }
+
+    /// Returns a `Future` that resolves with the totals of the directory tree
+    /// at the supplied path, as configured by `opts`.
+    ///
+    /// The whole tree is walked on a single thread of the pool. An error
+    /// names the path that failed to be read.
+    pub fn dir_stats<P>(&self, path: P, opts: DirStatsOptions) -> FsFuture<DirStats>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.exec(move || ::walk::dir_stats(path.as_ref(), &opts))
+    }
+
+    /// Returns a `Stream` of the paths matching the supplied glob pattern.
+    ///
+    /// Within a component of the pattern, `*` matches any number of
+    /// characters, `?` matches a single character, and `[...]` matches a
+    /// single character in a set like `[abc]` or a range like `[a-z]`, or
+    /// outside of them like `[!a-z]`. A component of just `**` matches any
+    /// number of nested directories, so `logs/**/*.gz` matches every file
+    /// ending in `.gz` anywhere below `logs`. It follows a link to a
+    /// directory only the first time it finds the directory, and never into
+    /// a directory that contains the link. A relative pattern is matched
+    /// from the current directory.
+    pub fn glob(&self, pattern: &str) -> FsGlobStream {
+        ::glob::new(self, pattern)
+    }
+
+    /// Returns a `Future` that resolves when a symbolic link at `dst`,
+    /// pointing to `src`, has been created.
+    ///
+    /// On Windows, a directory or file link is created depending on what
+    /// `src` currently points to.
+    pub fn symlink<P, Q>(&self, src: P, dst: Q) -> FsFuture<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+        Q: AsRef<Path> + Send + 'static,
+    {
+        self.exec_as(Some(FsOperation::Metadata), move || {
+            symlink(src.as_ref(), dst.as_ref())
+        })
+    }
+
+    /// Returns a `Future` that resolves with the target of the symbolic link
+    /// at the supplied path, exactly as stored in the link.
+    pub fn read_link<P>(&self, path: P) -> FsFuture<PathBuf>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.exec_as(Some(FsOperation::Metadata), move || fs::read_link(path))
+    }
+
+    /// Returns a `Future` that resolves when a hard link at `dst`, pointing
+    /// to the same file as `src`, has been created.
+    pub fn hard_link<P, Q>(&self, src: P, dst: Q) -> FsFuture<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+        Q: AsRef<Path> + Send + 'static,
+    {
+        self.exec_as(Some(FsOperation::Metadata), move || fs::hard_link(src, dst))
+    }
+
+    /// Returns a `Future` that resolves when the permissions of the file at
+    /// the supplied path have been changed.
+    ///
+    /// On Windows, only the readonly flag is applied.
+    pub fn set_permissions<P>(&self, path: P, perms: fs::Permissions) -> FsFuture<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.exec_as(Some(FsOperation::Metadata), move || {
+            fs::set_permissions(path, perms)
+        })
+    }
+
+    /// Returns a `Future` that resolves when the readonly flag of the file at
+    /// the supplied path has been changed, leaving other permissions as is.
+    pub fn set_readonly<P>(&self, path: P, readonly: bool) -> FsFuture<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.exec_as(Some(FsOperation::Metadata), move || {
+            let mut perms = fs::metadata(&path)?.permissions();
+            perms.set_readonly(readonly);
+            fs::set_permissions(path, perms)
+        })
+    }
+
+    /// Returns a `Future` that resolves with the canonical, absolute form of
+    /// the supplied path, with all symbolic links resolved.
+    pub fn canonicalize<P>(&self, path: P) -> FsFuture<PathBuf>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.exec_as(Some(FsOperation::Metadata), move || fs::canonicalize(path))
+    }
+
+    /// Returns a `Future` that resolves with the metadata of the file at the
+    /// supplied path, without following symbolic links.
+    pub fn symlink_metadata<P>(&self, path: P) -> FsFuture<fs::Metadata>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.exec_as(Some(FsOperation::Metadata), move || {
+            fs::symlink_metadata(path)
+        })
+    }
+
+    /// Returns a `Future` that resolves when the file at the supplied path has
+    /// been truncated or extended to `size` bytes.
+    pub fn set_len<P>(&self, path: P, size: u64) -> FsFuture<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.exec_as(Some(FsOperation::Metadata), move || {
+            fs::OpenOptions::new().write(true).open(path)?.set_len(size)
+        })
+    }
+
+    /// Returns a `Future` that resolves when the supplied file has been
+    /// truncated or extended to `size` bytes.
+    pub fn set_len_file(&self, file: &fs::File, size: u64) -> FsFuture<()> {
+        match file.try_clone() {
+            Ok(file) => self.exec_as(Some(FsOperation::Metadata), move || file.set_len(size)),
+            Err(e) => failed(e),
+        }
+    }
+
+    /// Returns a `Future` that resolves with statistics of the filesystem
+    /// that the file at `path` is on, such as how much space is free.
+    pub fn statfs<P>(&self, path: P) -> FsFuture<FsStats>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.exec(move || ::sys::statfs(path.as_ref()))
+    }
+
+    /// Returns a `Future` that resolves when the access and modification
+    /// times of the file at `path` have been set.
+    ///
+    /// The times are kept as precisely as the platform and filesystem allow,
+    /// down to nanoseconds.
+    pub fn set_file_times<P>(
+        &self,
+        path: P,
+        accessed: SystemTime,
+        modified: SystemTime,
+    ) -> FsFuture<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.exec_as(Some(FsOperation::Metadata), move || {
+            open_for_sync(path.as_ref())?.set_times(file_times(accessed, modified))
+        })
+    }
+
+    /// Returns a `Future` that resolves when the access and modification
+    /// times of the supplied file have been set.
+    ///
+    /// Setting the times of a file that is still being written to only lasts
+    /// until the next write, so do it once everything is written.
+    pub fn set_file_times_file(
+        &self,
+        file: &fs::File,
+        accessed: SystemTime,
+        modified: SystemTime,
+    ) -> FsFuture<()> {
+        match file.try_clone() {
+            Ok(file) => self.exec_as(Some(FsOperation::Metadata), move || {
+                file.set_times(file_times(accessed, modified))
+            }),
+            Err(e) => failed(e),
+        }
+    }
+
+    /// Returns a `Future` that resolves when all data and metadata of the
+    /// file at the supplied path have been synced to disk.
+    pub fn sync_all<P>(&self, path: P) -> FsFuture<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.exec(move || open_for_sync(path.as_ref())?.sync_all())
+    }
+
+    /// Returns a `Future` that resolves when the data of the file at the
+    /// supplied path has been synced to disk.
+    ///
+    /// Unlike `sync_all`, this may not sync metadata such as modification
+    /// times.
+    pub fn sync_data<P>(&self, path: P) -> FsFuture<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        self.exec(move || open_for_sync(path.as_ref())?.sync_data())
+    }
+
+    /// Returns a `Future` that resolves when all data and metadata of the
+    /// supplied file have been synced to disk.
+    pub fn sync_all_file(&self, file: &fs::File) -> FsFuture<()> {
+        match file.try_clone() {
+            Ok(file) => self.exec(move || file.sync_all()),
+            Err(e) => failed(e),
+        }
+    }
+
+    /// Returns a `Future` that resolves when the data of the supplied file
+    /// has been synced to disk.
+    pub fn sync_data_file(&self, file: &fs::File) -> FsFuture<()> {
+        match file.try_clone() {
+            Ok(file) => self.exec(move || file.sync_data()),
+            Err(e) => failed(e),
+        }
+    }
+
+    /// Returns a `Future` that resolves with the result of running `f` on
+    /// the pool.
+    ///
+    /// This is for any blocking work that there is no method for, like an
+    /// `ioctl`. If `f` panics, the future fails with an error instead, and
+    /// the pool keeps running.
+    pub fn spawn_fn<F, T>(&self, f: F) -> FsFuture<T>
+    where
+        F: FnOnce() -> io::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.exec(f)
+    }
+
+    /// Returns a `Future` that resolves once every operation on the pool has
+    /// finished.
+    ///
+    /// This closes the pool for all of its clones, so that any operation
+    /// started afterwards fails with an error instead. Operations that are
+    /// already running still finish, even if they are made of more work on
+    /// the pool, like copying a directory. Streams and sinks count as
+    /// running until they are finished or dropped, so a sink should be
+    /// closed for its writes to be complete.
+    pub fn close(&self) -> FsFuture<()> {
+        self.tasks.close()
+    }
+
+    /// Returns a snapshot of the counters of this pool, and all of its
+    /// clones.
+    ///
+    /// The counters are kept with relaxed atomics, so this is cheap enough
+    /// to sample often, though counters that change while being read may be
+    /// slightly off from each other.
+    pub fn metrics(&self) -> FsPoolMetrics {
+        self.metrics.snapshot(self.tasks.queued())
+    }
+
+    fn lock<P>(&self, path: P, mode: ::lock::Mode) -> FsFuture<FsFileLock>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        let pool = self.clone();
+        self.exec(move || ::lock::lock(&pool, path.as_ref(), mode))
+    }
+
+    fn try_lock<P>(&self, path: P, mode: ::lock::Mode) -> FsFuture<Option<FsFileLock>>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        let pool = self.clone();
+        self.exec(move || ::lock::try_lock(&pool, path.as_ref(), mode))
+    }
+
+    fn exec<F, T>(&self, f: F) -> FsFuture<T>
+    where
+        F: FnOnce() -> io::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.exec_as(None, f)
+    }
+
+    /// Like `exec`, counting the operation as `kind` in the metrics.
+    fn exec_as<F, T>(&self, kind: Option<FsOperation>, f: F) -> FsFuture<T>
+    where
+        F: FnOnce() -> io::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        let mut op = ::metrics::start(&self.metrics, kind);
+
+        let fut = Box::new(lazy(move || {
+            // the work is skipped if the future was dropped before it ran
+            if tx.is_canceled() {
+                return Err(());
+            }
+            let res = refused().and_then(|()| {
+                panic::catch_unwind(AssertUnwindSafe(f))
+                    .unwrap_or_else(|_| Err(io::Error::other("fs task panicked")))
+            });
+            op.set_failed(res.is_err());
+            drop(op);
+            tx.send(res).map_err(|_| ())
+        }));
+
+        if let Err(e) = self.execute(fut) {
+            return failed(e);
+        }
+
+        fs(rx)
+    }
+
+    /// Spawns `fut` on the executor, failing if it refuses to run it.
+    fn execute(&self, fut: Box<dyn Future<Item = (), Error = ()> + Send>) -> io::Result<()> {
+        if !self.tasks.accepts() {
+            return Err(io::Error::other("fs pool is closed"));
+        }
+        self.spawn_cleanup(fut)
+    }
+
+    /// Spawns `fut` on the executor, even once the pool is closed.
+    fn spawn_cleanup(&self, fut: Box<dyn Future<Item = (), Error = ()> + Send>) -> io::Result<()> {
+        let fut = Box::new(::drain::track(&self.tasks, fut));
+        let id = self.queues.push(self.priority, fut);
+        let runner = ::priority::runner(&self.queues, self.priority, id);
+        let e = match self.executor.execute(runner) {
+            Ok(()) => return Ok(()),
+            Err(e) => e,
+        };
+        // a runner of another task may have taken it already
+        if !self.queues.remove(self.priority, id) {
+            return Ok(());
+        }
+        match e.kind() {
+            ExecuteErrorKind::NoCapacity => Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "fs executor is at capacity",
+            )),
+            _ => Err(io::Error::other("fs executor is shut down")),
+        }
+    }
+}
+
+impl Default for FsPool {
+    fn default() -> FsPool {
+        FsPool::builder().build()
+    }
+}
+
+impl fmt::Debug for FsPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsPool").finish()
+    }
+}
+
+// ===== impl FsFuture =====
+
+/// A future representing work in the `FsPool`.
+///
+/// Dropping the future before the work has started on the pool skips it.
+pub struct FsFuture<T> {
+    inner: Receiver<io::Result<T>>,
+    context: Option<::error::Context>,
+    timeout: Option<::timeout::Timeout>,
+}
+
+fn fs<T: Send>(rx: Receiver<io::Result<T>>) -> FsFuture<T> {
+    FsFuture {
+        inner: rx,
+        context: None,
+        timeout: None,
+    }
+}
+
+fn failed<T: Send>(err: io::Error) -> FsFuture<T> {
+    let (tx, rx) = oneshot::channel();
+    let _ = tx.send(Err(err));
+    fs(rx)
+}
+
+impl<T> FsFuture<T> {
+    /// Wraps the error this future fails with in an `FsError`, recording
+    /// the `operation` and `path` that failed.
+    pub fn with_context<P>(self, operation: FsOperation, path: P) -> FsFuture<T>
+    where
+        P: Into<PathBuf>,
+    {
+        let context = ::error::Context::new(operation, Arc::new(path.into()));
+        self.context(Some(context))
+    }
+
+    /// Fails this future with an error of kind `TimedOut`, if it doesn't
+    /// resolve within `timeout`.
+    ///
+    /// `timer` is called to get a future that resolves after the supplied
+    /// duration, like a delay of the runtime of the caller. It is called
+    /// again for the time that is left, if that future resolves early. A
+    /// timer that fails counts as expired.
+    ///
+    /// Once timed out, work that hasn't started on the pool yet is skipped.
+    /// Work that is already running, like a call blocked on a hung mount,
+    /// can't be interrupted and still occupies its pool thread until it
+    /// returns.
+    pub fn timeout<F, U>(mut self, timeout: Duration, timer: F) -> FsFuture<T>
+    where
+        F: Fn(Duration) -> U + Send + Sync + 'static,
+        U: Future<Item = ()> + Send + 'static,
+    {
+        let timer = ::timeout::Timer::new(timer);
+        self.timeout = Some(::timeout::Timeout::new(timeout, &timer));
+        self
+    }
+
+    fn context(mut self, context: Option<::error::Context>) -> FsFuture<T> {
+        if context.is_some() {
+            self.context = context;
+        }
+        self
+    }
+}
+
+impl<T: Send + 'static> Future for FsFuture<T> {
+    type Item = T;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        // the task is dropped without an answer when the executor drops it,
+        // such as when shutting down
+        let e = match self.inner.poll() {
+            Ok(Async::Ready(Ok(item))) => return Ok(Async::Ready(item)),
+            Ok(Async::Ready(Err(e))) => e,
+            Ok(Async::NotReady) => {
+                let expired = match self.timeout {
+                    Some(ref mut timeout) => timeout.poll_expired(),
+                    None => false,
+                };
+                if !expired {
+                    return Ok(Async::NotReady);
+                }
+                // dropping the receiver skips the work, if it hasn't started
+                self.inner = oneshot::channel().1;
+                self.timeout = None;
+                timed_out()
+            }
+            Err(_) => canceled(),
+        };
+        match self.context {
+            Some(ref context) => Err(context.wrap(e)),
+            None => Err(e),
+        }
+    }
+}
+
+impl<T> fmt::Debug for FsFuture<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsFuture").finish()
+    }
+}
+
+/// Whether `opts` open a file for writing or appending.
+///
+/// `OpenOptions` has no getters, but its `Debug` output names both flags on
+/// every platform.
+fn opens_for_writing(opts: &fs::OpenOptions) -> bool {
+    let opts = format!("{:?}", opts);
+    opts.contains("write: true") || opts.contains("append: true")
+}
+
+/// Fails if the work being run can't block its thread, like that of
+/// `FsPool::with_tokio_blocking` outside of a tokio threadpool.
+fn refused() -> io::Result<()> {
+    #[cfg(feature = "tokio-blocking")]
+    ::tokio_blocking::check()?;
+    Ok(())
+}
+
+fn canceled() -> io::Error {
+    io::Error::other("fs task canceled")
+}
+
+fn timed_out() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "fs task timed out")
+}
+
+/// Adds the path that failed to an error.
+fn path_error(path: &Path, e: io::Error) -> io::Error {
+    io::Error::new(e.kind(), format!("{}: {}", path.display(), e))
+}
+
+fn file_times(accessed: SystemTime, modified: SystemTime) -> fs::FileTimes {
+    fs::FileTimes::new()
+        .set_accessed(accessed)
+        .set_modified(modified)
+}
+
+fn open_for_sync(path: &Path) -> io::Result<fs::File> {
+    // Windows requires write access to flush a file
+    fs::OpenOptions::new()
+        .read(true)
+        .write(cfg!(windows))
+        .open(path)
+}
+
+#[cfg(unix)]
+fn symlink(src: &Path, dst: &Path) -> io::Result<()> {
+    ::std::os::unix::fs::symlink(src, dst)
+}
+
+#[cfg(windows)]
+fn symlink(src: &Path, dst: &Path) -> io::Result<()> {
+    use std::os::windows::fs::{symlink_dir, symlink_file};
+
+    let is_dir = fs::metadata(src).map(|meta| meta.is_dir()).unwrap_or(false);
+    if is_dir {
+        symlink_dir(src, dst)
+    } else {
+        symlink_file(src, dst)
+    }
+}
+
+fn _assert_kinds() {
+    fn assert_send<T: Send>() {}
+    fn assert_sync<T: Sync>() {}
+    fn assert_clone<T: Clone>() {}
+
+    assert_send::<FsPool>();
+    assert_sync::<FsPool>();
+    assert_clone::<FsPool>();
+
+    assert_send::<FsFuture<()>>();
+    assert_send::<FsDirStream>();
+    assert_send::<FsFile>();
+    assert_sync::<FsFile>();
+    assert_send::<FsBlockingRead>();
+    assert_send::<FsBlockingWrite>();
+    assert_send::<FsChunksExact>();
+    assert_send::<FsConcatStream>();
+    assert_send::<FsGlobStream>();
+    assert_send::<FsLines>();
+    assert_send::<FsManyReadStream>();
+    assert_send::<FsOffsetReadStream>();
+    assert_send::<FsProgress>();
+    assert_send::<FsPositionedWriteSink>();
+    assert_send::<FsSplit>();
+    assert_send::<FsTempDir>();
+    assert_send::<FsWalkStream>();
+    assert_send::<FsWatchStream>();
+    #[cfg(feature = "std-future")]
+    {
+        assert_send::<FsCompat<FsReadStream>>();
+        assert_send::<FsCompatSink<FsWriteSink>>();
+    }
+    #[cfg(feature = "mmap")]
+    {
+        assert_send::<FsMmap>();
+        assert_sync::<FsMmap>();
+        assert_send::<FsMmapMut>();
+        assert_sync::<FsMmapMut>();
+    }
+    #[cfg(feature = "tokio-io")]
+    {
+        assert_send::<FsAsyncRead>();
+        assert_send::<FsAsyncWrite>();
+    }
 }
@@ -0,0 +1,205 @@
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{cmp, fmt, io, mem};
+
+use futures::sync::oneshot;
+use futures::{Async, Future, Poll};
+
+use sys;
+use timeout::{Expiry, Timer};
+use FsFuture;
+use FsPool;
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(1);
+const MAX_BACKOFF: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy)]
+pub(crate) enum Mode {
+    Shared,
+    Exclusive,
+}
+
+impl Mode {
+    fn is_exclusive(self) -> bool {
+        match self {
+            Mode::Shared => false,
+            Mode::Exclusive => true,
+        }
+    }
+}
+
+pub(crate) fn lock(pool: &FsPool, path: &Path, mode: Mode) -> io::Result<FsFileLock> {
+    let file = open(path, mode)?;
+    sys::lock(&file, mode.is_exclusive(), true)?;
+    Ok(FsFileLock::new(pool, file))
+}
+
+pub(crate) fn try_lock(pool: &FsPool, path: &Path, mode: Mode) -> io::Result<Option<FsFileLock>> {
+    let file = open(path, mode)?;
+    if sys::lock(&file, mode.is_exclusive(), false)? {
+        Ok(Some(FsFileLock::new(pool, file)))
+    } else {
+        Ok(None)
+    }
+}
+
+pub(crate) fn lock_timeout(
+    pool: &FsPool,
+    path: PathBuf,
+    mode: Mode,
+    timeout: Duration,
+) -> FsFuture<FsFileLock> {
+    let mut lock = LockTimeout {
+        pool: pool.clone(),
+        path: Arc::new(path),
+        mode,
+        deadline: Instant::now() + timeout,
+        backoff: INITIAL_BACKOFF,
+        timer: pool.timer(),
+        state: State::Swapping,
+    };
+    lock.try_lock();
+    let (tx, rx) = oneshot::channel();
+    let fut = Box::new(lock.then(|res| tx.send(res).map_err(|_| ())));
+    if let Err(e) = pool.execute(fut) {
+        return ::failed(e);
+    }
+    ::fs(rx)
+}
+
+/// Tries to lock a file on the pool, waiting longer and longer in between,
+/// until the deadline passes.
+struct LockTimeout {
+    pool: FsPool,
+    path: Arc<PathBuf>,
+    mode: Mode,
+    deadline: Instant,
+    backoff: Duration,
+    /// The timer of the pool, to wait with in between tries.
+    timer: Timer,
+    state: State,
+}
+
+enum State {
+    Trying(FsFuture<Option<FsFileLock>>),
+    Waiting(Expiry),
+    Swapping,
+}
+
+impl LockTimeout {
+    fn try_lock(&mut self) {
+        let pool = self.pool.clone();
+        let path = self.path.clone();
+        let mode = self.mode;
+        self.state = State::Trying(self.pool.exec(move || try_lock(&pool, &path, mode)));
+    }
+}
+
+impl Future for LockTimeout {
+    type Item = FsFileLock;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            match mem::replace(&mut self.state, State::Swapping) {
+                State::Trying(mut fut) => {
+                    let polled = fut.poll();
+                    self.state = State::Trying(fut);
+                    if let Some(lock) = try_ready!(polled) {
+                        return Ok(Async::Ready(lock));
+                    }
+                    let now = Instant::now();
+                    if now >= self.deadline {
+                        return Err(::path_error(
+                            &self.path,
+                            io::Error::new(
+                                io::ErrorKind::TimedOut,
+                                "timed out waiting for the lock",
+                            ),
+                        ));
+                    }
+                    let wait = cmp::min(self.backoff, self.deadline - now);
+                    self.backoff = cmp::min(self.backoff * 2, MAX_BACKOFF);
+                    self.state = State::Waiting(self.timer.delay(wait));
+                }
+                State::Waiting(mut delay) => {
+                    if let Ok(Async::NotReady) = delay.poll() {
+                        self.state = State::Waiting(delay);
+                        return Ok(Async::NotReady);
+                    }
+                    self.try_lock();
+                }
+                State::Swapping => unreachable!(),
+            }
+        }
+    }
+}
+
+/// Opens the file to lock, creating it if it doesn't exist.
+///
+/// A shared lock only opens the file for reading, so that a file that can't
+/// be written can still be locked.
+fn open(path: &Path, mode: Mode) -> io::Result<File> {
+    let mut open = OpenOptions::new();
+    open.read(true).write(true).create(true).truncate(false);
+    match mode {
+        Mode::Exclusive => open.open(path),
+        Mode::Shared => match File::open(path) {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                open.open(path)?;
+                File::open(path)
+            }
+            res => res,
+        },
+    }
+}
+
+/// An advisory lock on a file, that is released when dropped.
+///
+/// Created by `FsPool::lock_exclusive` and `FsPool::lock_shared`, and their
+/// `try_lock_*` variants. The lock belongs to this handle of the file, so
+/// even another lock of the same file in this process has to wait for it.
+pub struct FsFileLock {
+    file: Option<File>,
+    pool: FsPool,
+}
+
+impl FsFileLock {
+    fn new(pool: &FsPool, file: File) -> FsFileLock {
+        FsFileLock {
+            file: Some(file),
+            pool: pool.clone(),
+        }
+    }
+
+    /// The locked file, opened for reading, and for writing as well if the
+    /// lock is exclusive.
+    pub fn file(&self) -> &File {
+        self.file.as_ref().expect("lock already released")
+    }
+
+    /// Returns a `Future` that resolves when the lock is released.
+    pub fn unlock(mut self) -> FsFuture<()> {
+        let file = self.file.take().expect("lock already released");
+        self.pool.exec(move || sys::unlock(&file))
+    }
+}
+
+impl Drop for FsFileLock {
+    fn drop(&mut self) {
+        if let Some(file) = self.file.take() {
+            // closing the file releases the lock anyway
+            let _ = sys::unlock(&file);
+        }
+    }
+}
+
+impl fmt::Debug for FsFileLock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsFileLock")
+            .field("file", &self.file)
+            .finish()
+    }
+}
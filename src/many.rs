@@ -0,0 +1,197 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::{cmp, fmt, io};
+
+use bytes::Bytes;
+use futures::sync::mpsc;
+use futures::{Async, Future, Poll, Sink, Stream};
+
+use metrics;
+use FsOperation;
+use FsPool;
+
+/// The number of files read at once, when the size of the pool is unknown.
+const DEFAULT_CONCURRENCY: usize = 4;
+
+type Item = (PathBuf, io::Result<Bytes>);
+
+pub(crate) fn new<I>(pool: &FsPool, paths: I) -> FsManyReadStream
+where
+    I: IntoIterator<Item = PathBuf>,
+{
+    FsManyReadStream {
+        pool: pool.clone(),
+        paths: paths.into_iter().enumerate().collect(),
+        max_size: None,
+        ordered: false,
+        items: None,
+        next: 0,
+        pending: BTreeMap::new(),
+    }
+}
+
+/// A `Stream` of the whole contents of many files, along with their paths.
+///
+/// Created by `FsPool::read_many`. Once polled, as many files as the pool
+/// has threads are read at once, by tasks that each take the next path until
+/// none are left. A file that can't be read yields its error, and the other
+/// files are still read.
+pub struct FsManyReadStream {
+    pool: FsPool,
+    paths: VecDeque<(usize, PathBuf)>,
+    max_size: Option<u64>,
+    ordered: bool,
+    items: Option<mpsc::Receiver<(usize, Item)>>,
+    /// The index of the next item to yield in order.
+    next: usize,
+    /// The items read ahead of those before them, while in order.
+    pending: BTreeMap<usize, Item>,
+}
+
+impl FsManyReadStream {
+    /// The most bytes to read of each file.
+    ///
+    /// A larger file yields an error instead, without being read.
+    ///
+    /// Default is no maximum.
+    pub fn max_size(mut self, max_size: u64) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Whether to yield the files in the order of their paths, instead of
+    /// in the order they were read.
+    ///
+    /// Files that were read before those ahead of them are buffered, until
+    /// they are next.
+    ///
+    /// Default is `false`.
+    pub fn ordered(mut self, ordered: bool) -> Self {
+        self.ordered = ordered;
+        self
+    }
+
+    fn start(&mut self) -> mpsc::Receiver<(usize, Item)> {
+        let tasks = cmp::min(
+            self.pool.threads.unwrap_or(DEFAULT_CONCURRENCY),
+            cmp::max(self.paths.len(), 1),
+        );
+        let (tx, rx) = mpsc::channel(tasks);
+        let queue = Arc::new(Mutex::new(self.paths.split_off(0)));
+        for _ in 0..tasks {
+            let reader = Reader {
+                pool: self.pool.clone(),
+                queue: queue.clone(),
+                max_size: self.max_size,
+                items: tx.clone(),
+            };
+            if let Err(e) = self.pool.execute(Box::new(reader)) {
+                // the paths no task will take fail with the error instead
+                let mut queue = queue.lock().unwrap();
+                while let Some((i, path)) = queue.pop_front() {
+                    let e = io::Error::new(e.kind(), e.to_string());
+                    self.pending.insert(i, (path, Err(e)));
+                }
+                break;
+            }
+        }
+        rx
+    }
+}
+
+impl Stream for FsManyReadStream {
+    type Item = Item;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        if self.items.is_none() {
+            self.items = Some(self.start());
+        }
+        loop {
+            if self.ordered {
+                if let Some(item) = self.pending.remove(&self.next) {
+                    self.next += 1;
+                    return Ok(Async::Ready(Some(item)));
+                }
+            } else if let Some(&i) = self.pending.keys().next() {
+                return Ok(Async::Ready(self.pending.remove(&i)));
+            }
+
+            let items = self.items.as_mut().unwrap();
+            match items.poll() {
+                Ok(Async::Ready(Some((i, item)))) => {
+                    if !self.ordered {
+                        return Ok(Async::Ready(Some(item)));
+                    }
+                    self.pending.insert(i, item);
+                }
+                Ok(Async::Ready(None)) | Err(()) => {
+                    // what is left was read out of order, after a gap
+                    if self.pending.is_empty() {
+                        return Ok(Async::Ready(None));
+                    }
+                    let i = *self.pending.keys().next().unwrap();
+                    self.next = i;
+                }
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+            }
+        }
+    }
+}
+
+impl fmt::Debug for FsManyReadStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsManyReadStream")
+            .field("max_size", &self.max_size)
+            .field("ordered", &self.ordered)
+            .finish()
+    }
+}
+
+/// A task on the pool that reads the files of a `FsManyReadStream`, taking
+/// the next path until none are left.
+struct Reader {
+    pool: FsPool,
+    queue: Arc<Mutex<VecDeque<(usize, PathBuf)>>>,
+    max_size: Option<u64>,
+    items: mpsc::Sender<(usize, Item)>,
+}
+
+impl Future for Reader {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        loop {
+            // the stream was dropped, if this fails
+            match self.items.poll_ready() {
+                Ok(Async::Ready(())) => (),
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(_) => return Ok(Async::Ready(())),
+            }
+            let (i, path) = match self.queue.lock().unwrap().pop_front() {
+                Some(next) => next,
+                None => return Ok(Async::Ready(())),
+            };
+
+            let mut running = metrics::start(&self.pool.metrics, Some(FsOperation::Read));
+            let res = ::refused().and_then(|()| ::read::read_to_end(&path, self.max_size));
+            match res {
+                Ok(ref bytes) => {
+                    running.set_failed(false);
+                    running.metrics().read(bytes.len() as u64);
+                    if let Some(ref rate) = self.pool.rate {
+                        rate.take(bytes.len() as u64);
+                    }
+                }
+                Err(_) => running.set_failed(true),
+            }
+            drop(running);
+
+            if self.items.start_send((i, (path, res))).is_err() {
+                return Ok(Async::Ready(()));
+            }
+        }
+    }
+}
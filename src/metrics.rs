@@ -0,0 +1,186 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use FsOperation;
+
+/// The kinds that operations are counted by, with others last.
+const KINDS: usize = 5;
+
+/// The counters of a pool, shared by its clones.
+#[derive(Default)]
+pub(crate) struct Metrics {
+    ops: [Counters; KINDS],
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+}
+
+#[derive(Default)]
+struct Counters {
+    started: AtomicU64,
+    completed: AtomicU64,
+    failed: AtomicU64,
+}
+
+fn index(kind: Option<FsOperation>) -> usize {
+    match kind {
+        Some(FsOperation::Read) => 0,
+        Some(FsOperation::Write) => 1,
+        Some(FsOperation::Delete) => 2,
+        Some(FsOperation::Metadata) => 3,
+        None => 4,
+    }
+}
+
+impl Metrics {
+    pub(crate) fn read(&self, n: u64) {
+        self.bytes_read.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub(crate) fn written(&self, n: u64) {
+        self.bytes_written.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self, queued: usize) -> FsPoolMetrics {
+        let mut ops = [FsOpCounts::default(); KINDS];
+        for (counts, counters) in ops.iter_mut().zip(&self.ops) {
+            *counts = FsOpCounts {
+                started: counters.started.load(Ordering::Relaxed),
+                completed: counters.completed.load(Ordering::Relaxed),
+                failed: counters.failed.load(Ordering::Relaxed),
+            };
+        }
+        FsPoolMetrics {
+            ops,
+            queued,
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Counts an operation as started, until the returned `Running` is dropped.
+pub(crate) fn start(metrics: &Arc<Metrics>, kind: Option<FsOperation>) -> Running {
+    let kind = index(kind);
+    metrics.ops[kind].started.fetch_add(1, Ordering::Relaxed);
+    Running {
+        metrics: metrics.clone(),
+        kind,
+        failed: true,
+    }
+}
+
+/// An operation that is running, which counts as failed when dropped unless
+/// marked otherwise.
+pub(crate) struct Running {
+    metrics: Arc<Metrics>,
+    kind: usize,
+    failed: bool,
+}
+
+impl Running {
+    pub(crate) fn set_failed(&mut self, failed: bool) {
+        self.failed = failed;
+    }
+
+    pub(crate) fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        let counters = &self.metrics.ops[self.kind];
+        if self.failed {
+            counters.failed.fetch_add(1, Ordering::Relaxed);
+        } else {
+            counters.completed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// A snapshot of the counters of an `FsPool`, from `FsPool::metrics`.
+///
+/// The counters are shared by all clones of the pool, and are only ever
+/// incremented, so rates can be found from the difference of two snapshots.
+#[derive(Clone, Debug)]
+pub struct FsPoolMetrics {
+    ops: [FsOpCounts; KINDS],
+    queued: usize,
+    bytes_read: u64,
+    bytes_written: u64,
+}
+
+impl FsPoolMetrics {
+    /// The counts of all operations, of any kind.
+    pub fn total(&self) -> FsOpCounts {
+        let mut total = FsOpCounts::default();
+        for counts in &self.ops {
+            total.started += counts.started;
+            total.completed += counts.completed;
+            total.failed += counts.failed;
+        }
+        total
+    }
+
+    /// The counts of the operations of one kind.
+    ///
+    /// A read or write stream counts as a single operation, as does any
+    /// whole-file read or write. Operations of other kinds, like copying a
+    /// file or walking a directory, are only counted in `total`.
+    pub fn operation(&self, operation: FsOperation) -> FsOpCounts {
+        self.ops[index(Some(operation))]
+    }
+
+    /// The number of operations that have started, but not finished yet.
+    pub fn in_flight(&self) -> u64 {
+        self.total().in_flight()
+    }
+
+    /// The number of tasks waiting for a thread of the pool.
+    pub fn queued(&self) -> usize {
+        self.queued
+    }
+
+    /// The number of bytes read by streams, whole-file reads and copies.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// The number of bytes written by sinks, whole-file writes and copies.
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+}
+
+/// The number of operations started, completed and failed, in an
+/// `FsPoolMetrics`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FsOpCounts {
+    started: u64,
+    completed: u64,
+    failed: u64,
+}
+
+impl FsOpCounts {
+    /// The number of operations started.
+    pub fn started(&self) -> u64 {
+        self.started
+    }
+
+    /// The number of operations that completed successfully.
+    pub fn completed(&self) -> u64 {
+        self.completed
+    }
+
+    /// The number of operations that failed, including work that never ran
+    /// because its future was dropped, and canceled writes.
+    pub fn failed(&self) -> u64 {
+        self.failed
+    }
+
+    /// The number of operations that have started, but not finished yet.
+    pub fn in_flight(&self) -> u64 {
+        // the counters are read one by one, while they may change
+        self.started.saturating_sub(self.completed + self.failed)
+    }
+}
@@ -0,0 +1,199 @@
+use std::fs::{File, OpenOptions};
+use std::ops::{Deref, Range};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::{fmt, hint, io};
+
+use memmap2::{Mmap, MmapMut};
+
+use sys;
+use FsFuture;
+use FsOperation;
+use FsPool;
+use FsReadStream;
+use ReadOptions;
+
+pub(crate) fn map(pool: &FsPool, path: &Path) -> io::Result<FsMmap> {
+    let file = File::open(path)?;
+    // an empty file can't be mapped
+    let map = if file.metadata()?.len() == 0 {
+        None
+    } else {
+        let map = unsafe { Mmap::map(&file)? };
+        // the pages are faulted in here on the pool, rather than by whoever
+        // reads them first
+        let mut touched = 0u8;
+        for i in (0..map.len()).step_by(sys::PAGE_SIZE as usize) {
+            touched = touched.wrapping_add(map[i]);
+        }
+        hint::black_box(touched);
+        Some(Arc::new(map))
+    };
+    Ok(FsMmap {
+        map,
+        file,
+        pool: pool.clone(),
+    })
+}
+
+/// The contents of a file, mapped into memory.
+///
+/// Created by `FsPool::mmap_read`, and dereferences to the mapped bytes.
+///
+/// # Safety
+///
+/// The map reflects changes to the file made by other processes, and if the
+/// file is truncated while mapped, accessing the bytes past its new end
+/// kills the process with `SIGBUS` on Unix. Only map files that aren't
+/// changed while they are mapped.
+pub struct FsMmap {
+    map: Option<Arc<Mmap>>,
+    file: File,
+    pool: FsPool,
+}
+
+impl FsMmap {
+    /// Returns a `Stream` of the mapped bytes, read with `opts`.
+    ///
+    /// Each chunk is copied out of the map, since a `Bytes` can't refer to
+    /// it, though without reading the file again. The stream keeps the map
+    /// alive until it is dropped. Any bytes beyond the end of the map, like
+    /// those appended to a followed file, are read from the file instead.
+    pub fn into_read_stream(self, opts: ReadOptions) -> FsReadStream {
+        ::read::new_from_mmap(&self.pool, self.file, self.map, opts)
+    }
+}
+
+impl Deref for FsMmap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self.map {
+            Some(ref map) => map,
+            None => &[],
+        }
+    }
+}
+
+impl AsRef<[u8]> for FsMmap {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl fmt::Debug for FsMmap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsMmap")
+            .field("file", &self.file)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+pub(crate) fn map_mut(pool: &FsPool, path: &Path, len: u64) -> io::Result<FsMmapMut> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?;
+    file.set_len(len)?;
+    // an empty file can't be mapped
+    let map = if len == 0 {
+        None
+    } else {
+        Some(unsafe { MmapMut::map_mut(&file)? })
+    };
+    Ok(FsMmapMut {
+        map: Arc::new(RwLock::new(map)),
+        len,
+        file,
+        pool: pool.clone(),
+    })
+}
+
+/// A file of a fixed length, mapped into memory to be written.
+///
+/// Created by `FsPool::mmap_write`. Writes are copied into the map, and
+/// reach the file once the OS writes the pages back, or once flushed.
+///
+/// # Safety
+///
+/// Like with `FsMmap`, if the file is truncated by another process while
+/// mapped, writing to the bytes past its new end kills the process with
+/// `SIGBUS` on Unix.
+pub struct FsMmapMut {
+    map: Arc<RwLock<Option<MmapMut>>>,
+    len: u64,
+    file: File,
+    pool: FsPool,
+}
+
+impl FsMmapMut {
+    /// The length of the map, and of the file.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Copies `data` into the map, starting at the byte `offset`.
+    ///
+    /// A write that doesn't fit within the map fails with an error of kind
+    /// `InvalidInput`, without writing anything.
+    pub fn write_at(&self, offset: u64, data: &[u8]) -> io::Result<()> {
+        let end = offset.checked_add(data.len() as u64);
+        if end.map_or(true, |end| end > self.len) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("write is past the end of the map of {} bytes", self.len),
+            ));
+        }
+        if data.is_empty() {
+            return Ok(());
+        }
+        let mut map = self.map.write().unwrap();
+        let map = map.as_mut().unwrap();
+        let start = offset as usize;
+        map[start..start + data.len()].copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Returns a `Future` that resolves once the bytes of the map within
+    /// `range` have been written back to the file, with `msync`.
+    ///
+    /// If `range` is `None`, the whole map is flushed. Writes wait for the
+    /// flush to finish. A range that isn't within the map fails with an
+    /// error of kind `InvalidInput`.
+    pub fn flush(&self, range: Option<Range<u64>>) -> FsFuture<()> {
+        let range = range.unwrap_or(0..self.len);
+        if range.start > range.end || range.end > self.len {
+            return ::failed(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("flush is past the end of the map of {} bytes", self.len),
+            ));
+        }
+        let map = self.map.clone();
+        self.pool.exec_as(Some(FsOperation::Write), move || {
+            match *map.read().unwrap() {
+                Some(ref map) if range.start < range.end => {
+                    let len = (range.end - range.start) as usize;
+                    map.flush_range(range.start as usize, len)
+                }
+                _ => Ok(()),
+            }
+        })
+    }
+}
+
+impl fmt::Debug for FsMmapMut {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsMmapMut")
+            .field("file", &self.file)
+            .field("len", &self.len)
+            .finish()
+    }
+}
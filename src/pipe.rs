@@ -0,0 +1,132 @@
+use std::cmp;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use error::FsError;
+use metrics::Metrics;
+use rate::RateLimit;
+use write::Target;
+use FsOperation;
+use ReadOptions;
+use WriteOptions;
+
+/// Options for how to pipe one file into another, with `FsPool::pipe`.
+///
+/// The default is to read the whole source, and write it to a new file at
+/// the destination.
+#[derive(Debug, Default)]
+pub struct PipeOptions {
+    read: ReadOptions,
+    write: WriteOptions,
+}
+
+impl PipeOptions {
+    /// The options to read the source with.
+    ///
+    /// Its open options, buffer size and range apply, like to a stream. The
+    /// rest of them only apply to streams.
+    ///
+    /// Default is `ReadOptions::default()`.
+    pub fn with_read_options(mut self, read: ReadOptions) -> Self {
+        self.read = read;
+        self
+    }
+
+    /// The options to write the destination with.
+    ///
+    /// Its open options, and whether to create the parent directories,
+    /// preallocate, write atomically, truncate or sync on close apply, like
+    /// to a sink. The rest of them only apply to sinks.
+    ///
+    /// Default is `WriteOptions::default()`.
+    pub fn with_write_options(mut self, write: WriteOptions) -> Self {
+        self.write = write;
+        self
+    }
+}
+
+/// Pipes the range of the file at `from` into the file at `to`.
+pub(crate) fn pipe(
+    from: &Path,
+    to: &Path,
+    opts: &PipeOptions,
+    metrics: &Metrics,
+    rate: Option<&RateLimit>,
+) -> io::Result<u64> {
+    let (mut src, len, buf_size) = ::read::open_range(from, &opts.read)?;
+    let mut dst = Target::open(to, &opts.write)?;
+    let piped = copy(&mut src, dst.file(), len, buf_size, metrics, rate)?;
+    dst.finish()?;
+    Ok(piped)
+}
+
+/// Copies from the current position of `src` to that of `dst`, until `len`
+/// bytes were copied or the end of `src`, through a single buffer.
+pub(crate) fn copy(
+    src: &mut File,
+    dst: &mut File,
+    len: Option<u64>,
+    buf_size: usize,
+    metrics: &Metrics,
+    rate: Option<&RateLimit>,
+) -> io::Result<u64> {
+    let mut buf = vec![0; cmp::max(buf_size, 1)];
+    let mut piped = 0;
+    loop {
+        let left = len.map_or(u64::MAX, |len| len - piped);
+        let want = cmp::min(buf.len() as u64, left) as usize;
+        if want == 0 {
+            return Ok(piped);
+        }
+        let n = match src.read(&mut buf[..want]) {
+            Ok(0) => return Ok(piped),
+            Ok(n) => n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        metrics.read(n as u64);
+        if let Some(rate) = rate {
+            rate.take(n as u64);
+        }
+        dst.write_all(&buf[..n])?;
+        metrics.written(n as u64);
+        piped += n as u64;
+    }
+}
+
+/// Copies the whole file at `path` into `writer`, and flushes it.
+///
+/// Errors of the file are wrapped in an `FsError` of the read, while those
+/// of the writer are described as such.
+pub(crate) fn copy_to_writer<W: Write>(
+    path: &Path,
+    writer: &mut W,
+    metrics: &Metrics,
+    rate: Option<&RateLimit>,
+) -> io::Result<u64> {
+    let read_error = |e| io::Error::from(FsError::new(FsOperation::Read, path, e));
+    let writer_error =
+        |e: io::Error| io::Error::new(e.kind(), format!("writing to the writer failed: {}", e));
+
+    let (mut src, _, buf_size) =
+        ::read::open_range(path, &ReadOptions::default()).map_err(read_error)?;
+    let mut buf = vec![0; cmp::max(buf_size, 1)];
+    let mut copied = 0;
+    loop {
+        let n = match src.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(read_error(e)),
+        };
+        metrics.read(n as u64);
+        if let Some(rate) = rate {
+            rate.take(n as u64);
+        }
+        writer.write_all(&buf[..n]).map_err(writer_error)?;
+        copied += n as u64;
+    }
+    writer.flush().map_err(writer_error)?;
+    Ok(copied)
+}
@@ -0,0 +1,147 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use futures::{Async, Future, Poll};
+
+/// How many times a queue with tasks is passed over for a more urgent one,
+/// before it goes next anyway.
+const MAX_SKIPPED: usize = 8;
+
+type Task = Box<dyn Future<Item = (), Error = ()> + Send>;
+
+/// How urgently an operation should run, compared to the others waiting for
+/// a thread of the pool.
+///
+/// A waiting operation of a higher priority starts before those of a lower
+/// one, though a lower priority is never starved: it goes next anyway once
+/// it has been passed over a few times. An operation that has started isn't
+/// interrupted for a more urgent one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OpPriority {
+    /// Work that something is waiting on, like serving a request.
+    High,
+    /// The default priority.
+    #[default]
+    Normal,
+    /// Background work, like a compaction.
+    Low,
+}
+
+impl OpPriority {
+    fn index(self) -> usize {
+        match self {
+            OpPriority::High => 0,
+            OpPriority::Normal => 1,
+            OpPriority::Low => 2,
+        }
+    }
+}
+
+/// The tasks of a pool that wait for a thread, one queue per priority.
+#[derive(Default)]
+pub(crate) struct Queues {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    tasks: [VecDeque<(u64, Task)>; 3],
+    /// How many times each queue was passed over, while it had tasks.
+    skipped: [usize; 3],
+    next_id: u64,
+}
+
+impl Queues {
+    /// Queues `task`, returning its id to `remove` it again.
+    pub(crate) fn push(&self, priority: OpPriority, task: Task) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.tasks[priority.index()].push_back((id, task));
+        id
+    }
+
+    /// Removes the task with `id`, if it hasn't been taken by a runner yet.
+    pub(crate) fn remove(&self, priority: OpPriority, id: u64) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let queue = &mut state.tasks[priority.index()];
+        match queue.iter().position(|&(queued, _)| queued == id) {
+            Some(i) => queue.remove(i).is_some(),
+            None => false,
+        }
+    }
+
+    /// Takes the task to run next.
+    fn pop(&self) -> Option<Task> {
+        let mut state = self.state.lock().unwrap();
+        let next = (0..3)
+            .rev()
+            .find(|&i| state.skipped[i] >= MAX_SKIPPED && !state.tasks[i].is_empty())
+            .or_else(|| (0..3).find(|&i| !state.tasks[i].is_empty()))?;
+
+        state.skipped[next] = 0;
+        for i in next + 1..3 {
+            if !state.tasks[i].is_empty() {
+                state.skipped[i] += 1;
+            }
+        }
+        state.tasks[next].pop_front().map(|(_, task)| task)
+    }
+}
+
+/// Returns the future to spawn on the executor, for the task with `id` that
+/// was pushed to `queues`.
+///
+/// A runner doesn't run the task it was spawned for, but whichever task
+/// should go next once the executor gets to it. It then keeps running
+/// tasks until the queues are empty, so a task is never left behind.
+pub(crate) fn runner(queues: &Arc<Queues>, priority: OpPriority, id: u64) -> Task {
+    Box::new(Runner {
+        queues: queues.clone(),
+        priority,
+        id,
+        task: None,
+        polled: false,
+    })
+}
+
+struct Runner {
+    queues: Arc<Queues>,
+    priority: OpPriority,
+    id: u64,
+    task: Option<Task>,
+    polled: bool,
+}
+
+impl Future for Runner {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        self.polled = true;
+        loop {
+            if self.task.is_none() {
+                self.task = self.queues.pop();
+            }
+            match self.task {
+                Some(ref mut task) => match task.poll() {
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    Ok(Async::Ready(())) | Err(()) => (),
+                },
+                None => return Ok(Async::Ready(())),
+            }
+            self.task = None;
+        }
+    }
+}
+
+impl Drop for Runner {
+    fn drop(&mut self) {
+        // the executor dropped this without running it, such as when
+        // shutting down, so its task is dropped too, unless another runner
+        // has taken it
+        if !self.polled {
+            self.queues.remove(self.priority, self.id);
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A token bucket that limits the bytes read and written per second.
+///
+/// The bucket starts empty, and holds up to a second's worth of bytes, so
+/// a burst after being idle is at most that large. Waiting for the bucket
+/// sleeps on the pool thread, which would block on the IO anyway.
+pub(crate) struct RateLimit {
+    bytes_per_sec: u64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    /// The bytes that may be used right away, negative while sleeping off a
+    /// debt.
+    available: f64,
+    refilled: Instant,
+}
+
+impl RateLimit {
+    pub(crate) fn new(bytes_per_sec: u64) -> RateLimit {
+        assert!(bytes_per_sec > 0, "rate limit must be larger than 0");
+        RateLimit {
+            bytes_per_sec,
+            bucket: Mutex::new(Bucket {
+                available: 0.0,
+                refilled: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes `n` bytes from the bucket, sleeping until they are paid for.
+    pub(crate) fn take(&self, n: u64) {
+        let rate = self.bytes_per_sec as f64;
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            let now = Instant::now();
+            let elapsed = now.duration_since(bucket.refilled).as_secs_f64();
+            bucket.available = f64::min(rate, bucket.available + elapsed * rate);
+            bucket.refilled = now;
+            bucket.available -= n as f64;
+            if bucket.available >= 0.0 {
+                return;
+            }
+            // later callers wait behind this debt, so the rate holds across
+            // all the threads sharing the bucket
+            -bucket.available / rate
+        };
+        thread::sleep(Duration::from_secs_f64(wait));
+    }
+}
@@ -1,28 +1,93 @@
-use std::fs::{File, Metadata};
-use std::io::{self, Read};
+use std::collections::VecDeque;
+use std::fs::{File, Metadata, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::{cmp, fmt, mem};
+use std::time::Duration;
+use std::{cmp, fmt};
 
 use bytes::{BufMut, Bytes, BytesMut};
-use futures::future::lazy;
-use futures::sync::oneshot;
-use futures::{Async, Future, Poll, Stream};
+use futures::sync::{mpsc, oneshot};
+use futures::{Async, Future, Poll, Sink, Stream};
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
 
+use error::Context;
+use metrics::{self, Running};
+use rate::RateLimit;
+use sys;
+use timeout::{Expiry, Timeout, Timer};
+use FsCancelHandle;
+use FsChunksExact;
 use FsFuture;
+use FsLines;
+use FsOperation;
 use FsPool;
+use FsSplit;
+use OpPriority;
 
 const BUF_SIZE: usize = 8192;
+const FOLLOW_INTERVAL_MS: u64 = 1000;
 
 /// Options for how to read the file.
 ///
 /// The default is to automatically determine the buffer size.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct ReadOptions {
     /// The buffer size to use.
     ///
     /// If set to `None`, this is automatically determined from the operating system.
     buffer_size: Option<usize>,
+    /// The position to start reading from.
+    start: Option<SeekFrom>,
+    /// The maximum number of bytes to read.
+    len: Option<u64>,
+    /// The options to open the file with.
+    ///
+    /// If set to `None`, the file is opened read-only.
+    open: Option<OpenOptions>,
+    /// Whether to keep waiting for new bytes at the end of the file.
+    follow: bool,
+    /// How long to wait before checking for new bytes again.
+    follow_interval: Option<Duration>,
+    /// Whether a truncated file is an error, instead of reading it again.
+    error_on_truncate: bool,
+    /// How many chunks to read before they are polled.
+    read_ahead: usize,
+    /// Whether to read into the buffers of chunks that have been dropped.
+    buffer_reuse: Option<bool>,
+    /// Whether to bypass the page cache.
+    direct: bool,
+    /// How the file is going to be accessed.
+    advice: Option<Advice>,
+    /// Whether errors are wrapped in an `FsError`.
+    contextual_errors: bool,
+    /// How long to wait for each chunk, and the timer to wait with.
+    chunk_timeout: Option<(Duration, Timer)>,
+    /// The priority to read with, instead of that of the pool.
+    priority: Option<OpPriority>,
+    /// The rate limit to read with, instead of that of the pool.
+    max_bytes_per_sec: Option<u64>,
+    /// Whether to fail instead of opening a symbolic link.
+    no_follow: bool,
+    /// Whether to fail unless the file is a regular file.
+    regular_only: bool,
+}
+
+/// How a file is going to be accessed, as a hint to the OS.
+///
+/// Passed to `ReadOptions::advise`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Advice {
+    /// The file is read sequentially, so the OS may read ahead more.
+    Sequential,
+    /// The file is read in a random order, so the OS shouldn't read ahead.
+    Random,
+    /// The file is read soon, so the OS may start reading it right away.
+    WillNeed,
+    /// The bytes aren't read again, so the OS may drop them from its page
+    /// cache once the stream has read them.
+    DontNeed,
 }
 
 impl ReadOptions {
@@ -38,128 +103,919 @@ impl ReadOptions {
         self.buffer_size = Some(buffer_size);
         self
     }
+
+    /// The `OpenOptions` to use when opening the file at the path.
+    ///
+    /// This has no effect on streams created from an already opened file.
+    ///
+    /// Default is to open the file read-only.
+    pub fn with_open_options(mut self, open: OpenOptions) -> Self {
+        self.open = Some(open);
+        self
+    }
+
+    /// Whether to fail with an error of kind `InvalidInput`, instead of
+    /// following a symbolic link as the last component of the path.
+    ///
+    /// This opens with `O_NOFOLLOW` on Unix, and opens the reparse point
+    /// itself on Windows. Symbolic links in the directories leading up to
+    /// the file are still followed. This has no effect on streams created
+    /// from an already opened file.
+    ///
+    /// Default is `false`.
+    pub fn no_follow(mut self, no_follow: bool) -> Self {
+        self.no_follow = no_follow;
+        self
+    }
+
+    /// Whether to fail with an error of kind `InvalidInput`, unless the
+    /// opened file is a regular file, rejecting directories, FIFOs and
+    /// devices.
+    ///
+    /// The check is made on the file once opened, so it can't be raced by
+    /// replacing the path. A FIFO is opened without waiting for its other
+    /// end. This has no effect on streams created from an already opened
+    /// file.
+    ///
+    /// Default is `false`.
+    pub fn regular_only(mut self, regular_only: bool) -> Self {
+        self.regular_only = regular_only;
+        self
+    }
+
+    /// Only read `len` bytes of the file, starting at the byte `offset`.
+    ///
+    /// The stream ends once `len` bytes have been read, or once the end of the
+    /// file has been reached. An `offset` past the end of the file results in
+    /// an empty stream.
+    ///
+    /// Default is to read the whole file.
+    pub fn range(mut self, offset: u64, len: u64) -> Self {
+        self.start = Some(SeekFrom::Start(offset));
+        self.len = Some(len);
+        self
+    }
+
+    /// Only read the last `len` bytes of the file.
+    ///
+    /// If the file is shorter than `len`, the whole file is read. This
+    /// replaces any previously set `range`.
+    pub fn from_end(mut self, len: u64) -> Self {
+        let len = cmp::min(len, i64::MAX as u64) as i64;
+        self.start = Some(SeekFrom::End(-len));
+        self.len = None;
+        self
+    }
+
+    /// Whether to keep following the file once its end has been reached,
+    /// like `tail -f`.
+    ///
+    /// Instead of ending, the stream waits for bytes to be appended to the
+    /// file, checking its size on the pool every `follow_interval`. It waits
+    /// with the timer of the pool, from `FsPool::with_timer`.
+    ///
+    /// Default is `false`.
+    pub fn follow(mut self, follow: bool) -> Self {
+        self.follow = follow;
+        self
+    }
+
+    /// How long to wait between checks for new bytes, while following a
+    /// file.
+    ///
+    /// Default is 1 second.
+    pub fn follow_interval(mut self, interval: Duration) -> Self {
+        self.follow_interval = Some(interval);
+        self
+    }
+
+    /// Whether a followed file shrinking below the current position is an
+    /// error of kind `UnexpectedEof`.
+    ///
+    /// Otherwise, the file is read again from its start.
+    ///
+    /// Default is `false`.
+    pub fn error_on_truncate(mut self, error_on_truncate: bool) -> Self {
+        self.error_on_truncate = error_on_truncate;
+        self
+    }
+
+    /// How many more chunks to keep reading on the pool, while earlier chunks
+    /// are still being processed.
+    ///
+    /// Chunks that were read ahead are buffered until they are polled, so
+    /// this uses up to `read_ahead + 1` times the buffer size of memory. An
+    /// error while reading ahead is returned once the chunks before it have
+    /// been yielded.
+    ///
+    /// Default is 0, reading a single chunk ahead.
+    pub fn read_ahead(mut self, read_ahead: usize) -> Self {
+        self.read_ahead = read_ahead;
+        self
+    }
+
+    /// Whether to read into the buffer of an earlier chunk again, once every
+    /// reference to it has been dropped.
+    ///
+    /// A chunk that is still referenced is never written to. Disabling this
+    /// avoids keeping track of chunks that are held on to for a long time.
+    ///
+    /// Default is `true`.
+    pub fn buffer_reuse(mut self, buffer_reuse: bool) -> Self {
+        self.buffer_reuse = Some(buffer_reuse);
+        self
+    }
+
+    /// Whether to bypass the page cache of the OS, by reading with
+    /// `O_DIRECT`.
+    ///
+    /// Chunks are then read into buffers aligned to 4096 bytes, and the
+    /// buffer size is rounded up to a multiple of that. A read from an
+    /// unaligned position or of an unaligned length, like the end of a
+    /// `range`, goes through the page cache instead. If the filesystem
+    /// doesn't support direct IO, the stream fails with an error of kind
+    /// `InvalidInput`.
+    ///
+    /// This only has an effect on Linux, and is ignored elsewhere.
+    ///
+    /// Default is `false`.
+    pub fn direct(mut self, direct: bool) -> Self {
+        self.direct = direct;
+        self
+    }
+
+    /// Advises the OS how the file is going to be accessed, with
+    /// `posix_fadvise`.
+    ///
+    /// The advice is given for the bytes to be read, once the file has been
+    /// opened. With `Advice::DontNeed`, the bytes are also dropped from the
+    /// page cache as the stream reads them, so streaming a large file doesn't
+    /// push everything else out of it. The advice is only a hint, so any
+    /// error from it is ignored.
+    ///
+    /// This has no effect on platforms without `posix_fadvise`.
+    ///
+    /// Default is to give no advice.
+    pub fn advise(mut self, advice: Advice) -> Self {
+        self.advice = Some(advice);
+        self
+    }
+
+    /// Whether the stream fails with errors that wrap an `FsError`, which
+    /// records the path of the file along with the original error.
+    ///
+    /// The errors keep the kind of the original error. This has no effect
+    /// on a stream of an already opened file, which has no path.
+    ///
+    /// Default is `false`.
+    pub fn contextual_errors(mut self, contextual_errors: bool) -> Self {
+        self.contextual_errors = contextual_errors;
+        self
+    }
+
+    /// How long the stream waits for the next chunk, before failing with an
+    /// error of kind `TimedOut`.
+    ///
+    /// The time is counted from when the previous chunk was received, or the
+    /// stream was created or seeked. Once timed out, reading is canceled,
+    /// though a read that is stuck on the pool can't be interrupted. When
+    /// following a file, waiting for it to grow counts as well.
+    ///
+    /// `timer` is called to get a future that resolves after the supplied
+    /// duration, like a delay of the runtime of the caller. Every chunk
+    /// pushes the timeout back, and a new future is only asked for once the
+    /// current one resolves. A timer that fails counts as expired.
+    ///
+    /// Default is to wait forever.
+    pub fn chunk_timeout<F, T>(mut self, chunk_timeout: Duration, timer: F) -> Self
+    where
+        F: Fn(Duration) -> T + Send + Sync + 'static,
+        T: Future<Item = ()> + Send + 'static,
+    {
+        self.chunk_timeout = Some((chunk_timeout, Timer::new(timer)));
+        self
+    }
+
+    /// The priority of reading the file, which is queued by it until it
+    /// starts.
+    ///
+    /// Default is the priority of the pool, from `FsPool::with_priority`.
+    pub fn priority(mut self, priority: OpPriority) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// The most bytes per second to read the file with.
+    ///
+    /// The stream gets a limit of its own, instead of sharing that of the
+    /// pool, from `FsPoolBuilder::max_bytes_per_sec`.
+    ///
+    /// Default is the limit of the pool.
+    ///
+    /// # Panic
+    ///
+    /// The passed argument must be larger than 0.
+    pub fn max_bytes_per_sec(mut self, max_bytes_per_sec: u64) -> Self {
+        assert!(max_bytes_per_sec > 0, "rate limit must be larger than 0");
+        self.max_bytes_per_sec = Some(max_bytes_per_sec);
+        self
+    }
+}
+
+/// How to follow a file once its end has been reached.
+#[derive(Clone)]
+struct Follow {
+    interval: Duration,
+    error_on_truncate: bool,
+    /// The timer of the pool, to wait with in between checks.
+    timer: Timer,
 }
 
-pub(crate) fn new<P>(pool: &FsPool, path: P, opts: ReadOptions) -> FsReadStream
+fn follow(pool: &FsPool, opts: &ReadOptions) -> Option<Follow> {
+    if opts.follow {
+        Some(Follow {
+            interval: opts
+                .follow_interval
+                .unwrap_or_else(|| Duration::from_millis(FOLLOW_INTERVAL_MS)),
+            error_on_truncate: opts.error_on_truncate,
+            timer: pool.timer(),
+        })
+    } else {
+        None
+    }
+}
+
+pub(crate) fn new<P>(pool: &FsPool, path: P, mut opts: ReadOptions) -> FsReadStream
 where
     P: AsRef<Path> + Send + 'static,
 {
-    FsReadStream {
-        buffer: BytesMut::with_capacity(0),
-        //TODO: can we adjust bounds, since this is making an owned copy anyways?
-        path: Arc::new(path.as_ref().to_owned()),
-        pool: pool.clone(),
-        state: State::Init(opts.buffer_size),
-    }
+    //TODO: can we adjust bounds, since this is making an owned copy anyways?
+    let path = Arc::new(path.as_ref().to_owned());
+    let source = Source::Path(path.clone(), opts.buffer_size, opts.open.take());
+    let context = if opts.contextual_errors {
+        Some(Context::new(FsOperation::Read, path.clone()))
+    } else {
+        None
+    };
+    spawn(pool, path, context, source, opts)
 }
 
 pub(crate) fn new_from_file(pool: &FsPool, file: File, opts: ReadOptions) -> FsReadStream {
-    let final_buf_size = finalize_buf_size(opts.buffer_size, &file);
+    let source = Source::File(file, opts.buffer_size);
+    spawn(pool, Arc::new(PathBuf::new()), None, source, opts)
+}
+
+#[cfg(feature = "mmap")]
+pub(crate) fn new_from_mmap(
+    pool: &FsPool,
+    file: File,
+    map: Option<Arc<Mmap>>,
+    mut opts: ReadOptions,
+) -> FsReadStream {
+    let source = Source::Mmap(file, map, opts.buffer_size);
+    // the map is read through the page cache either way
+    opts.direct = false;
+    spawn(pool, Arc::new(PathBuf::new()), None, source, opts)
+}
+
+fn spawn(
+    pool: &FsPool,
+    path: Arc<PathBuf>,
+    context: Option<Context>,
+    source: Source,
+    opts: ReadOptions,
+) -> FsReadStream {
+    let (cmd_tx, cmd_rx) = mpsc::unbounded();
+    let (chunk_tx, chunk_rx) = mpsc::channel(opts.read_ahead);
+    let cancel = FsCancelHandle::new();
+    let mut running = metrics::start(&pool.metrics, Some(FsOperation::Read));
+    running.set_failed(false);
+
+    let worker = Worker {
+        source: Some(source),
+        file: None,
+        start: opts.start,
+        remaining: opts.len,
+        follow: follow(pool, &opts),
+        recycle: VecDeque::new(),
+        direct: opts.direct && sys::HAS_DIRECT,
+        // reused buffers aren't aligned for direct IO
+        max_recycle: if opts.buffer_reuse.unwrap_or(true) && !opts.direct {
+            opts.read_ahead + 2
+        } else {
+            0
+        },
+        advice: opts.advice,
+        #[cfg(feature = "mmap")]
+        map: None,
+        no_follow: opts.no_follow,
+        regular_only: opts.regular_only,
+        advised: 0,
+        origin: 0,
+        gen: 0,
+        done: false,
+        error: None,
+        delay: None,
+        commands: cmd_rx,
+        chunks: chunk_tx.clone(),
+        cancel: cancel.clone(),
+        running,
+        rate: pool.rate_limit(opts.max_bytes_per_sec),
+    };
+
+    // the worker can't report that it never ran itself
+    let mut failed = chunk_tx.clone();
+    if let Err(e) = pool.prioritized(opts.priority).execute(Box::new(worker)) {
+        let _ = failed.try_send((0, Err(e)));
+    }
+
     FsReadStream {
-        buffer: BytesMut::with_capacity(0),
-        //TODO: can we adjust bounds, since this is making an owned copy anyways?
-        path: Arc::new(PathBuf::new()),
-        pool: pool.clone(),
-        state: State::Ready(file, final_buf_size),
+        path,
+        context,
+        cancel,
+        timeout: opts
+            .chunk_timeout
+            .as_ref()
+            .map(|&(duration, ref timer)| Timeout::new(duration, timer)),
+        commands: cmd_tx,
+        chunks: chunk_rx,
+        gen: 0,
+        next: None,
+        remaining: opts.len,
+        done: false,
     }
 }
 
 /// A `Stream` of bytes from a target file.
+///
+/// The file is read by a single task on the pool, which keeps reading ahead
+/// of the stream until the chunks it has sent are polled. Dropping the stream
+/// stops the task before its next read.
 pub struct FsReadStream {
-    buffer: BytesMut,
     path: Arc<PathBuf>,
-    pool: FsPool,
-    state: State,
+    context: Option<Context>,
+    cancel: FsCancelHandle,
+    timeout: Option<Timeout>,
+    commands: mpsc::UnboundedSender<Command>,
+    chunks: mpsc::Receiver<Message>,
+    gen: u64,
+    next: Option<u64>,
+    remaining: Option<u64>,
+    done: bool,
 }
 
-enum State {
-    Init(Option<usize>),
-    Opening(FsFuture<(File, BytesMut)>),
-    Working(FsFuture<(File, BytesMut)>, usize),
-    Ready(File, usize),
-    Eof,
-    Swapping,
+/// A chunk read by a `Worker`, tagged with the generation of the stream it
+/// was read for, which changes with every seek.
+type Message = (u64, io::Result<Option<(u64, Bytes)>>);
+
+enum Command {
+    Seek {
+        pos: SeekFrom,
+        next: Option<u64>,
+        remaining: Option<u64>,
+        gen: u64,
+        tx: oneshot::Sender<io::Result<u64>>,
+    },
+    IntoFile {
+        next: Option<u64>,
+        tx: oneshot::Sender<io::Result<File>>,
+    },
 }
 
-impl FsReadStream {
-    fn handle_read(
-        &mut self,
-        file: File,
-        chunk: BytesMut,
-        buf_size: usize,
-    ) -> Poll<Option<<Self as Stream>::Item>, <Self as Stream>::Error> {
-        if chunk.is_empty() {
-            self.state = State::Eof;
-
-            Ok(Async::Ready(None))
-        } else {
-            self.buffer = chunk;
-            self.state = State::Ready(file, buf_size);
+enum Source {
+    Path(Arc<PathBuf>, Option<usize>, Option<OpenOptions>),
+    File(File, Option<usize>),
+    /// A file along with its map, which is read instead as far as it goes.
+    #[cfg(feature = "mmap")]
+    Mmap(File, Option<Arc<Mmap>>, Option<usize>),
+}
+
+/// The task on the pool that reads the file of an `FsReadStream`.
+///
+/// Reading blocks the thread it is polled on, and it only reads once the
+/// stream has room for another chunk.
+struct Worker {
+    source: Option<Source>,
+    file: Option<(File, usize)>,
+    start: Option<SeekFrom>,
+    remaining: Option<u64>,
+    follow: Option<Follow>,
+    /// The chunks that were sent, to read into their buffers again once the
+    /// stream has dropped them.
+    recycle: VecDeque<Bytes>,
+    max_recycle: usize,
+    direct: bool,
+    advice: Option<Advice>,
+    #[cfg(feature = "mmap")]
+    map: Option<Arc<Mmap>>,
+    no_follow: bool,
+    regular_only: bool,
+    /// The position up to which the bytes were dropped from the page cache,
+    /// with `Advice::DontNeed`.
+    advised: u64,
+    /// The position reading started from, since opening or seeking.
+    origin: u64,
+    gen: u64,
+    /// Whether nothing is to be read until the next seek.
+    done: bool,
+    error: Option<io::Error>,
+    /// When to check whether the followed file has grown, while waiting.
+    delay: Option<Expiry>,
+    commands: mpsc::UnboundedReceiver<Command>,
+    chunks: mpsc::Sender<Message>,
+    cancel: FsCancelHandle,
+    running: Running,
+    rate: Option<Arc<RateLimit>>,
+}
+
+impl Future for Worker {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        if let Err(e) = super::refused() {
+            self.running.set_failed(true);
+            let _ = self.chunks.try_send((self.gen, Err(e)));
+            return Ok(Async::Ready(()));
+        }
+        self.cancel.register();
+        loop {
+            let cmd = match self.commands.poll()? {
+                Async::Ready(Some(cmd)) => Some(cmd),
+                // the stream was dropped
+                Async::Ready(None) => return Ok(Async::Ready(())),
+                Async::NotReady => None,
+            };
+            if let Some(cmd) = cmd {
+                if self.command(cmd) {
+                    return Ok(Async::Ready(()));
+                }
+                continue;
+            }
+            // commands sent right before canceling, like `into_file`, are
+            // still handled
+            if self.cancel.is_canceled() {
+                return Ok(Async::Ready(()));
+            }
 
-            Ok(Async::Ready(Some(self.buffer.take().freeze())))
+            if self.done && self.error.is_none() {
+                return Ok(Async::NotReady);
+            }
+            match self.chunks.poll_ready() {
+                Ok(Async::Ready(())) => (),
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(_) => return self.finish(),
+            }
+
+            let res = match self.error.take() {
+                Some(e) => Err(e),
+                None => match self.read() {
+                    Some(res) => res,
+                    // the followed file hasn't grown yet, and the timer
+                    // wakes this up to check again
+                    None => return Ok(Async::NotReady),
+                },
+            };
+            match res {
+                Ok(Some((_, ref chunk))) => {
+                    self.running.metrics().read(chunk.len() as u64);
+                    // the chunk is held back until the budget allows it
+                    if let Some(ref rate) = self.rate {
+                        rate.take(chunk.len() as u64);
+                    }
+                }
+                Ok(None) => self.done = true,
+                Err(_) => {
+                    self.done = true;
+                    self.running.set_failed(true);
+                }
+            }
+            if self.chunks.start_send((self.gen, res)).is_err() {
+                return self.finish();
+            }
         }
     }
 }
 
-impl Stream for FsReadStream {
-    type Item = Bytes;
-    type Error = io::Error;
+impl Worker {
+    /// Finishes this worker, once the stream has been dropped.
+    fn finish(&mut self) -> Poll<(), ()> {
+        // a command may have been sent right before dropping the stream
+        while let Ok(Async::Ready(Some(cmd))) = self.commands.poll() {
+            if self.command(cmd) {
+                break;
+            }
+        }
+        Ok(Async::Ready(()))
+    }
 
-    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
-        loop {
-            match mem::replace(&mut self.state, State::Swapping) {
-                State::Init(buf_size) => {
-                    let path = self.path.clone();
+    /// Handles a command from the stream, returning whether this worker is
+    /// finished.
+    fn command(&mut self, cmd: Command) -> bool {
+        match cmd {
+            Command::Seek {
+                pos,
+                next,
+                remaining,
+                gen,
+                tx,
+            } => {
+                self.gen = gen;
+                self.remaining = remaining;
+                self.done = false;
+                self.error = None;
+                self.delay = None;
 
-                    let (tx, rx) = oneshot::channel();
+                let res = self.seek(pos, next);
+                let res = match res {
+                    Ok(pos) => Ok(pos),
+                    Err(e) => {
+                        let dup = io::Error::new(e.kind(), e.to_string());
+                        self.error = Some(e);
+                        Err(dup)
+                    }
+                };
+                let _ = tx.send(res);
+                false
+            }
+            Command::IntoFile { next, tx } => {
+                let res = self.open().and_then(|()| {
+                    let (mut file, _) = self.file.take().unwrap();
+                    file.seek(SeekFrom::Start(next.unwrap_or(self.origin)))?;
+                    Ok(file)
+                });
+                let _ = tx.send(res);
+                true
+            }
+        }
+    }
 
-                    let fut = Box::new(lazy(move || {
-                        let res = open_and_read(&path, buf_size).map_err(From::from);
+    /// Opens the file if it hasn't been yet, and seeks to the start.
+    fn open(&mut self) -> io::Result<()> {
+        if self.file.is_some() {
+            return Ok(());
+        }
+        let (mut file, buf_size) = match self.source.take() {
+            Some(Source::Path(path, buf_size, open)) => {
+                let file = open_file(&path, open, self.no_follow, self.regular_only)?;
+                (file, buf_size)
+            }
+            Some(Source::File(file, buf_size)) => (file, buf_size),
+            #[cfg(feature = "mmap")]
+            Some(Source::Mmap(file, map, buf_size)) => {
+                self.map = map;
+                (file, buf_size)
+            }
+            None => return Err(io::Error::other("read stream failed to open its file")),
+        };
+        let mut buf_size = finalize_buf_size(buf_size, &file, self.follow.is_none());
+        if self.direct {
+            sys::set_direct(&file, true)?;
+            buf_size = sys::align_up(cmp::max(buf_size, 1));
+        }
+        seek(&mut file, self.start.take())?;
+        self.origin = file.stream_position()?;
+        self.advised = self.origin;
+        if let Some(advice) = self.advice {
+            let _ = sys::advise(&file, self.origin, self.remaining.unwrap_or(0), advice);
+        }
+        self.file = Some((file, buf_size));
+        Ok(())
+    }
 
-                        tx.send(res).map_err(|_| ())
-                    }));
+    fn seek(&mut self, pos: SeekFrom, next: Option<u64>) -> io::Result<u64> {
+        self.open()?;
+        let origin = next.unwrap_or(self.origin);
+        let file = &mut self.file.as_mut().unwrap().0;
+        let pos = match pos {
+            // relative to the bytes the stream has yielded, not those read ahead
+            SeekFrom::Current(n) => {
+                if n < 0 && n.unsigned_abs() > origin {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "invalid seek to a negative position",
+                    ));
+                }
+                file.seek(SeekFrom::Start((origin as i64 + n) as u64))?
+            }
+            pos => file.seek(pos)?,
+        };
+        self.origin = pos;
+        self.advised = pos;
+        Ok(pos)
+    }
 
-                    self.pool.executor.execute(fut).unwrap();
+    /// Reads the next chunk, or `None` at the end of the file.
+    ///
+    /// When following the file, this instead checks whether it has grown,
+    /// and returns `None` while waiting for it to.
+    fn read(&mut self) -> Option<io::Result<Option<(u64, Bytes)>>> {
+        if let Err(e) = self.open() {
+            return Some(Err(e));
+        }
+        if self.remaining == Some(0) {
+            return Some(Ok(None));
+        }
+        loop {
+            let res = {
+                let buf = self.buffer();
+                let direct = self.direct;
+                let (ref mut file, buf_size) = *self.file.as_mut().unwrap();
+                #[cfg(feature = "mmap")]
+                {
+                    match self.map {
+                        Some(ref map) => read_mapped(file, map, buf, buf_size, self.remaining),
+                        None => read(file, buf, buf_size, self.remaining, direct),
+                    }
+                }
+                #[cfg(not(feature = "mmap"))]
+                read(file, buf, buf_size, self.remaining, direct)
+            };
+            let (pos, chunk) = match res {
+                Ok(chunk) => chunk,
+                Err(e) => return Some(Err(e)),
+            };
+            if !chunk.is_empty() {
+                if let Some(ref mut remaining) = self.remaining {
+                    *remaining -= chunk.len() as u64;
+                }
+                self.drop_cached(pos + chunk.len() as u64, false);
+                if self.max_recycle > 0 {
+                    if self.recycle.len() == self.max_recycle {
+                        self.recycle.pop_front();
+                    }
+                    self.recycle.push_back(chunk.clone());
+                }
+                return Some(Ok(Some((pos, chunk))));
+            }
 
-                    self.state = State::Opening(super::fs(rx));
+            let follow = match self.follow {
+                Some(ref follow) => follow.clone(),
+                None => {
+                    self.drop_cached(pos, true);
+                    return Some(Ok(None));
                 }
-                State::Opening(mut rx) => {
-                    let polled = rx.poll();
-                    self.state = State::Opening(rx);
-                    let (file, chunk) = try_ready!(polled);
-                    let buf_size = chunk.capacity();
+            };
+            match self.poll_grown(follow) {
+                Ok(Async::Ready(())) => (),
+                Ok(Async::NotReady) => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
 
-                    return self.handle_read(file, chunk, buf_size);
+    /// Drops the bytes read up to `end` from the page cache, with
+    /// `Advice::DontNeed`.
+    ///
+    /// Only whole pages can be dropped, so the page that `end` is in is kept
+    /// until reading past it, or until the end of the file.
+    fn drop_cached(&mut self, end: u64, eof: bool) {
+        if self.advice != Some(Advice::DontNeed) {
+            return;
+        }
+        // a followed file may have been truncated, and read from its start
+        if end < self.advised {
+            self.advised = 0;
+        }
+        let end = if eof { end } else { end - end % sys::PAGE_SIZE };
+        if end > self.advised {
+            let file = &self.file.as_ref().unwrap().0;
+            let _ = sys::advise(file, self.advised, end - self.advised, Advice::DontNeed);
+            self.advised = end;
+        }
+    }
+
+    /// Returns the buffer of a chunk that was sent and isn't referenced
+    /// anymore, or a new buffer.
+    fn buffer(&mut self) -> BytesMut {
+        if self.direct {
+            let buf_size = self.file.as_ref().map_or(0, |&(_, buf_size)| buf_size);
+            return sys::aligned(buf_size);
+        }
+        for _ in 0..self.recycle.len() {
+            match self.recycle.pop_front().unwrap().try_mut() {
+                Ok(mut buf) => {
+                    buf.clear();
+                    return buf;
                 }
-                State::Working(mut rx, buf_size) => {
-                    let polled = rx.poll();
-                    self.state = State::Working(rx, buf_size);
-                    let (file, chunk) = try_ready!(polled);
+                Err(chunk) => self.recycle.push_back(chunk),
+            }
+        }
+        BytesMut::new()
+    }
+
+    /// Polls whether the followed file has grown, checking its size every
+    /// interval.
+    ///
+    /// The timer of the pool wakes this worker for each check, so that the
+    /// worker doesn't keep a thread busy while waiting.
+    fn poll_grown(&mut self, follow: Follow) -> Poll<(), io::Error> {
+        loop {
+            let delay = self
+                .delay
+                .get_or_insert_with(|| follow.timer.delay(follow.interval));
+            if delay.poll() == Ok(Async::NotReady) {
+                return Ok(Async::NotReady);
+            }
+            self.delay = None;
 
-                    return self.handle_read(file, chunk, buf_size);
+            let file = &mut self.file.as_mut().unwrap().0;
+            let pos = file.stream_position()?;
+            let len = file.metadata()?.len();
+            if len < pos {
+                if follow.error_on_truncate {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "followed file was truncated",
+                    ));
                 }
-                State::Ready(file, buf_size) => {
-                    let buf = self.buffer.split_off(0);
+                file.seek(SeekFrom::Start(0))?;
+                if len > 0 {
+                    return Ok(Async::Ready(()));
+                }
+            } else if len > pos {
+                return Ok(Async::Ready(()));
+            }
+        }
+    }
+}
+
+impl FsReadStream {
+    /// Returns a `Future` that resolves with the underlying `File`.
+    ///
+    /// Any chunks that were read ahead are discarded, and the position of the
+    /// file is set to right after the bytes that this stream has yielded so
+    /// far.
+    pub fn into_file(self) -> FsFuture<File> {
+        let (tx, rx) = oneshot::channel();
+        let cmd = Command::IntoFile {
+            next: self.next,
+            tx,
+        };
+        let _ = self.commands.unbounded_send(cmd);
+
+        super::fs(rx).context(self.context.clone())
+    }
+
+    /// Seeks to a new position in the file, from which the stream continues.
+    ///
+    /// Returns a `Future` that resolves with the new position from the start
+    /// of the file. Any chunks that were read ahead are discarded, so that
+    /// `SeekFrom::Current` is relative to the bytes this stream has yielded so
+    /// far. Seeking past the end of the file results in the stream ending.
+    ///
+    /// A limit set by `ReadOptions::range` still applies to the number of
+    /// bytes yielded after seeking.
+    pub fn seek(&mut self, pos: SeekFrom) -> FsFuture<u64> {
+        let (tx, rx) = oneshot::channel();
+        self.gen += 1;
+        let cmd = Command::Seek {
+            pos,
+            next: self.next.take(),
+            remaining: self.remaining,
+            gen: self.gen,
+            tx,
+        };
+        self.done = false;
+        if let Some(ref mut timeout) = self.timeout {
+            timeout.reset();
+        }
+        let _ = self.commands.unbounded_send(cmd);
+
+        super::fs(rx).context(self.context.clone())
+    }
+
+    /// Returns a handle to cancel reading the file.
+    ///
+    /// Once canceled, the stream fails with an error on its next poll.
+    pub fn cancel_handle(&self) -> FsCancelHandle {
+        self.cancel.clone()
+    }
+
+    /// Returns a `Stream` of the lines of the file.
+    ///
+    /// Lines are split on `\n`, with any trailing `\r\n` or `\n` removed, and a
+    /// final line without a newline is yielded as well. A line that isn't
+    /// valid UTF-8 is an error of kind `InvalidData`.
+    pub fn lines(self) -> FsLines {
+        ::frame::lines(self)
+    }
+
+    /// Returns a `Stream` of the frames of the file between each `delimiter`.
+    ///
+    /// The delimiter itself isn't included in the frames, and a final frame
+    /// without a delimiter is yielded as well.
+    pub fn split(self, delimiter: u8) -> FsSplit {
+        ::frame::split(self, delimiter)
+    }
+
+    /// Returns a `Stream` of chunks of the file, each of exactly `size` bytes.
+    ///
+    /// Only the final chunk may be shorter, if the length of the file isn't a
+    /// multiple of `size`.
+    ///
+    /// # Panic
+    ///
+    /// The passed argument must be larger than 0.
+    pub fn chunks_exact(self, size: usize) -> FsChunksExact {
+        ::frame::chunks_exact(self, size)
+    }
 
-                    let (tx, rx) = oneshot::channel();
+    /// Returns a `Stream` that yields each chunk of the file along with its
+    /// offset from the start of the file.
+    ///
+    /// The offset is the position of the first byte of the chunk, including
+    /// any position set by `ReadOptions::range`, `ReadOptions::from_end` or
+    /// `seek`.
+    pub fn with_offsets(self) -> FsOffsetReadStream {
+        FsOffsetReadStream { inner: self }
+    }
 
-                    let fut = Box::new(lazy(move || {
-                        let res = read(file, buf_size, buf).map_err(From::from);
+    /// Returns a reader of the file, implementing `std::io::Read`, that waits
+    /// for each chunk of this stream.
+    ///
+    /// Reading blocks the calling thread, so it must not be used on a thread
+    /// that runs futures, like one of the pool.
+    pub fn into_blocking_read(self) -> ::FsBlockingRead {
+        ::blocking::read(self)
+    }
 
-                        tx.send(res).map_err(|_| ())
-                    }));
+    /// Returns a reader of the file, implementing `AsyncRead`, that serves
+    /// reads from the chunks of this stream.
+    ///
+    /// Requires the `tokio-io` feature.
+    #[cfg(feature = "tokio-io")]
+    pub fn into_async_read(self) -> ::FsAsyncRead {
+        ::async_io::read(self)
+    }
 
-                    self.pool.executor.execute(fut).unwrap();
+    fn poll_chunk(&mut self) -> Poll<Option<(u64, Bytes)>, io::Error> {
+        self.poll_chunk_inner().map_err(|e| match self.context {
+            Some(ref context) => context.wrap(e),
+            None => e,
+        })
+    }
 
-                    self.state = State::Working(super::fs(rx), buf_size);
+    fn poll_chunk_inner(&mut self) -> Poll<Option<(u64, Bytes)>, io::Error> {
+        if !self.done && self.cancel.is_canceled() {
+            self.done = true;
+            return Err(super::canceled());
+        }
+        while !self.done {
+            let (gen, res) = match self.chunks.poll().map_err(|()| closed())? {
+                Async::Ready(Some(msg)) => msg,
+                Async::Ready(None) => return Err(closed()),
+                Async::NotReady => {
+                    if self.timeout.as_mut().is_some_and(Timeout::poll_expired) {
+                        self.done = true;
+                        self.cancel.cancel();
+                        return Err(io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            "timed out waiting for a chunk",
+                        ));
+                    }
+                    return Ok(Async::NotReady);
                 }
-                State::Eof => {
-                    self.state = State::Eof;
-                    return Ok(Async::Ready(None));
+            };
+            if let Some(ref mut timeout) = self.timeout {
+                timeout.reset();
+            }
+            if gen != self.gen {
+                // read before the last seek
+                continue;
+            }
+            match res {
+                Ok(Some((pos, chunk))) => {
+                    self.next = Some(pos + chunk.len() as u64);
+                    if let Some(ref mut remaining) = self.remaining {
+                        *remaining -= chunk.len() as u64;
+                    }
+                    return Ok(Async::Ready(Some((pos, chunk))));
+                }
+                Ok(None) => self.done = true,
+                Err(e) => {
+                    self.done = true;
+                    return Err(e);
                 }
-                State::Swapping => unreachable!(),
             }
         }
+        Ok(Async::Ready(None))
+    }
+}
+
+impl Stream for FsReadStream {
+    type Item = Bytes;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        let chunk = try_ready!(self.poll_chunk());
+        Ok(Async::Ready(chunk.map(|(_, bytes)| bytes)))
+    }
+}
+
+impl Drop for FsReadStream {
+    fn drop(&mut self) {
+        self.cancel.cancel();
     }
 }
 
@@ -171,35 +1027,191 @@ impl fmt::Debug for FsReadStream {
     }
 }
 
-fn read(mut file: File, buf_size: usize, mut buf: BytesMut) -> io::Result<(File, BytesMut)> {
-    if !buf.has_remaining_mut() {
-        buf.reserve(buf_size);
+/// A `Stream` of bytes from a target file, along with their offset in the
+/// file.
+///
+/// Created by `FsReadStream::with_offsets`.
+pub struct FsOffsetReadStream {
+    inner: FsReadStream,
+}
+
+impl Stream for FsOffsetReadStream {
+    type Item = (u64, Bytes);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        self.inner.poll_chunk()
+    }
+}
+
+impl fmt::Debug for FsOffsetReadStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsOffsetReadStream")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+fn closed() -> io::Error {
+    io::Error::other("read stream stopped")
+}
+
+fn read(
+    file: &mut File,
+    mut buf: BytesMut,
+    buf_size: usize,
+    limit: Option<u64>,
+    direct: bool,
+) -> io::Result<(u64, Bytes)> {
+    let pos = file.stream_position()?;
+    let len = match limit {
+        Some(limit) => cmp::min(buf_size as u64, limit) as usize,
+        None => buf_size,
+    };
+
+    // direct IO needs aligned positions and lengths, so this goes through the
+    // page cache instead
+    let buffered = direct && (pos % sys::DIRECT_ALIGN as u64 != 0 || len % sys::DIRECT_ALIGN != 0);
+    if buffered {
+        sys::set_direct(file, false)?;
     }
-    let n = file.read(unsafe { buf.bytes_mut() })?;
+
+    buf.reserve(len);
+    let n = {
+        let dst = unsafe { buf.bytes_mut() };
+        let len = cmp::min(dst.len(), len);
+        file.read(&mut dst[..len])?
+    };
     unsafe { buf.advance_mut(n) };
-    Ok((file, buf))
+
+    if buffered {
+        sys::set_direct(file, true)?;
+    }
+    Ok((pos, buf.freeze()))
+}
+
+/// Like `read`, copying the bytes out of `map` as far as it goes, and moving
+/// the position of `file` past them.
+#[cfg(feature = "mmap")]
+fn read_mapped(
+    file: &mut File,
+    map: &Mmap,
+    mut buf: BytesMut,
+    buf_size: usize,
+    limit: Option<u64>,
+) -> io::Result<(u64, Bytes)> {
+    let pos = file.stream_position()?;
+    if pos >= map.len() as u64 {
+        return read(file, buf, buf_size, limit, false);
+    }
+    let len = match limit {
+        Some(limit) => cmp::min(buf_size as u64, limit) as usize,
+        None => buf_size,
+    };
+    let start = pos as usize;
+    let end = cmp::min(map.len(), start + len);
+    buf.extend_from_slice(&map[start..end]);
+    file.seek(SeekFrom::Start(end as u64))?;
+    Ok((pos, buf.freeze()))
+}
+
+fn seek(file: &mut File, offset: Option<SeekFrom>) -> io::Result<()> {
+    match offset {
+        Some(SeekFrom::End(n)) if n < 0 => {
+            // clamp to the start of the file, instead of seeking before it
+            let len = file.metadata()?.len();
+            file.seek(SeekFrom::Start(len.saturating_sub(n.unsigned_abs())))?;
+        }
+        Some(pos) => {
+            file.seek(pos)?;
+        }
+        None => (),
+    }
+    Ok(())
 }
 
-fn finalize_buf_size(buf_size: Option<usize>, file: &File) -> usize {
+fn finalize_buf_size(buf_size: Option<usize>, file: &File, clamp: bool) -> usize {
     match file.metadata() {
         Ok(metadata) => {
             // try to get the buffer size from the OS if necessary
             let buf_size = buf_size.unwrap_or_else(|| get_block_size(&metadata));
 
             // if size is smaller than our chunk size, don't reserve wasted space
-            cmp::min(metadata.len() as usize, buf_size)
+            if clamp {
+                cmp::min(metadata.len() as usize, buf_size)
+            } else {
+                buf_size
+            }
         }
         _ => buf_size.unwrap_or(BUF_SIZE),
     }
 }
 
-fn open_and_read(path: &Path, buf_size: Option<usize>) -> io::Result<(File, BytesMut)> {
-    let file = File::open(path)?;
-    let final_buf_size = finalize_buf_size(buf_size, &file);
-    read(
-        file,
-        final_buf_size,
-        BytesMut::with_capacity(final_buf_size),
+/// Opens the file at `path` like a stream with `opts` would, at the start of
+/// its range, returning the file along with the most bytes to read of it and
+/// the buffer size to read it with.
+pub(crate) fn open_range(
+    path: &Path,
+    opts: &ReadOptions,
+) -> io::Result<(File, Option<u64>, usize)> {
+    let mut file = open_file(path, opts.open.clone(), opts.no_follow, opts.regular_only)?;
+    let buf_size = finalize_buf_size(opts.buffer_size, &file, false);
+    seek(&mut file, opts.start)?;
+    Ok((file, opts.len, buf_size))
+}
+
+/// The buffer size to read an already opened file with, by default.
+pub(crate) fn buffer_size(file: &File) -> usize {
+    finalize_buf_size(None, file, false)
+}
+
+fn open_file(
+    path: &Path,
+    open: Option<OpenOptions>,
+    no_follow: bool,
+    regular_only: bool,
+) -> io::Result<File> {
+    let open = open.unwrap_or_else(|| {
+        let mut open = OpenOptions::new();
+        open.read(true);
+        open
+    });
+    if no_follow || regular_only {
+        sys::open_checked(path, &open, no_follow, regular_only)
+    } else {
+        open.open(path)
+    }
+}
+
+pub(crate) fn read_to_end(path: &Path, max_size: Option<u64>) -> io::Result<Bytes> {
+    let mut file = File::open(path)?;
+    let len = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+    if let Some(max_size) = max_size {
+        if len > max_size {
+            return Err(too_large(max_size));
+        }
+    }
+
+    let mut buf = Vec::with_capacity(len as usize);
+    match max_size {
+        Some(max_size) => {
+            // the file may have grown since checking its length
+            file.take(max_size + 1).read_to_end(&mut buf)?;
+            if buf.len() as u64 > max_size {
+                return Err(too_large(max_size));
+            }
+        }
+        None => {
+            file.read_to_end(&mut buf)?;
+        }
+    }
+    Ok(buf.into())
+}
+
+fn too_large(max_size: u64) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("file is larger than the maximum size of {} bytes", max_size),
     )
 }
 
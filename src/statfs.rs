@@ -0,0 +1,41 @@
+/// Statistics of a filesystem, from `FsPool::statfs`.
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct FsStats {
+    total: u64,
+    free: u64,
+    available: u64,
+    block_size: u64,
+}
+
+impl FsStats {
+    pub(crate) fn new(total: u64, free: u64, available: u64, block_size: u64) -> FsStats {
+        FsStats {
+            total,
+            free,
+            available,
+            block_size,
+        }
+    }
+
+    /// The size of the filesystem in bytes.
+    pub fn total_bytes(&self) -> u64 {
+        self.total
+    }
+
+    /// The number of bytes that are free, including any reserved for
+    /// privileged users.
+    pub fn free_bytes(&self) -> u64 {
+        self.free
+    }
+
+    /// The number of bytes that are free for the current user to use.
+    pub fn available_bytes(&self) -> u64 {
+        self.available
+    }
+
+    /// The size of a block, which space is allocated in.
+    pub fn block_size(&self) -> u64 {
+        self.block_size
+    }
+}
@@ -0,0 +1,768 @@
+#[cfg(unix)]
+use std::ffi::{OsStr, OsString};
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+
+use bytes::BytesMut;
+
+use Advice;
+use FsStats;
+
+/// The alignment of buffers, positions and lengths for direct IO.
+pub(crate) const DIRECT_ALIGN: usize = 4096;
+
+/// Rounds `n` up to a multiple of `DIRECT_ALIGN`.
+pub(crate) fn align_up(n: usize) -> usize {
+    n.div_ceil(DIRECT_ALIGN) * DIRECT_ALIGN
+}
+
+/// Returns an empty buffer that starts at an address aligned for direct IO,
+/// with room for at least `cap` bytes.
+pub(crate) fn aligned(cap: usize) -> BytesMut {
+    let mut buf = BytesMut::with_capacity(cap + DIRECT_ALIGN);
+    let offset = buf.as_ptr().align_offset(DIRECT_ALIGN);
+    unsafe { buf.set_len(offset) };
+    buf.advance(offset);
+    buf
+}
+
+/// The granularity in which bytes are dropped from the page cache.
+pub(crate) const PAGE_SIZE: u64 = 4096;
+
+/// Whether direct IO is supported on this platform at all.
+pub(crate) const HAS_DIRECT: bool = cfg!(target_os = "linux");
+
+/// Enables or disables `O_DIRECT` on an opened file.
+///
+/// Fails with `InvalidInput` if the filesystem doesn't support direct IO.
+#[cfg(target_os = "linux")]
+pub(crate) fn set_direct(file: &File, direct: bool) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let fd = file.as_raw_fd();
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let flags = if direct {
+        flags | libc::O_DIRECT
+    } else {
+        flags & !libc::O_DIRECT
+    };
+    if unsafe { libc::fcntl(fd, libc::F_SETFL, flags) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn set_direct(_file: &File, _direct: bool) -> io::Result<()> {
+    Ok(())
+}
+
+/// Whether the file was opened in append mode.
+#[cfg(unix)]
+pub(crate) fn is_append(file: &File) -> io::Result<bool> {
+    use std::os::unix::io::AsRawFd;
+
+    let flags = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GETFL) };
+    if flags < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(flags & libc::O_APPEND != 0)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn is_append(_file: &File) -> io::Result<bool> {
+    Ok(false)
+}
+
+/// Advises the OS how `len` bytes from `offset` are going to be accessed.
+///
+/// A `len` of 0 means up to the end of the file.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+pub(crate) fn advise(file: &File, offset: u64, len: u64, advice: Advice) -> io::Result<()> {
+    use std::cmp;
+    use std::os::unix::io::AsRawFd;
+
+    let advice = match advice {
+        Advice::Sequential => libc::POSIX_FADV_SEQUENTIAL,
+        Advice::Random => libc::POSIX_FADV_RANDOM,
+        Advice::WillNeed => libc::POSIX_FADV_WILLNEED,
+        Advice::DontNeed => libc::POSIX_FADV_DONTNEED,
+    };
+    let offset = cmp::min(offset, libc::off_t::MAX as u64) as libc::off_t;
+    let len = cmp::min(len, libc::off_t::MAX as u64) as libc::off_t;
+    // returns the error, instead of setting errno
+    match unsafe { libc::posix_fadvise(file.as_raw_fd(), offset, len, advice) } {
+        0 => Ok(()),
+        err => Err(io::Error::from_raw_os_error(err)),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+pub(crate) fn advise(_file: &File, _offset: u64, _len: u64, _advice: Advice) -> io::Result<()> {
+    Ok(())
+}
+
+/// Reserves space for `file` to be at least `len` bytes long.
+#[cfg(target_os = "linux")]
+pub(crate) fn preallocate(file: &File, len: u64) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    if len == 0 {
+        return Ok(());
+    }
+    let len = len.min(libc::off_t::MAX as u64) as libc::off_t;
+    if unsafe { libc::fallocate(file.as_raw_fd(), 0, 0, len) } == 0 {
+        return Ok(());
+    }
+    let e = io::Error::last_os_error();
+    match e.raw_os_error() {
+        // not every filesystem supports reserving space
+        Some(libc::EOPNOTSUPP) => extend(file, len as u64),
+        _ => Err(e),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn preallocate(file: &File, len: u64) -> io::Result<()> {
+    extend(file, len)
+}
+
+fn extend(file: &File, len: u64) -> io::Result<()> {
+    if file.metadata()?.len() < len {
+        file.set_len(len)?;
+    }
+    Ok(())
+}
+
+/// Returns the next range of `file` from `pos` that contains data, or `None`
+/// if there is only a hole after `pos`.
+///
+/// Where holes can't be found, everything after `pos` is data.
+#[cfg(target_os = "linux")]
+pub(crate) fn next_data(file: &File, pos: u64) -> io::Result<Option<(u64, u64)>> {
+    use std::os::unix::io::AsRawFd;
+
+    let fd = file.as_raw_fd();
+    let start = unsafe { libc::lseek(fd, pos as libc::off_t, libc::SEEK_DATA) };
+    if start < 0 {
+        let e = io::Error::last_os_error();
+        return match e.raw_os_error() {
+            Some(libc::ENXIO) => Ok(None),
+            Some(libc::EINVAL) | Some(libc::EOPNOTSUPP) => Ok(Some((pos, u64::MAX))),
+            _ => Err(e),
+        };
+    }
+    let end = unsafe { libc::lseek(fd, start, libc::SEEK_HOLE) };
+    if end < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(Some((start as u64, end as u64)))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn next_data(_file: &File, pos: u64) -> io::Result<Option<(u64, u64)>> {
+    Ok(Some((pos, u64::MAX)))
+}
+
+/// Clones all blocks of `src` into `dst`, which must be empty.
+#[cfg(target_os = "linux")]
+pub(crate) fn reflink(src: &File, dst: &File) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    if unsafe { libc::ioctl(dst.as_raw_fd(), libc::FICLONE, src.as_raw_fd()) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn reflink(_src: &File, _dst: &File) -> io::Result<()> {
+    Err(io::Error::other("reflinks are not supported"))
+}
+
+/// Copies up to `len` bytes from `offset` of `src` to the same offset of
+/// `dst` within the kernel, with `copy_file_range`.
+///
+/// Returns `None` if the kernel can't copy between these files, before
+/// anything was copied.
+#[cfg(target_os = "linux")]
+pub(crate) fn copy_range(src: &File, dst: &File, offset: u64, len: u64) -> io::Result<Option<u64>> {
+    use std::os::unix::io::AsRawFd;
+
+    const MAX_CHUNK: u64 = 1 << 30;
+
+    let mut off_in = offset as libc::loff_t;
+    let mut off_out = offset as libc::loff_t;
+    let mut copied = 0;
+    while copied < len {
+        let chunk = (len - copied).min(MAX_CHUNK) as usize;
+        let n = unsafe {
+            libc::copy_file_range(
+                src.as_raw_fd(),
+                &mut off_in,
+                dst.as_raw_fd(),
+                &mut off_out,
+                chunk,
+                0,
+            )
+        };
+        if n < 0 {
+            let e = io::Error::last_os_error();
+            match e.raw_os_error() {
+                Some(libc::EINTR) => continue,
+                Some(libc::ENOSYS)
+                | Some(libc::EXDEV)
+                | Some(libc::EOPNOTSUPP)
+                | Some(libc::EINVAL)
+                | Some(libc::EPERM)
+                    if copied == 0 =>
+                {
+                    return Ok(None)
+                }
+                _ => return Err(e),
+            }
+        }
+        if n == 0 {
+            break;
+        }
+        copied += n as u64;
+    }
+    Ok(Some(copied))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn copy_range(
+    _src: &File,
+    _dst: &File,
+    _offset: u64,
+    _len: u64,
+) -> io::Result<Option<u64>> {
+    Ok(None)
+}
+
+/// Opens the file at `path` with `open`, failing instead of following a
+/// symbolic link as its last component if `no_follow`, and unless it is a
+/// regular file if `regular_only`.
+#[cfg(unix)]
+pub(crate) fn open_checked(
+    path: &Path,
+    open: &OpenOptions,
+    no_follow: bool,
+    regular_only: bool,
+) -> io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+
+    let mut open = open.clone();
+    let mut flags = 0;
+    if no_follow {
+        flags |= libc::O_NOFOLLOW;
+    }
+    if regular_only {
+        // opening a FIFO would block until its other end is opened
+        flags |= libc::O_NONBLOCK;
+    }
+    open.custom_flags(flags);
+
+    let file = match open.open(path) {
+        Ok(file) => file,
+        Err(ref e) if no_follow && e.raw_os_error() == Some(libc::ELOOP) && is_symlink(path) => {
+            return Err(symlink_error());
+        }
+        Err(ref e) if regular_only && e.raw_os_error() == Some(libc::ENXIO) => {
+            return Err(not_regular_error());
+        }
+        Err(e) => return Err(e),
+    };
+    if regular_only {
+        if !file.metadata()?.is_file() {
+            return Err(not_regular_error());
+        }
+        let fd = file.as_raw_fd();
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+        if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags & !libc::O_NONBLOCK) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(file)
+}
+
+#[cfg(windows)]
+pub(crate) fn open_checked(
+    path: &Path,
+    open: &OpenOptions,
+    no_follow: bool,
+    regular_only: bool,
+) -> io::Result<File> {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    const FILE_FLAG_OPEN_REPARSE_POINT: u32 = 0x0020_0000;
+
+    let mut open = open.clone();
+    if no_follow {
+        // opens a symbolic link or junction itself, instead of its target
+        open.custom_flags(FILE_FLAG_OPEN_REPARSE_POINT);
+    }
+    let file = open.open(path)?;
+    let metadata = file.metadata()?;
+    if no_follow && metadata.file_type().is_symlink() {
+        return Err(symlink_error());
+    }
+    if regular_only && !metadata.is_file() {
+        return Err(not_regular_error());
+    }
+    Ok(file)
+}
+
+#[cfg(unix)]
+fn is_symlink(path: &Path) -> bool {
+    path.symlink_metadata()
+        .map(|metadata| metadata.file_type().is_symlink())
+        .unwrap_or(false)
+}
+
+fn symlink_error() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "the path is a symbolic link, which no_follow doesn't open",
+    )
+}
+
+fn not_regular_error() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "the path is not a regular file",
+    )
+}
+
+/// Whether `e` is the error of renaming a file to another filesystem.
+#[cfg(unix)]
+pub(crate) fn is_cross_device(e: &io::Error) -> bool {
+    e.raw_os_error() == Some(libc::EXDEV)
+}
+
+#[cfg(windows)]
+pub(crate) fn is_cross_device(e: &io::Error) -> bool {
+    // ERROR_NOT_SAME_DEVICE
+    e.raw_os_error() == Some(17)
+}
+
+#[cfg(unix)]
+pub(crate) fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    use std::os::unix::fs::FileExt;
+    file.read_at(buf, offset)
+}
+
+#[cfg(windows)]
+pub(crate) fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    use std::os::windows::fs::FileExt;
+    file.seek_read(buf, offset)
+}
+
+#[cfg(unix)]
+pub(crate) fn write_all_at(file: &File, buf: &[u8], offset: u64) -> io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.write_all_at(buf, offset)
+}
+
+#[cfg(windows)]
+pub(crate) fn write_all_at(file: &File, mut buf: &[u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !buf.is_empty() {
+        match file.seek_write(buf, offset) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "failed to write whole buffer",
+                ))
+            }
+            Ok(n) => {
+                buf = &buf[n..];
+                offset += n as u64;
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Takes an advisory lock of the whole file, waiting for it if `wait`, or
+/// else returning `false` if another lock is in the way.
+#[cfg(unix)]
+pub(crate) fn lock(file: &File, exclusive: bool, wait: bool) -> io::Result<bool> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut op = if exclusive {
+        libc::LOCK_EX
+    } else {
+        libc::LOCK_SH
+    };
+    if !wait {
+        op |= libc::LOCK_NB;
+    }
+    loop {
+        if unsafe { libc::flock(file.as_raw_fd(), op) } == 0 {
+            return Ok(true);
+        }
+        let e = io::Error::last_os_error();
+        match e.kind() {
+            io::ErrorKind::Interrupted => (),
+            io::ErrorKind::WouldBlock if !wait => return Ok(false),
+            _ => return Err(e),
+        }
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn unlock(file: &File) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_UN) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+#[repr(C)]
+struct Overlapped {
+    internal: usize,
+    internal_high: usize,
+    offset: u32,
+    offset_high: u32,
+    event: *mut ::std::os::raw::c_void,
+}
+
+#[cfg(windows)]
+extern "system" {
+    fn LockFileEx(
+        file: *mut ::std::os::raw::c_void,
+        flags: u32,
+        reserved: u32,
+        len_low: u32,
+        len_high: u32,
+        overlapped: *mut Overlapped,
+    ) -> i32;
+    fn UnlockFileEx(
+        file: *mut ::std::os::raw::c_void,
+        reserved: u32,
+        len_low: u32,
+        len_high: u32,
+        overlapped: *mut Overlapped,
+    ) -> i32;
+}
+
+#[cfg(windows)]
+fn overlapped() -> Overlapped {
+    // the range starts at offset 0
+    Overlapped {
+        internal: 0,
+        internal_high: 0,
+        offset: 0,
+        offset_high: 0,
+        event: ::std::ptr::null_mut(),
+    }
+}
+
+#[cfg(windows)]
+pub(crate) fn lock(file: &File, exclusive: bool, wait: bool) -> io::Result<bool> {
+    use std::os::windows::io::AsRawHandle;
+
+    const LOCKFILE_FAIL_IMMEDIATELY: u32 = 0x1;
+    const LOCKFILE_EXCLUSIVE_LOCK: u32 = 0x2;
+    const ERROR_LOCK_VIOLATION: i32 = 33;
+
+    let mut flags = 0;
+    if exclusive {
+        flags |= LOCKFILE_EXCLUSIVE_LOCK;
+    }
+    if !wait {
+        flags |= LOCKFILE_FAIL_IMMEDIATELY;
+    }
+    let mut overlapped = overlapped();
+    let handle = file.as_raw_handle() as *mut _;
+    // the whole file is locked, including what is appended to it later
+    if unsafe { LockFileEx(handle, flags, 0, !0, !0, &mut overlapped) } != 0 {
+        return Ok(true);
+    }
+    let e = io::Error::last_os_error();
+    if !wait && e.raw_os_error() == Some(ERROR_LOCK_VIOLATION) {
+        return Ok(false);
+    }
+    Err(e)
+}
+
+#[cfg(windows)]
+pub(crate) fn unlock(file: &File) -> io::Result<()> {
+    use std::os::windows::io::AsRawHandle;
+
+    let mut overlapped = overlapped();
+    let handle = file.as_raw_handle() as *mut _;
+    if unsafe { UnlockFileEx(handle, 0, !0, !0, &mut overlapped) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn c_string(s: &OsStr) -> io::Result<::std::ffi::CString> {
+    use std::os::unix::ffi::OsStrExt;
+
+    ::std::ffi::CString::new(s.as_bytes()).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "path or name contains a nul byte",
+        )
+    })
+}
+
+/// Calls `f` first to get the size of a value and then to read it, until
+/// the value didn't grow in between.
+#[cfg(unix)]
+fn read_sized<F>(f: F) -> io::Result<Vec<u8>>
+where
+    F: Fn(*mut u8, usize) -> isize,
+{
+    loop {
+        let len = f(::std::ptr::null_mut(), 0);
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut buf = vec![0; len as usize];
+        let n = f(buf.as_mut_ptr(), buf.len());
+        if n < 0 {
+            let e = io::Error::last_os_error();
+            if e.raw_os_error() == Some(libc::ERANGE) {
+                continue;
+            }
+            return Err(e);
+        }
+        buf.truncate(n as usize);
+        return Ok(buf);
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod xattr {
+    use libc::{c_char, c_void};
+
+    pub const MISSING: i32 = libc::ENODATA;
+
+    pub unsafe fn get(path: *const c_char, name: *const c_char, buf: *mut u8, len: usize) -> isize {
+        libc::getxattr(path, name, buf as *mut c_void, len)
+    }
+
+    pub unsafe fn set(path: *const c_char, name: *const c_char, value: &[u8]) -> i32 {
+        libc::setxattr(path, name, value.as_ptr() as *const c_void, value.len(), 0)
+    }
+
+    pub unsafe fn remove(path: *const c_char, name: *const c_char) -> i32 {
+        libc::removexattr(path, name)
+    }
+
+    pub unsafe fn list(path: *const c_char, buf: *mut u8, len: usize) -> isize {
+        libc::listxattr(path, buf as *mut c_char, len)
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod xattr {
+    use libc::{c_char, c_void};
+
+    pub const MISSING: i32 = libc::ENOATTR;
+
+    pub unsafe fn get(path: *const c_char, name: *const c_char, buf: *mut u8, len: usize) -> isize {
+        libc::getxattr(path, name, buf as *mut c_void, len, 0, 0)
+    }
+
+    pub unsafe fn set(path: *const c_char, name: *const c_char, value: &[u8]) -> i32 {
+        libc::setxattr(
+            path,
+            name,
+            value.as_ptr() as *const c_void,
+            value.len(),
+            0,
+            0,
+        )
+    }
+
+    pub unsafe fn remove(path: *const c_char, name: *const c_char) -> i32 {
+        libc::removexattr(path, name, 0)
+    }
+
+    pub unsafe fn list(path: *const c_char, buf: *mut u8, len: usize) -> isize {
+        libc::listxattr(path, buf as *mut c_char, len, 0)
+    }
+}
+
+/// Reads the extended attribute `name` of `path`, or `None` if it isn't
+/// set.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+pub(crate) fn get_xattr(path: &Path, name: &OsStr) -> io::Result<Option<Vec<u8>>> {
+    let c_path = c_string(path.as_os_str())?;
+    let c_name = c_string(name)?;
+    let value =
+        read_sized(|buf, len| unsafe { xattr::get(c_path.as_ptr(), c_name.as_ptr(), buf, len) });
+    match value {
+        Ok(value) => Ok(Some(value)),
+        Err(ref e) if e.raw_os_error() == Some(xattr::MISSING) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+pub(crate) fn set_xattr(path: &Path, name: &OsStr, value: &[u8]) -> io::Result<()> {
+    let c_path = c_string(path.as_os_str())?;
+    let c_name = c_string(name)?;
+    if unsafe { xattr::set(c_path.as_ptr(), c_name.as_ptr(), value) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+pub(crate) fn remove_xattr(path: &Path, name: &OsStr) -> io::Result<()> {
+    let c_path = c_string(path.as_os_str())?;
+    let c_name = c_string(name)?;
+    if unsafe { xattr::remove(c_path.as_ptr(), c_name.as_ptr()) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+pub(crate) fn list_xattrs(path: &Path) -> io::Result<Vec<OsString>> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = c_string(path.as_os_str())?;
+    let names = read_sized(|buf, len| unsafe { xattr::list(c_path.as_ptr(), buf, len) })?;
+    // the names are each terminated by a nul byte
+    Ok(names
+        .split(|&b| b == 0)
+        .filter(|name| !name.is_empty())
+        .map(|name| OsStr::from_bytes(name).to_owned())
+        .collect())
+}
+
+#[cfg(all(
+    unix,
+    not(any(target_os = "linux", target_os = "android", target_os = "macos"))
+))]
+fn xattrs_unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "extended attributes are not supported",
+    )
+}
+
+#[cfg(all(
+    unix,
+    not(any(target_os = "linux", target_os = "android", target_os = "macos"))
+))]
+pub(crate) fn get_xattr(_path: &Path, _name: &OsStr) -> io::Result<Option<Vec<u8>>> {
+    Err(xattrs_unsupported())
+}
+
+#[cfg(all(
+    unix,
+    not(any(target_os = "linux", target_os = "android", target_os = "macos"))
+))]
+pub(crate) fn set_xattr(_path: &Path, _name: &OsStr, _value: &[u8]) -> io::Result<()> {
+    Err(xattrs_unsupported())
+}
+
+#[cfg(all(
+    unix,
+    not(any(target_os = "linux", target_os = "android", target_os = "macos"))
+))]
+pub(crate) fn remove_xattr(_path: &Path, _name: &OsStr) -> io::Result<()> {
+    Err(xattrs_unsupported())
+}
+
+#[cfg(all(
+    unix,
+    not(any(target_os = "linux", target_os = "android", target_os = "macos"))
+))]
+pub(crate) fn list_xattrs(_path: &Path) -> io::Result<Vec<OsString>> {
+    Err(xattrs_unsupported())
+}
+
+#[cfg(unix)]
+pub(crate) fn statfs(path: &Path) -> io::Result<FsStats> {
+    let c_path = c_string(path.as_os_str())?;
+    let mut stats = unsafe { ::std::mem::zeroed::<libc::statvfs>() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stats) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // the counts of blocks are in units of the fragment size
+    let fragment = stats.f_frsize as u64;
+    Ok(FsStats::new(
+        stats.f_blocks as u64 * fragment,
+        stats.f_bfree as u64 * fragment,
+        stats.f_bavail as u64 * fragment,
+        stats.f_bsize as u64,
+    ))
+}
+
+#[cfg(windows)]
+pub(crate) fn statfs(path: &Path) -> io::Result<FsStats> {
+    use std::os::windows::ffi::OsStrExt;
+
+    extern "system" {
+        fn GetDiskFreeSpaceExW(
+            directory: *const u16,
+            available: *mut u64,
+            total: *mut u64,
+            free: *mut u64,
+        ) -> i32;
+        fn GetVolumePathNameW(path: *const u16, volume: *mut u16, len: u32) -> i32;
+        fn GetDiskFreeSpaceW(
+            root: *const u16,
+            sectors_per_cluster: *mut u32,
+            bytes_per_sector: *mut u32,
+            free_clusters: *mut u32,
+            total_clusters: *mut u32,
+        ) -> i32;
+    }
+
+    let wide = path
+        .as_os_str()
+        .encode_wide()
+        .chain(Some(0))
+        .collect::<Vec<u16>>();
+    let (mut available, mut total, mut free) = (0, 0, 0);
+    if unsafe { GetDiskFreeSpaceExW(wide.as_ptr(), &mut available, &mut total, &mut free) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // the cluster size is only known for the root of the volume
+    let mut volume = vec![0u16; wide.len() + 1];
+    if unsafe { GetVolumePathNameW(wide.as_ptr(), volume.as_mut_ptr(), volume.len() as u32) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let (mut sectors, mut bytes, mut free_clusters, mut total_clusters) = (0, 0, 0, 0);
+    let ok = unsafe {
+        GetDiskFreeSpaceW(
+            volume.as_ptr(),
+            &mut sectors,
+            &mut bytes,
+            &mut free_clusters,
+            &mut total_clusters,
+        )
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(FsStats::new(
+        total,
+        free,
+        available,
+        sectors as u64 * bytes as u64,
+    ))
+}
@@ -0,0 +1,154 @@
+use std::collections::hash_map::RandomState;
+use std::fs::{self, DirBuilder, File, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{fmt, io, mem, process};
+
+use futures::future::lazy;
+
+use FsFuture;
+use FsPool;
+
+/// How many names are tried before giving up.
+const ATTEMPTS: u32 = 1 << 10;
+
+pub(crate) fn create_file(dir: &Path) -> io::Result<(PathBuf, File)> {
+    create(dir, |path| {
+        let mut opts = OpenOptions::new();
+        opts.read(true).write(true).create_new(true);
+        file_mode(&mut opts);
+        opts.open(path)
+    })
+}
+
+#[cfg(unix)]
+fn file_mode(opts: &mut OpenOptions) {
+    use std::os::unix::fs::OpenOptionsExt;
+    opts.mode(0o600);
+}
+
+#[cfg(not(unix))]
+fn file_mode(_opts: &mut OpenOptions) {}
+
+pub(crate) fn create_dir(pool: &FsPool, dir: &Path) -> io::Result<FsTempDir> {
+    let (path, ()) = create(dir, |path| {
+        let mut builder = DirBuilder::new();
+        dir_mode(&mut builder);
+        builder.create(path)
+    })?;
+    Ok(FsTempDir {
+        path,
+        pool: pool.clone(),
+    })
+}
+
+#[cfg(unix)]
+fn dir_mode(builder: &mut DirBuilder) {
+    use std::os::unix::fs::DirBuilderExt;
+    builder.mode(0o700);
+}
+
+#[cfg(not(unix))]
+fn dir_mode(_builder: &mut DirBuilder) {}
+
+/// A temporary directory, that is removed with all of its contents when
+/// dropped.
+///
+/// Created by `FsPool::tempdir` and `FsPool::tempdir_in`. Dropping it only
+/// schedules the removal on the pool, and any error is ignored, including
+/// the pool not running anymore. Use `close` to wait for the removal and to
+/// get its error instead.
+pub struct FsTempDir {
+    path: PathBuf,
+    pool: FsPool,
+}
+
+impl FsTempDir {
+    /// The path of this directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns a `Future` that resolves when this directory and all of its
+    /// contents are removed.
+    pub fn close(mut self) -> FsFuture<()> {
+        let path = mem::take(&mut self.path);
+        self.pool.exec(move || fs::remove_dir_all(path))
+    }
+
+    /// Keeps this directory when dropped, returning its path.
+    pub fn into_path(mut self) -> PathBuf {
+        mem::take(&mut self.path)
+    }
+}
+
+impl Deref for FsTempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for FsTempDir {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for FsTempDir {
+    fn drop(&mut self) {
+        // an empty path was already closed or kept
+        if self.path.as_os_str().is_empty() {
+            return;
+        }
+        let path = mem::take(&mut self.path);
+        let fut = Box::new(lazy(move || {
+            let _ = fs::remove_dir_all(path);
+            Ok(())
+        }));
+        let _ = self.pool.spawn_cleanup(fut);
+    }
+}
+
+impl fmt::Debug for FsTempDir {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsTempDir")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+/// Calls `f` with new paths in `dir`, until one of them doesn't exist yet.
+fn create<T, F>(dir: &Path, f: F) -> io::Result<(PathBuf, T)>
+where
+    F: Fn(&Path) -> io::Result<T>,
+{
+    for _ in 0..ATTEMPTS {
+        let path = dir.join(random_name());
+        match f(&path) {
+            Ok(created) => return Ok((path, created)),
+            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => (),
+            Err(e) => return Err(::path_error(dir, e)),
+        }
+    }
+    Err(::path_error(
+        dir,
+        io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "failed to find an unused temporary name",
+        ),
+    ))
+}
+
+fn random_name() -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    // every `RandomState` is seeded differently
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_usize(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.write_u32(process::id());
+    format!(".tmp{:016x}", hasher.finish())
+}
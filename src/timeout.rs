@@ -0,0 +1,78 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::{Async, Future};
+
+/// A timer of the caller, that expires once its future resolves.
+///
+/// A timer that fails counts as expired as well.
+pub(crate) type Expiry = Box<dyn Future<Item = (), Error = ()> + Send>;
+
+/// Creates timers from the duration to wait for, with a function of the
+/// caller.
+#[derive(Clone)]
+pub(crate) struct Timer(Arc<dyn Fn(Duration) -> Expiry + Send + Sync>);
+
+impl Timer {
+    pub(crate) fn new<F, T>(timer: F) -> Timer
+    where
+        F: Fn(Duration) -> T + Send + Sync + 'static,
+        T: Future<Item = ()> + Send + 'static,
+    {
+        Timer(Arc::new(move |duration| -> Expiry {
+            Box::new(timer(duration).then(|_| Ok(())))
+        }))
+    }
+
+    /// Returns a timer that expires after `duration`.
+    pub(crate) fn delay(&self, duration: Duration) -> Expiry {
+        (self.0)(duration)
+    }
+}
+
+impl fmt::Debug for Timer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Timer").finish()
+    }
+}
+
+/// A timeout that is pushed back whenever progress is made.
+///
+/// Resetting it doesn't create a timer. Instead, once the current timer
+/// expires, it waits again for whatever is left since the last reset.
+pub(crate) struct Timeout {
+    duration: Duration,
+    timer: Timer,
+    reset: Instant,
+    expiry: Expiry,
+}
+
+impl Timeout {
+    pub(crate) fn new(duration: Duration, timer: &Timer) -> Timeout {
+        Timeout {
+            duration,
+            timer: timer.clone(),
+            reset: Instant::now(),
+            expiry: timer.delay(duration),
+        }
+    }
+
+    /// Restarts the timeout from now.
+    pub(crate) fn reset(&mut self) {
+        self.reset = Instant::now();
+    }
+
+    /// Whether the duration has passed since the last reset, registering the
+    /// current task with the timer otherwise.
+    pub(crate) fn poll_expired(&mut self) -> bool {
+        while self.expiry.poll() != Ok(Async::NotReady) {
+            let elapsed = self.reset.elapsed();
+            if elapsed >= self.duration {
+                return true;
+            }
+            self.expiry = self.timer.delay(self.duration - elapsed);
+        }
+        false
+    }
+}
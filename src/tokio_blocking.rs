@@ -0,0 +1,88 @@
+use std::cell::Cell;
+use std::io;
+
+use futures::future::{ExecuteError, ExecuteErrorKind, Executor};
+use futures::{Async, Future, Poll};
+use tokio_executor::{DefaultExecutor, Executor as TokioExecutor};
+use tokio_threadpool;
+
+type Task = Box<dyn Future<Item = (), Error = ()> + Send>;
+
+thread_local! {
+    /// Whether the task being polled on this thread can't block it.
+    static REFUSED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Spawns the tasks of a pool on the default tokio executor, running each
+/// poll of them within `tokio_threadpool::blocking`.
+pub(crate) struct TokioBlocking;
+
+impl Executor<Task> for TokioBlocking {
+    fn execute(&self, task: Task) -> Result<(), ExecuteError<Task>> {
+        let mut executor = DefaultExecutor::current();
+        if let Err(e) = executor.status() {
+            let kind = if e.is_at_capacity() {
+                ExecuteErrorKind::NoCapacity
+            } else {
+                ExecuteErrorKind::Shutdown
+            };
+            return Err(ExecuteError::new(kind, task));
+        }
+        // if the executor goes away right now, the task is dropped with it,
+        // which its handle sees just the same
+        let _ = executor.spawn(Box::new(Blocking { task }));
+        Ok(())
+    }
+}
+
+struct Blocking {
+    task: Task,
+}
+
+impl Future for Blocking {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        let task = &mut self.task;
+        match tokio_threadpool::blocking(|| task.poll()) {
+            Ok(Async::Ready(res)) => res,
+            // the pool has no room for another blocking section yet, and
+            // notifies this task once it does
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Err(_) => {
+                // not on a worker of a tokio threadpool, so the work fails
+                // instead of blocking this thread
+                let _refused = Refused::enter();
+                task.poll()
+            }
+        }
+    }
+}
+
+/// Marks the current thread as refusing to block, until dropped.
+struct Refused;
+
+impl Refused {
+    fn enter() -> Refused {
+        REFUSED.with(|refused| refused.set(true));
+        Refused
+    }
+}
+
+impl Drop for Refused {
+    fn drop(&mut self) {
+        REFUSED.with(|refused| refused.set(false));
+    }
+}
+
+/// Fails if the task being polled on this thread can't block it.
+pub(crate) fn check() -> io::Result<()> {
+    if REFUSED.with(Cell::get) {
+        Err(io::Error::other(
+            "FsPool::with_tokio_blocking must run on a tokio threadpool",
+        ))
+    } else {
+        Ok(())
+    }
+}
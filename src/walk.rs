@@ -0,0 +1,454 @@
+use std::collections::VecDeque;
+use std::ffi::OsStr;
+use std::fs::{self, FileType, ReadDir};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::{fmt, mem};
+
+use futures::{Async, Future, Poll, Stream};
+
+use FsFuture;
+use FsPool;
+
+const BATCH_SIZE: usize = 64;
+
+/// Options for how to walk a directory tree.
+///
+/// The default is to walk the whole tree below the root, yielding each
+/// directory before its contents.
+#[derive(Clone, Debug, Default)]
+pub struct WalkOptions {
+    max_depth: Option<usize>,
+    contents_first: bool,
+    include_root: bool,
+    skip_errors: bool,
+}
+
+impl WalkOptions {
+    /// The maximum depth of entries to yield, where the root is at depth 0
+    /// and the entries directly in it at depth 1.
+    ///
+    /// Directories at the maximum depth are yielded, but not walked into.
+    ///
+    /// Default is no maximum.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Whether to yield each directory after its contents, instead of
+    /// before them.
+    ///
+    /// Default is `false`.
+    pub fn contents_first(mut self, contents_first: bool) -> Self {
+        self.contents_first = contents_first;
+        self
+    }
+
+    /// Whether to yield the root itself as well, at depth 0.
+    ///
+    /// Default is `false`.
+    pub fn include_root(mut self, include_root: bool) -> Self {
+        self.include_root = include_root;
+        self
+    }
+
+    /// Whether to skip entries that fail to be read, like directories that
+    /// can't be opened, instead of yielding their errors.
+    ///
+    /// Default is `false`.
+    pub fn skip_errors(mut self, skip_errors: bool) -> Self {
+        self.skip_errors = skip_errors;
+        self
+    }
+}
+
+pub(crate) fn new<P>(pool: &FsPool, path: P, opts: WalkOptions) -> FsWalkStream
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    let root = path.as_ref().to_owned();
+    FsWalkStream {
+        root: root.clone(),
+        entries: VecDeque::new(),
+        pool: pool.clone(),
+        state: State::Ready(Walker {
+            opts,
+            root: Some(root),
+            stack: Vec::new(),
+            queue: VecDeque::new(),
+        }),
+    }
+}
+
+/// A `Stream` of the entries in a directory tree, walked depth first.
+///
+/// Entries are fetched from the pool in batches. An error doesn't end the
+/// stream, so polling it again continues the walk after the entry that
+/// failed. Symbolic links are yielded as they are, without being followed.
+pub struct FsWalkStream {
+    root: PathBuf,
+    entries: VecDeque<io::Result<FsWalkEntry>>,
+    pool: FsPool,
+    state: State,
+}
+
+/// An entry in a directory tree, yielded by `FsWalkStream`.
+#[derive(Debug)]
+pub struct FsWalkEntry {
+    path: PathBuf,
+    file_type: FileType,
+    depth: usize,
+}
+
+impl FsWalkEntry {
+    /// The full path of this entry.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The bare file name of this entry, without any leading path.
+    ///
+    /// For a root like `..`, this is the whole path.
+    pub fn file_name(&self) -> &OsStr {
+        self.path
+            .file_name()
+            .unwrap_or_else(|| self.path.as_os_str())
+    }
+
+    /// The file type of this entry.
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    /// The depth of this entry below the root, which is at depth 0.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+}
+
+type Batch = (Walker, Vec<io::Result<FsWalkEntry>>);
+
+enum State {
+    Working(FsFuture<Batch>),
+    Ready(Walker),
+    Eof,
+    Swapping,
+}
+
+impl Stream for FsWalkStream {
+    type Item = FsWalkEntry;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            if let Some(entry) = self.entries.pop_front() {
+                return entry.map(|entry| Async::Ready(Some(entry)));
+            }
+
+            match mem::replace(&mut self.state, State::Swapping) {
+                State::Working(mut rx) => {
+                    let polled = rx.poll();
+                    self.state = State::Working(rx);
+                    if polled.is_err() {
+                        // the walker was lost along with the task
+                        self.state = State::Eof;
+                    }
+                    let (walker, entries) = try_ready!(polled);
+
+                    if walker.is_done() {
+                        self.state = State::Eof;
+                    } else {
+                        self.state = State::Ready(walker);
+                    }
+                    self.entries.extend(entries);
+                }
+                State::Ready(mut walker) => {
+                    let rx = self.pool.exec(move || {
+                        let entries = walker.batch();
+                        Ok((walker, entries))
+                    });
+                    self.state = State::Working(rx);
+                }
+                State::Eof => {
+                    self.state = State::Eof;
+                    return Ok(Async::Ready(None));
+                }
+                State::Swapping => unreachable!(),
+            }
+        }
+    }
+}
+
+impl fmt::Debug for FsWalkStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsWalkStream")
+            .field("root", &self.root)
+            .finish()
+    }
+}
+
+/// The blocking state of a walk, moved to the pool for every batch.
+struct Walker {
+    opts: WalkOptions,
+    /// The root, until the walk has started.
+    root: Option<PathBuf>,
+    stack: Vec<Frame>,
+    /// The entries and errors to yield before continuing the walk.
+    queue: VecDeque<io::Result<FsWalkEntry>>,
+}
+
+/// A directory being walked.
+struct Frame {
+    dir: ReadDir,
+    /// The depth of the entries in the directory.
+    depth: usize,
+    /// The directory itself, when yielding it after its contents.
+    entry: Option<FsWalkEntry>,
+}
+
+impl Walker {
+    fn is_done(&self) -> bool {
+        self.root.is_none() && self.stack.is_empty() && self.queue.is_empty()
+    }
+
+    fn batch(&mut self) -> Vec<io::Result<FsWalkEntry>> {
+        let mut entries = Vec::with_capacity(BATCH_SIZE);
+        while entries.len() < BATCH_SIZE {
+            match self.next() {
+                Some(Err(_)) if self.opts.skip_errors => (),
+                Some(entry) => entries.push(entry),
+                None => break,
+            }
+        }
+        entries
+    }
+
+    fn next(&mut self) -> Option<io::Result<FsWalkEntry>> {
+        loop {
+            if let Some(entry) = self.queue.pop_front() {
+                return Some(entry);
+            }
+            if let Some(root) = self.root.take() {
+                self.start(root);
+                continue;
+            }
+
+            let (entry, depth) = {
+                let frame = self.stack.last_mut()?;
+                (frame.dir.next(), frame.depth)
+            };
+            match entry {
+                Some(Ok(entry)) => match entry.file_type() {
+                    Ok(file_type) => self.enter(entry.path(), file_type, depth, true),
+                    Err(e) => return Some(Err(::path_error(&entry.path(), e))),
+                },
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    if let Some(entry) = self.stack.pop().unwrap().entry {
+                        return Some(Ok(entry));
+                    }
+                }
+            }
+        }
+    }
+
+    fn start(&mut self, root: PathBuf) {
+        match fs::metadata(&root) {
+            Ok(metadata) => {
+                let include = self.opts.include_root;
+                self.enter(root, metadata.file_type(), 0, include);
+            }
+            Err(e) => self.queue.push_back(Err(::path_error(&root, e))),
+        }
+    }
+
+    /// Queues an entry, and starts walking it if it is a directory within the
+    /// maximum depth.
+    fn enter(&mut self, path: PathBuf, file_type: FileType, depth: usize, include: bool) {
+        let walk = file_type.is_dir() && self.opts.max_depth.map_or(true, |max| depth < max);
+        let entry = FsWalkEntry {
+            path,
+            file_type,
+            depth,
+        };
+        if !walk {
+            if include {
+                self.queue.push_back(Ok(entry));
+            }
+            return;
+        }
+
+        let dir = fs::read_dir(&entry.path).map_err(|e| ::path_error(&entry.path, e));
+        let entry = if include { Some(entry) } else { None };
+        let contents_first = self.opts.contents_first;
+        match dir {
+            Ok(dir) => {
+                let entry = if contents_first {
+                    entry
+                } else {
+                    self.queue.extend(entry.map(Ok));
+                    None
+                };
+                self.stack.push(Frame {
+                    dir,
+                    depth: depth + 1,
+                    entry,
+                });
+            }
+            // the directory itself is still yielded in its place
+            Err(e) if contents_first => {
+                self.queue.push_back(Err(e));
+                self.queue.extend(entry.map(Ok));
+            }
+            Err(e) => {
+                self.queue.extend(entry.map(Ok));
+                self.queue.push_back(Err(e));
+            }
+        }
+    }
+}
+
+/// Options for how to sum up a directory tree.
+///
+/// The default is to sum up the lengths of the files, without following
+/// symbolic links.
+#[derive(Clone, Debug, Default)]
+pub struct DirStatsOptions {
+    follow_symlinks: bool,
+    allocated: bool,
+}
+
+impl DirStatsOptions {
+    /// Whether to count whatever symbolic links point to, instead of skipping
+    /// the links.
+    ///
+    /// A file linked to more than once is counted every time, and a link to
+    /// a directory that contains it is an error.
+    ///
+    /// Default is `false`.
+    pub fn follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Whether to count the space allocated for each file on disk, instead
+    /// of its length.
+    ///
+    /// This differs for sparse files, and for files not filling their last
+    /// block. It only has an effect on Unix.
+    ///
+    /// Default is `false`.
+    pub fn allocated(mut self, allocated: bool) -> Self {
+        self.allocated = allocated;
+        self
+    }
+}
+
+/// The totals of a directory tree, from `FsPool::dir_stats`.
+#[derive(Debug, Default)]
+pub struct DirStats {
+    files: u64,
+    dirs: u64,
+    bytes: u64,
+    largest: Option<(PathBuf, u64)>,
+}
+
+impl DirStats {
+    /// The number of files in the tree.
+    pub fn files(&self) -> u64 {
+        self.files
+    }
+
+    /// The number of directories in the tree, not counting the root.
+    pub fn dirs(&self) -> u64 {
+        self.dirs
+    }
+
+    /// The total size of all files in the tree.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// The path and size of the largest file in the tree.
+    pub fn largest(&self) -> Option<(&Path, u64)> {
+        self.largest
+            .as_ref()
+            .map(|&(ref path, size)| (path.as_path(), size))
+    }
+}
+
+pub(crate) fn dir_stats(path: &Path, opts: &DirStatsOptions) -> io::Result<DirStats> {
+    let mut stats = DirStats::default();
+    let mut ancestors = Vec::new();
+    if opts.follow_symlinks {
+        ancestors.push(fs::canonicalize(path).map_err(|e| ::path_error(path, e))?);
+    }
+    sum_dir(path, opts, &mut stats, &mut ancestors)?;
+    Ok(stats)
+}
+
+fn sum_dir(
+    dir: &Path,
+    opts: &DirStatsOptions,
+    stats: &mut DirStats,
+    ancestors: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir).map_err(|e| ::path_error(dir, e))? {
+        let entry = entry.map_err(|e| ::path_error(dir, e))?;
+        let path = entry.path();
+        let mut metadata = entry.metadata().map_err(|e| ::path_error(&path, e))?;
+        if metadata.file_type().is_symlink() {
+            if !opts.follow_symlinks {
+                continue;
+            }
+            metadata = fs::metadata(&path).map_err(|e| ::path_error(&path, e))?;
+        }
+
+        if metadata.is_dir() {
+            if opts.follow_symlinks {
+                let canonical = fs::canonicalize(&path).map_err(|e| ::path_error(&path, e))?;
+                if ancestors.contains(&canonical) {
+                    return Err(::path_error(
+                        &path,
+                        io::Error::other("symbolic link points to a directory that contains it"),
+                    ));
+                }
+                ancestors.push(canonical);
+            }
+            stats.dirs += 1;
+            sum_dir(&path, opts, stats, ancestors)?;
+            if opts.follow_symlinks {
+                ancestors.pop();
+            }
+        } else if metadata.is_file() {
+            let size = if opts.allocated {
+                allocated_size(&metadata)
+            } else {
+                metadata.len()
+            };
+            stats.files += 1;
+            stats.bytes += size;
+            if stats
+                .largest
+                .as_ref()
+                .map_or(true, |&(_, largest)| size > largest)
+            {
+                stats.largest = Some((path, size));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn allocated_size(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    // always in units of 512 bytes, whatever the block size
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn allocated_size(metadata: &fs::Metadata) -> u64 {
+    metadata.len()
+}
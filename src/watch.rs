@@ -0,0 +1,528 @@
+#[cfg(feature = "notify")]
+use std::collections::VecDeque;
+#[cfg(feature = "notify")]
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+#[cfg(feature = "notify")]
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use std::{fmt, fs, io, mem};
+
+#[cfg(feature = "notify")]
+use futures::task::{self, Task};
+use futures::{Async, Future, Poll, Stream};
+#[cfg(feature = "notify")]
+use notify::event::{EventKind, ModifyKind, RenameMode};
+#[cfg(feature = "notify")]
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+
+use timeout::{Expiry, Timer};
+use FsFuture;
+use FsPool;
+
+#[cfg(feature = "notify")]
+const DEFAULT_CAPACITY: usize = 1024;
+
+#[cfg(feature = "notify")]
+/// Options for how to watch a path.
+///
+/// The default is to only watch the path itself, or the entries directly in
+/// it for a directory, and to keep every event.
+#[derive(Clone, Debug)]
+pub struct WatchOptions {
+    recursive: bool,
+    coalesce: bool,
+    capacity: usize,
+}
+
+#[cfg(feature = "notify")]
+impl WatchOptions {
+    /// Whether to watch the whole tree of a directory.
+    ///
+    /// Default is `false`.
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// Whether to drop an event that is the same as one that is still
+    /// waiting to be yielded, with nothing else about the path in between.
+    ///
+    /// This turns many writes to a file into a single `Modified` event, as
+    /// long as the stream isn't polled in between.
+    ///
+    /// Default is `false`.
+    pub fn coalesce(mut self, coalesce: bool) -> Self {
+        self.coalesce = coalesce;
+        self
+    }
+
+    /// The most events that wait to be yielded.
+    ///
+    /// Any more events are dropped, and yielded as a single
+    /// `FsWatchEvent::Overflow` instead.
+    ///
+    /// Default is 1024.
+    ///
+    /// # Panic
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        assert!(capacity > 0, "capacity must be larger than 0");
+        self.capacity = capacity;
+        self
+    }
+}
+
+#[cfg(feature = "notify")]
+impl Default for WatchOptions {
+    fn default() -> WatchOptions {
+        WatchOptions {
+            recursive: false,
+            coalesce: false,
+            capacity: DEFAULT_CAPACITY,
+        }
+    }
+}
+
+/// A change to a watched path, yielded by `FsWatchStream`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FsWatchEvent {
+    /// A file or directory was created, or moved to the path.
+    Created(PathBuf),
+    /// The contents or metadata of a file or directory changed.
+    Modified(PathBuf),
+    /// A file or directory was removed, or moved away from the path.
+    Removed(PathBuf),
+    /// A file or directory was moved from the first path to the second.
+    Renamed(PathBuf, PathBuf),
+    /// Events were dropped, because they weren't yielded fast enough.
+    ///
+    /// Anything might have changed in between, so it is best to look at the
+    /// watched paths again.
+    Overflow,
+    /// The path as the watching started, and whether it existed.
+    ///
+    /// Only yielded first by `FsPool::poll_watch`.
+    Snapshot(PathBuf, bool),
+}
+
+#[cfg(feature = "notify")]
+impl FsWatchEvent {
+    /// Whether this event is about `path`.
+    fn concerns(&self, path: &Path) -> bool {
+        match *self {
+            FsWatchEvent::Created(ref p)
+            | FsWatchEvent::Modified(ref p)
+            | FsWatchEvent::Removed(ref p)
+            | FsWatchEvent::Snapshot(ref p, _) => p == path,
+            FsWatchEvent::Renamed(ref from, ref to) => from == path || to == path,
+            FsWatchEvent::Overflow => false,
+        }
+    }
+
+    fn path(&self) -> Option<&Path> {
+        match *self {
+            FsWatchEvent::Created(ref p)
+            | FsWatchEvent::Modified(ref p)
+            | FsWatchEvent::Removed(ref p)
+            | FsWatchEvent::Snapshot(ref p, _) => Some(p),
+            FsWatchEvent::Renamed(_, ref to) => Some(to),
+            FsWatchEvent::Overflow => None,
+        }
+    }
+}
+
+#[cfg(feature = "notify")]
+pub(crate) fn watch(pool: &FsPool, path: PathBuf, opts: WatchOptions) -> FsWatchStream {
+    let shared = Arc::new(Mutex::new(Queue {
+        events: VecDeque::new(),
+        capacity: opts.capacity,
+        coalesce: opts.coalesce,
+        overflowed: false,
+        task: None,
+    }));
+
+    let queue = shared.clone();
+    let mode = if opts.recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    let starting = pool.exec(move || {
+        let mut translator = Translator::default();
+        let mut watcher = notify::recommended_watcher(move |res| {
+            let mut queue = queue.lock().unwrap();
+            match res {
+                Ok(event) => translator.translate(event, &mut queue),
+                Err(e) => queue.push(Err(notify_error(e))),
+            }
+        })
+        .map_err(notify_error)?;
+        watcher
+            .watch(&path, mode)
+            .map_err(|e| ::path_error(&path, notify_error(e)))?;
+        Ok(watcher)
+    });
+
+    FsWatchStream {
+        inner: Inner::Notify(NotifyWatch {
+            shared,
+            state: State::Starting(starting),
+        }),
+    }
+}
+
+pub(crate) fn poll_watch(pool: &FsPool, path: PathBuf, interval: Duration) -> FsWatchStream {
+    let mut watch = PollWatch {
+        pool: pool.clone(),
+        path: Arc::new(path),
+        interval,
+        timer: pool.timer(),
+        last: None,
+        state: PollState::Swapping,
+    };
+    watch.stat();
+    FsWatchStream {
+        inner: Inner::Poll(watch),
+    }
+}
+
+/// A `Stream` of the changes to a watched path.
+///
+/// Created by `FsPool::watch` and `FsPool::poll_watch`. Errors while
+/// watching don't end the stream. The watching stops when the stream is
+/// dropped.
+pub struct FsWatchStream {
+    inner: Inner,
+}
+
+enum Inner {
+    #[cfg(feature = "notify")]
+    Notify(NotifyWatch),
+    Poll(PollWatch),
+}
+
+#[cfg(feature = "notify")]
+struct NotifyWatch {
+    shared: Arc<Mutex<Queue>>,
+    state: State,
+}
+
+#[cfg(feature = "notify")]
+enum State {
+    Starting(FsFuture<RecommendedWatcher>),
+    // dropping the watcher stops it
+    #[allow(dead_code)]
+    Watching(RecommendedWatcher),
+    Failed,
+    Swapping,
+}
+
+/// The events waiting to be yielded.
+#[cfg(feature = "notify")]
+struct Queue {
+    events: VecDeque<io::Result<FsWatchEvent>>,
+    capacity: usize,
+    coalesce: bool,
+    overflowed: bool,
+    task: Option<Task>,
+}
+
+#[cfg(feature = "notify")]
+impl Queue {
+    fn push(&mut self, event: io::Result<FsWatchEvent>) {
+        if self.overflowed {
+            return;
+        }
+        if self.events.len() >= self.capacity {
+            self.overflow();
+        } else if !(self.coalesce && self.is_duplicate(&event)) {
+            self.events.push_back(event);
+            self.notify();
+        }
+    }
+
+    /// Drops any more events, until the queued ones are yielded.
+    fn overflow(&mut self) {
+        self.overflowed = true;
+        self.notify();
+    }
+
+    fn notify(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.notify();
+        }
+    }
+
+    /// Whether the latest queued event about the same path is this one.
+    fn is_duplicate(&self, event: &io::Result<FsWatchEvent>) -> bool {
+        let event = match *event {
+            Ok(ref event) => event,
+            Err(_) => return false,
+        };
+        let path = match event.path() {
+            Some(path) => path,
+            None => return false,
+        };
+        self.events
+            .iter()
+            .rev()
+            .filter_map(|queued| queued.as_ref().ok())
+            .find(|queued| queued.concerns(path))
+            .is_some_and(|queued| queued == event)
+    }
+
+    fn pop(&mut self) -> Option<io::Result<FsWatchEvent>> {
+        match self.events.pop_front() {
+            Some(event) => Some(event),
+            None if self.overflowed => {
+                self.overflowed = false;
+                Some(Ok(FsWatchEvent::Overflow))
+            }
+            None => None,
+        }
+    }
+}
+
+impl Stream for FsWatchStream {
+    type Item = FsWatchEvent;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        match self.inner {
+            #[cfg(feature = "notify")]
+            Inner::Notify(ref mut watch) => watch.poll(),
+            Inner::Poll(ref mut watch) => watch.poll(),
+        }
+    }
+}
+
+#[cfg(feature = "notify")]
+impl NotifyWatch {
+    fn poll(&mut self) -> Poll<Option<FsWatchEvent>, io::Error> {
+        match mem::replace(&mut self.state, State::Swapping) {
+            State::Starting(mut starting) => match starting.poll() {
+                Ok(Async::Ready(watcher)) => self.state = State::Watching(watcher),
+                Ok(Async::NotReady) => {
+                    self.state = State::Starting(starting);
+                    return Ok(Async::NotReady);
+                }
+                Err(e) => {
+                    self.state = State::Failed;
+                    return Err(e);
+                }
+            },
+            State::Failed => {
+                self.state = State::Failed;
+                return Ok(Async::Ready(None));
+            }
+            state => self.state = state,
+        }
+
+        let mut queue = self.shared.lock().unwrap();
+        match queue.pop() {
+            Some(event) => event.map(|event| Async::Ready(Some(event))),
+            None => {
+                queue.task = Some(task::current());
+                Ok(Async::NotReady)
+            }
+        }
+    }
+}
+
+impl fmt::Debug for FsWatchStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsWatchStream").finish()
+    }
+}
+
+/// Stats a path on the pool every so often.
+struct PollWatch {
+    pool: FsPool,
+    path: Arc<PathBuf>,
+    interval: Duration,
+    /// The timer of the pool, to wait with in between stats.
+    timer: Timer,
+    /// What the path looked like the last time, if it was stat'ed yet.
+    last: Option<Option<Stamp>>,
+    state: PollState,
+}
+
+/// The parts of the metadata of a path that are compared, or `None` if
+/// the path doesn't exist.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+enum PollState {
+    Stat(FsFuture<Option<Stamp>>),
+    Waiting(Expiry),
+    Swapping,
+}
+
+impl PollWatch {
+    fn stat(&mut self) {
+        let path = self.path.clone();
+        self.state = PollState::Stat(self.pool.exec(move || match fs::metadata(&*path) {
+            Ok(metadata) => Ok(Some(Stamp {
+                modified: metadata.modified().ok(),
+                len: metadata.len(),
+            })),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(::path_error(&path, e)),
+        }));
+    }
+
+    fn poll(&mut self) -> Poll<Option<FsWatchEvent>, io::Error> {
+        loop {
+            match mem::replace(&mut self.state, PollState::Swapping) {
+                PollState::Stat(mut stat) => {
+                    let polled = stat.poll();
+                    if let Ok(Async::NotReady) = polled {
+                        self.state = PollState::Stat(stat);
+                        return Ok(Async::NotReady);
+                    }
+                    self.state = PollState::Waiting(self.timer.delay(self.interval));
+                    let stamp = match polled {
+                        Ok(Async::Ready(stamp)) => stamp,
+                        Err(e) => return Err(e),
+                        Ok(Async::NotReady) => unreachable!(),
+                    };
+                    let path = (*self.path).clone();
+                    let event = match self.last.replace(stamp) {
+                        None => Some(FsWatchEvent::Snapshot(path, stamp.is_some())),
+                        Some(last) if last == stamp => None,
+                        Some(None) => Some(FsWatchEvent::Created(path)),
+                        Some(Some(_)) if stamp.is_none() => Some(FsWatchEvent::Removed(path)),
+                        Some(Some(_)) => Some(FsWatchEvent::Modified(path)),
+                    };
+                    if let Some(event) = event {
+                        return Ok(Async::Ready(Some(event)));
+                    }
+                }
+                PollState::Waiting(mut delay) => {
+                    if let Ok(Async::NotReady) = delay.poll() {
+                        self.state = PollState::Waiting(delay);
+                        return Ok(Async::NotReady);
+                    }
+                    self.stat();
+                }
+                PollState::Swapping => unreachable!(),
+            }
+        }
+    }
+}
+
+/// Turns the events of `notify` into `FsWatchEvent`s.
+#[cfg(feature = "notify")]
+#[derive(Default)]
+struct Translator {
+    /// The path a file was moved away from, waiting for where it was moved
+    /// to.
+    moved_from: Option<(usize, PathBuf)>,
+    /// The tracker of the last rename yielded from a pair of events.
+    paired: Option<usize>,
+}
+
+#[cfg(feature = "notify")]
+impl Translator {
+    fn translate(&mut self, event: notify::Event, queue: &mut Queue) {
+        if event.need_rescan() {
+            self.flush(queue);
+            queue.overflow();
+            return;
+        }
+
+        let tracker = event.tracker();
+        let mut paths = event.paths.into_iter();
+        match event.kind {
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                self.flush(queue);
+                if let Some(path) = paths.next() {
+                    match tracker {
+                        Some(tracker) => self.moved_from = Some((tracker, path)),
+                        None => queue.push(Ok(FsWatchEvent::Removed(path))),
+                    }
+                }
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                let path = match paths.next() {
+                    Some(path) => path,
+                    None => return,
+                };
+                match self.moved_from.take() {
+                    Some((from_tracker, from)) if tracker == Some(from_tracker) => {
+                        self.paired = tracker;
+                        queue.push(Ok(FsWatchEvent::Renamed(from, path)));
+                    }
+                    moved_from => {
+                        self.moved_from = moved_from;
+                        self.flush(queue);
+                        queue.push(Ok(FsWatchEvent::Created(path)));
+                    }
+                }
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                self.flush(queue);
+                // otherwise, it was already yielded from a `From` and a `To`
+                if tracker.is_none() || tracker != self.paired.take() {
+                    if let (Some(from), Some(to)) = (paths.next(), paths.next()) {
+                        queue.push(Ok(FsWatchEvent::Renamed(from, to)));
+                    }
+                }
+            }
+            EventKind::Modify(ModifyKind::Name(_)) => {
+                // which side of the rename isn't known
+                self.flush(queue);
+                for path in paths {
+                    if path.exists() {
+                        queue.push(Ok(FsWatchEvent::Created(path)));
+                    } else {
+                        queue.push(Ok(FsWatchEvent::Removed(path)));
+                    }
+                }
+            }
+            EventKind::Create(_) => {
+                self.flush(queue);
+                for path in paths {
+                    queue.push(Ok(FsWatchEvent::Created(path)));
+                }
+            }
+            EventKind::Modify(_) => {
+                self.flush(queue);
+                for path in paths {
+                    queue.push(Ok(FsWatchEvent::Modified(path)));
+                }
+            }
+            EventKind::Remove(_) => {
+                self.flush(queue);
+                for path in paths {
+                    queue.push(Ok(FsWatchEvent::Removed(path)));
+                }
+            }
+            EventKind::Access(_) | EventKind::Any | EventKind::Other => (),
+        }
+    }
+
+    /// A file moved away without being moved to a watched path was removed.
+    fn flush(&mut self, queue: &mut Queue) {
+        if let Some((_, path)) = self.moved_from.take() {
+            queue.push(Ok(FsWatchEvent::Removed(path)));
+        }
+    }
+}
+
+#[cfg(feature = "notify")]
+fn notify_error(e: notify::Error) -> io::Error {
+    match e.kind {
+        notify::ErrorKind::Io(e) => e,
+        notify::ErrorKind::PathNotFound => {
+            io::Error::new(io::ErrorKind::NotFound, "path not found")
+        }
+        _ => io::Error::other(e),
+    }
+}
@@ -1,47 +1,491 @@
-use std::fmt;
-use std::fs::{File, OpenOptions};
-use std::io::{self, Write};
-use std::path::Path;
+use std::any::Any;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, IoSlice, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use std::{cmp, fmt, mem, process};
 
-use bytes::Bytes;
-use futures::future::lazy;
-use futures::sync::oneshot;
-use futures::{Async, AsyncSink, Future, Poll, Sink, StartSend};
+use bytes::{Bytes, BytesMut};
+use futures::future::{lazy, ok, Either};
+use futures::sync::{mpsc, oneshot};
+use futures::{Async, AsyncSink, Future, Poll, Sink, StartSend, Stream};
 
+use error::{Context, FsError};
+#[cfg(feature = "hash")]
+use hash::{HashAlgorithm, Hasher};
+use metrics::{self, Running};
+use rate::RateLimit;
+use sys;
+use timeout::{Timeout, Timer};
+use FsCancelHandle;
 use FsFuture;
+use FsOperation;
 use FsPool;
+use OpPriority;
+
+const MAX_IN_FLIGHT: usize = 16;
+const MAX_QUEUED: usize = 64;
+const MAX_QUEUED_BYTES: u64 = 8 * 1024 * 1024;
+const DIRECT_BUF_SIZE: usize = 256 * 1024;
 
 pub(crate) fn new<P>(pool: &FsPool, path: P, opts: WriteOptions) -> FsWriteSink
 where
     P: AsRef<Path> + Send + 'static,
 {
-    let (tx, rx) = oneshot::channel();
+    let atomic = if opts.atomic {
+        Some(Atomic::new(path.as_ref()))
+    } else {
+        None
+    };
 
-    let fut = Box::new(lazy(move || {
-        let res = opts.open.open(path).map_err(From::from);
+    let config = config(&opts);
+    let context = context(path.as_ref(), &opts);
+    let target = match atomic {
+        Some(ref atomic) => atomic.tmp.clone(),
+        None => path.as_ref().to_owned(),
+    };
 
-        tx.send(res).map_err(|_| ())
-    }));
+    let open = Box::new(move || open(&target, &opts));
+    spawn(pool, atomic, config, context, open)
+}
 
-    pool.executor.execute(fut).unwrap();
+pub(crate) fn new_from_file(pool: &FsPool, file: File) -> FsWriteSink {
+    let config = config(&WriteOptions::default());
+    spawn(pool, None, config, None, Box::new(move || Ok(file)))
+}
 
-    FsWriteSink {
-        pool: pool.clone(),
-        state: State::Working(super::fs(rx)),
+pub(crate) fn new_from_file_append(pool: &FsPool, mut file: File) -> FsWriteSink {
+    let open = Box::new(move || {
+        file.seek(SeekFrom::End(0))?;
+        Ok(file)
+    });
+    spawn(pool, None, config(&WriteOptions::default()), None, open)
+}
+
+/// The options that still apply once the file has been opened.
+struct Config {
+    sync_on_close: bool,
+    buffer_size: Option<usize>,
+    max_queued_bytes: u64,
+    direct: bool,
+    truncate_on_close: bool,
+    timeout: Option<(Duration, Timer)>,
+    priority: Option<OpPriority>,
+    max_bytes_per_sec: Option<u64>,
+    #[cfg(feature = "hash")]
+    checksum: Option<HashAlgorithm>,
+}
+
+fn config(opts: &WriteOptions) -> Config {
+    Config {
+        sync_on_close: opts.sync_on_close,
+        buffer_size: opts.buffer_size,
+        max_queued_bytes: opts.max_queued_bytes,
+        direct: opts.direct && sys::HAS_DIRECT,
+        truncate_on_close: opts.truncate_on_close,
+        timeout: opts.timeout.clone(),
+        priority: opts.priority,
+        max_bytes_per_sec: opts.max_bytes_per_sec,
+        #[cfg(feature = "hash")]
+        checksum: opts.checksum,
     }
 }
 
-pub(crate) fn new_from_file(pool: &FsPool, file: File) -> FsWriteSink {
+/// The context to wrap errors in, which names the target rather than any
+/// temporary file.
+fn context(path: &Path, opts: &WriteOptions) -> Option<Context> {
+    if opts.contextual_errors {
+        Some(Context::new(FsOperation::Write, Arc::new(path.to_owned())))
+    } else {
+        None
+    }
+}
+
+fn spawn(
+    pool: &FsPool,
+    atomic: Option<Atomic>,
+    config: Config,
+    context: Option<Context>,
+    open: Open,
+) -> FsWriteSink {
+    let pool = &pool.prioritized(config.priority);
+    let (op_tx, op_rx) = mpsc::channel(MAX_QUEUED);
+    let (ack_tx, ack_rx) = mpsc::unbounded();
+    let cancel = FsCancelHandle::new();
+    let mut running = metrics::start(&pool.metrics, Some(FsOperation::Write));
+    running.set_failed(false);
+
+    let writer = Writer {
+        open: Some(open),
+        file: None,
+        atomic,
+        sync_on_close: config.sync_on_close,
+        want_direct: config.direct,
+        direct: None,
+        truncate_on_close: config.truncate_on_close,
+        written: 0,
+        error: None,
+        queue: Vec::new(),
+        ops: op_rx,
+        acks: ack_tx.clone(),
+        cancel: cancel.clone(),
+        running,
+        rate: pool.rate_limit(config.max_bytes_per_sec),
+        #[cfg(feature = "hash")]
+        hasher: config.checksum.map(Hasher::new),
+    };
+
+    // the writer can't report that it never ran itself
+    let failed = ack_tx.clone();
+    if let Err(e) = pool.execute(Box::new(writer)) {
+        let _ = failed.unbounded_send(Err(e));
+    }
+
     FsWriteSink {
         pool: pool.clone(),
-        state: State::Ready(file),
+        context,
+        cancel,
+        ops: op_tx,
+        acks: ack_rx,
+        buffer_size: config.buffer_size,
+        max_queued_bytes: config.max_queued_bytes,
+        timeout: config
+            .timeout
+            .map(|(duration, timer)| Timeout::new(duration, &timer)),
+        buf: BytesMut::new(),
+        opened: false,
+        sent: 0,
+        written: 0,
+        closing: None,
+        closed: false,
+        checksum: None,
+        item: PhantomData,
     }
 }
 
 /// A `Sink` to send bytes to be written to a target file.
-pub struct FsWriteSink {
+///
+/// The file is written by a single task on the pool, which writes every send
+/// that is queued up at once with a vectored write where possible. Dropping
+/// the sink before closing it stops the task before its next write.
+///
+/// The sink takes `Bytes` by default, but can take any buffer of bytes
+/// instead, with `with_item_type`.
+pub struct FsWriteSink<B = Bytes> {
     pool: FsPool,
-    state: State,
+    context: Option<Context>,
+    cancel: FsCancelHandle,
+    ops: mpsc::Sender<Op>,
+    acks: mpsc::UnboundedReceiver<io::Result<u64>>,
+    buffer_size: Option<usize>,
+    max_queued_bytes: u64,
+    timeout: Option<Timeout>,
+    buf: BytesMut,
+    /// Whether the result of opening the file has been received.
+    opened: bool,
+    sent: u64,
+    written: u64,
+    closing: Option<FsFuture<Option<Vec<u8>>>>,
+    closed: bool,
+    checksum: Option<Vec<u8>>,
+    item: PhantomData<fn(B)>,
+}
+
+type Open = Box<dyn FnOnce() -> io::Result<File> + Send>;
+
+/// The bytes of a send, as the writer takes them.
+enum Chunk {
+    Bytes(Bytes),
+    Other(Box<dyn AsRef<[u8]> + Send>),
+}
+
+impl Chunk {
+    fn new<B>(item: B) -> Chunk
+    where
+        B: AsRef<[u8]> + Send + 'static,
+    {
+        // a sink of `Bytes` shouldn't box every send
+        let mut item = Some(item);
+        if let Some(bytes) = (&mut item as &mut dyn Any).downcast_mut::<Option<Bytes>>() {
+            return Chunk::Bytes(bytes.take().unwrap());
+        }
+        Chunk::Other(Box::new(item.unwrap()))
+    }
+}
+
+impl AsRef<[u8]> for Chunk {
+    fn as_ref(&self) -> &[u8] {
+        match *self {
+            Chunk::Bytes(ref bytes) => bytes,
+            Chunk::Other(ref other) => (**other).as_ref(),
+        }
+    }
+}
+
+enum Op {
+    Write(Chunk),
+    /// Resolves with the checksum, if one is computed.
+    Close(oneshot::Sender<io::Result<Option<Vec<u8>>>>),
+    IntoFile(oneshot::Sender<io::Result<File>>),
+}
+
+/// The task on the pool that writes the file of an `FsWriteSink`.
+///
+/// Every write is acknowledged with the total number of bytes written so
+/// far, or the error it failed with. Once a write has failed, every later
+/// operation fails with the same error.
+struct Writer {
+    open: Option<Open>,
+    file: Option<File>,
+    atomic: Option<Atomic>,
+    sync_on_close: bool,
+    want_direct: bool,
+    /// The aligned buffer that bytes are collected in for direct IO.
+    direct: Option<BytesMut>,
+    truncate_on_close: bool,
+    written: u64,
+    error: Option<(io::ErrorKind, String)>,
+    queue: Vec<Chunk>,
+    ops: mpsc::Receiver<Op>,
+    acks: mpsc::UnboundedSender<io::Result<u64>>,
+    cancel: FsCancelHandle,
+    running: Running,
+    rate: Option<Arc<RateLimit>>,
+    /// The hash of the bytes written so far, for `WriteOptions::checksum`.
+    #[cfg(feature = "hash")]
+    hasher: Option<Hasher>,
+}
+
+impl Future for Writer {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        self.cancel.register();
+        if self.cancel.is_canceled() {
+            self.running.set_failed(true);
+            return Ok(Async::Ready(()));
+        }
+        if let Err(e) = super::refused() {
+            self.fail(&e);
+            // a failure to open the file is reported right away
+            if self.open.take().is_some() {
+                let _ = self.acks.unbounded_send(Err(e));
+            }
+        }
+        if let Some(open) = self.open.take() {
+            // the sink waits for this, to report a failure to open the file
+            // even before anything was sent
+            let ack = match open().and_then(|file| self.start_direct(file)) {
+                Ok(file) => {
+                    self.file = Some(file);
+                    Ok(0)
+                }
+                Err(e) => {
+                    self.fail(&e);
+                    Err(e)
+                }
+            };
+            let _ = self.acks.unbounded_send(ack);
+        }
+
+        loop {
+            let mut op = None;
+            let mut dropped = false;
+            while self.queue.len() < MAX_QUEUED {
+                match self.ops.poll()? {
+                    Async::Ready(Some(Op::Write(bytes))) => self.queue.push(bytes),
+                    Async::Ready(Some(other)) => {
+                        op = Some(other);
+                        break;
+                    }
+                    Async::Ready(None) => {
+                        dropped = true;
+                        break;
+                    }
+                    Async::NotReady => break,
+                }
+            }
+
+            if self.cancel.is_canceled() {
+                self.running.set_failed(true);
+                return Ok(Async::Ready(()));
+            }
+            if !self.queue.is_empty() {
+                let queue = mem::take(&mut self.queue);
+                let ack = self.write(queue);
+                let _ = self.acks.unbounded_send(ack);
+            } else if op.is_none() && !dropped {
+                return Ok(Async::NotReady);
+            }
+
+            match op {
+                Some(Op::Close(tx)) => {
+                    let res = self.close().map(|_| self.checksum());
+                    let _ = tx.send(res);
+                    return Ok(Async::Ready(()));
+                }
+                Some(Op::IntoFile(tx)) => {
+                    let _ = tx.send(self.close());
+                    return Ok(Async::Ready(()));
+                }
+                Some(Op::Write(_)) => unreachable!(),
+                None if dropped => return Ok(Async::Ready(())),
+                None => (),
+            }
+        }
+    }
+}
+
+impl Writer {
+    fn start_direct(&mut self, file: File) -> io::Result<File> {
+        if !self.want_direct {
+            return Ok(file);
+        }
+        let mut file = file;
+        let pos = if sys::is_append(&file)? {
+            file.metadata()?.len()
+        } else {
+            file.stream_position()?
+        };
+        // writing from an unaligned position goes through the page cache
+        if pos % sys::DIRECT_ALIGN as u64 == 0 {
+            sys::set_direct(&file, true)?;
+            self.direct = Some(sys::aligned(DIRECT_BUF_SIZE));
+        }
+        Ok(file)
+    }
+
+    fn write(&mut self, queue: Vec<Chunk>) -> io::Result<u64> {
+        self.check()?;
+        let len = queue
+            .iter()
+            .map(|chunk| chunk.as_ref().len() as u64)
+            .sum::<u64>();
+        if let Some(ref rate) = self.rate {
+            rate.take(len);
+        }
+        #[cfg(feature = "hash")]
+        {
+            if let Some(ref mut hasher) = self.hasher {
+                for chunk in &queue {
+                    hasher.update(chunk.as_ref());
+                }
+            }
+        }
+        let file = self.file.as_mut().unwrap();
+        let res = match self.direct {
+            Some(ref mut buf) => write_direct(file, buf, queue),
+            None => write_all_vectored(file, queue),
+        };
+        match res {
+            Ok(()) => {
+                self.written += len;
+                self.running.metrics().written(len);
+                Ok(self.written)
+            }
+            Err(e) => {
+                self.fail(&e);
+                Err(e)
+            }
+        }
+    }
+
+    /// Finishes writing, and returns the file.
+    fn close(&mut self) -> io::Result<File> {
+        let res = self.finish();
+        self.running.set_failed(res.is_err());
+        res
+    }
+
+    fn finish(&mut self) -> io::Result<File> {
+        self.check()?;
+        let mut file = self.file.take().unwrap();
+        if let Some(buf) = self.direct.take() {
+            // the final partial block can't be written directly
+            sys::set_direct(&file, false)?;
+            file.write_all(&buf)?;
+        }
+        if self.truncate_on_close {
+            truncate(&mut file)?;
+        }
+        if let Some(ref atomic) = self.atomic {
+            atomic.commit(&file)?;
+        } else if self.sync_on_close {
+            file.sync_all()?;
+        }
+        self.atomic = None;
+        Ok(file)
+    }
+
+    fn check(&self) -> io::Result<()> {
+        match self.error {
+            Some((kind, ref msg)) => Err(io::Error::new(kind, msg.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// The digest of everything written, once the file is finished.
+    #[cfg(feature = "hash")]
+    fn checksum(&mut self) -> Option<Vec<u8>> {
+        self.hasher.take().map(Hasher::finish)
+    }
+
+    #[cfg(not(feature = "hash"))]
+    fn checksum(&mut self) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn fail(&mut self, e: &io::Error) {
+        self.error = Some((e.kind(), e.to_string()));
+        self.running.set_failed(true);
+    }
+}
+
+impl Drop for Writer {
+    fn drop(&mut self) {
+        if let Some(atomic) = self.atomic.take() {
+            // the temporary file was never renamed into place
+            self.file = None;
+            let _ = fs::remove_file(atomic.tmp);
+        }
+    }
+}
+
+/// The temporary file of an atomic write, to be renamed over `path` once
+/// the sink is closed.
+#[derive(Clone)]
+struct Atomic {
+    tmp: PathBuf,
+    path: PathBuf,
+}
+
+impl Atomic {
+    fn new(path: &Path) -> Atomic {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let mut name = OsString::from(".");
+        name.push(path.file_name().unwrap_or_else(|| OsStr::new("futures-fs")));
+        name.push(format!(
+            ".{}.{}.tmp",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        Atomic {
+            tmp: path.with_file_name(name),
+            path: path.to_owned(),
+        }
+    }
+
+    fn commit(&self, file: &File) -> io::Result<()> {
+        file.sync_all()?;
+        fs::rename(&self.tmp, &self.path)
+    }
 }
 
 /// Options for how to write to the target file.
@@ -52,84 +496,1222 @@ pub struct FsWriteSink {
 #[derive(Debug)]
 pub struct WriteOptions {
     open: OpenOptions,
+    create_parents: bool,
+    atomic: bool,
+    sync_on_close: bool,
+    buffer_size: Option<usize>,
+    max_queued_bytes: u64,
+    direct: bool,
+    preallocate: Option<u64>,
+    truncate_on_close: bool,
+    contextual_errors: bool,
+    timeout: Option<(Duration, Timer)>,
+    priority: Option<OpPriority>,
+    pub(crate) max_bytes_per_sec: Option<u64>,
+    no_follow: bool,
+    regular_only: bool,
+    #[cfg(feature = "hash")]
+    checksum: Option<HashAlgorithm>,
+}
+
+impl WriteOptions {
+    /// The `OpenOptions` to use when opening the file.
+    ///
+    /// The file is opened exactly as configured by `open`, so the default of
+    /// creating the file no longer applies unless it is set in `open`. This
+    /// replaces any previously set `append` or `create_new`, while calling
+    /// those afterwards modifies `open`.
+    pub fn with_open_options(mut self, open: OpenOptions) -> Self {
+        self.open = open;
+        self
+    }
+
+    /// Whether to append to the end of the file, instead of writing from its
+    /// start.
+    ///
+    /// Appending can't be combined with truncating the file, and opening the
+    /// file will return an error if both were requested.
+    ///
+    /// Default is `false`.
+    pub fn append(mut self, append: bool) -> Self {
+        self.open.append(append);
+        self
+    }
+
+    /// Whether to only create a new file, failing if the file already exists.
+    ///
+    /// The `AlreadyExists` error is returned from the sink once the file has
+    /// been attempted to be opened.
+    ///
+    /// Default is `false`.
+    pub fn create_new(mut self, create_new: bool) -> Self {
+        self.open.create_new(create_new);
+        self
+    }
+
+    /// The permission bits to create the file with, still subject to the
+    /// process umask.
+    ///
+    /// This only has an effect on Unix.
+    pub fn mode(mut self, mode: u32) -> Self {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            self.open.mode(mode);
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
+        self
+    }
+
+    /// Whether to fail with an error of kind `InvalidInput`, instead of
+    /// following a symbolic link as the last component of the path.
+    ///
+    /// This opens with `O_NOFOLLOW` on Unix, and opens the reparse point
+    /// itself on Windows. Symbolic links in the directories leading up to
+    /// the file are still followed. An `atomic` write replaces a symbolic
+    /// link at the path, rather than writing through it, either way.
+    ///
+    /// Default is `false`.
+    pub fn no_follow(mut self, no_follow: bool) -> Self {
+        self.no_follow = no_follow;
+        self
+    }
+
+    /// Whether to fail with an error of kind `InvalidInput`, unless the
+    /// opened file is a regular file, rejecting FIFOs and devices.
+    ///
+    /// The check is made on the file once opened, so it can't be raced by
+    /// replacing the path. A FIFO is opened without waiting for its other
+    /// end.
+    ///
+    /// Default is `false`.
+    pub fn regular_only(mut self, regular_only: bool) -> Self {
+        self.regular_only = regular_only;
+        self
+    }
+
+    /// Whether to create any missing parent directories before opening the
+    /// file.
+    ///
+    /// Default is `false`.
+    pub fn create_parents(mut self, create_parents: bool) -> Self {
+        self.create_parents = create_parents;
+        self
+    }
+
+    /// Whether to write the file atomically.
+    ///
+    /// The bytes are written to a new temporary file in the same directory,
+    /// which is synced and renamed over the target file once the sink is
+    /// closed. If the sink is dropped before being closed, the temporary file
+    /// is removed and the target file is left untouched.
+    ///
+    /// Since the target file is replaced as a whole, options such as `append`
+    /// only apply to the temporary file.
+    ///
+    /// Default is `false`.
+    pub fn atomic(mut self, atomic: bool) -> Self {
+        self.atomic = atomic;
+        self
+    }
+
+    /// Whether to sync all data and metadata to disk when the sink is closed.
+    ///
+    /// Closing the sink only succeeds once the sync has succeeded.
+    ///
+    /// Default is `false`.
+    pub fn sync_on_close(mut self, sync_on_close: bool) -> Self {
+        self.sync_on_close = sync_on_close;
+        self
+    }
+
+    /// The number of bytes to collect from sends, before writing them to the
+    /// file together.
+    ///
+    /// Bytes that are still buffered are written once the sink is flushed or
+    /// closed, and any error writing them is returned from there. A send
+    /// larger than the buffer is written without being copied. This has no
+    /// effect on `FsPool::write_at`.
+    ///
+    /// Default is to write every send as it is.
+    ///
+    /// # Panic
+    ///
+    /// The passed argument must be larger than 0.
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        assert!(buffer_size > 0, "buffer size must be larger than 0");
+        self.buffer_size = Some(buffer_size);
+        self
+    }
+
+    /// The maximum number of bytes that the sink accepts before they have
+    /// been written.
+    ///
+    /// Once reached, the sink isn't ready for more sends until the pool has
+    /// caught up. A single send larger than this is still accepted, once all
+    /// earlier sends have been written. This has no effect on
+    /// `FsPool::write_at`.
+    ///
+    /// Default is 8 MiB.
+    pub fn max_queued_bytes(mut self, max_queued_bytes: u64) -> Self {
+        self.max_queued_bytes = max_queued_bytes;
+        self
+    }
+
+    /// Whether to bypass the page cache of the OS, by writing with
+    /// `O_DIRECT`.
+    ///
+    /// Sends are then collected in a buffer aligned to 4096 bytes, and
+    /// written in whole blocks of that size. A final partial block is only
+    /// written once the sink is closed, through the page cache. When the file
+    /// isn't written from an aligned position, like when appending to a file
+    /// of an unaligned length, the page cache is used throughout. If the
+    /// filesystem doesn't support direct IO, the sink fails with an error of
+    /// kind `InvalidInput`.
+    ///
+    /// This only has an effect on Linux, and is ignored elsewhere. It has no
+    /// effect on `FsPool::write_at`.
+    ///
+    /// Default is `false`.
+    pub fn direct(mut self, direct: bool) -> Self {
+        self.direct = direct;
+        self
+    }
+
+    /// Reserves space for the file to be at least `len` bytes long, once it
+    /// has been opened and before anything is written to it.
+    ///
+    /// This uses `fallocate` on Linux, so running out of space is an error
+    /// right away, and extends the file with `File::set_len` elsewhere. Either
+    /// way, the file is at least `len` bytes long afterwards, even if fewer
+    /// bytes are written, unless `truncate_on_close` is set. If reserving the
+    /// space fails, the error is returned from the first flush of the sink.
+    ///
+    /// Default is to not reserve any space.
+    pub fn preallocate(mut self, len: u64) -> Self {
+        self.preallocate = Some(len);
+        self
+    }
+
+    /// Whether to truncate the file to the end of the written bytes once the
+    /// sink is closed.
+    ///
+    /// This removes any rest of the file that wasn't overwritten, like space
+    /// reserved by `preallocate`. This has no effect on `FsPool::write_at`,
+    /// see `FsPositionedWriteSink::set_len_on_close` instead.
+    ///
+    /// Default is `false`.
+    pub fn truncate_on_close(mut self, truncate_on_close: bool) -> Self {
+        self.truncate_on_close = truncate_on_close;
+        self
+    }
+
+    /// Whether the sink fails with errors that wrap an `FsError`, which
+    /// records the path of the file along with the original error.
+    ///
+    /// The errors keep the kind of the original error, and name the target
+    /// path even when writing `atomic`ally to a temporary file first.
+    ///
+    /// Default is `false`.
+    pub fn contextual_errors(mut self, contextual_errors: bool) -> Self {
+        self.contextual_errors = contextual_errors;
+        self
+    }
+
+    /// How long the sink waits for the file to be opened, for a write to
+    /// finish, or for the file to be closed, before failing with an error of
+    /// kind `TimedOut`.
+    ///
+    /// Once timed out, writing is canceled like with
+    /// `FsWriteSink::cancel_handle`, though a write that is stuck on the
+    /// pool can't be interrupted. This has no effect on `FsPool::write_at`.
+    ///
+    /// Like with `ReadOptions::chunk_timeout`, `timer` is called to get a
+    /// future that resolves after the supplied duration.
+    ///
+    /// Default is to wait forever.
+    pub fn timeout<F, T>(mut self, timeout: Duration, timer: F) -> Self
+    where
+        F: Fn(Duration) -> T + Send + Sync + 'static,
+        T: Future<Item = ()> + Send + 'static,
+    {
+        self.timeout = Some((timeout, Timer::new(timer)));
+        self
+    }
+
+    /// The priority of writing the file, which is queued by it until it
+    /// starts. With `FsPool::write_at`, every write is queued by it.
+    ///
+    /// Default is the priority of the pool, from `FsPool::with_priority`.
+    pub fn priority(mut self, priority: OpPriority) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// The most bytes per second to write the file with.
+    ///
+    /// The sink gets a limit of its own, instead of sharing that of the
+    /// pool, from `FsPoolBuilder::max_bytes_per_sec`.
+    ///
+    /// Default is the limit of the pool.
+    ///
+    /// # Panic
+    ///
+    /// The passed argument must be larger than 0.
+    pub fn max_bytes_per_sec(mut self, max_bytes_per_sec: u64) -> Self {
+        assert!(max_bytes_per_sec > 0, "rate limit must be larger than 0");
+        self.max_bytes_per_sec = Some(max_bytes_per_sec);
+        self
+    }
+
+    /// Computes the digest of the bytes written with `algorithm`, on the
+    /// pool as they are written.
+    ///
+    /// The digest is available from `FsWriteSink::checksum` once the sink is
+    /// closed, or from `FsWriteSink::close_with_checksum`. This has no effect
+    /// on `FsPool::write_at` and `FsPool::write_all`. Requires the `hash`
+    /// feature.
+    ///
+    /// Default is to not compute one.
+    #[cfg(feature = "hash")]
+    pub fn checksum(mut self, algorithm: HashAlgorithm) -> Self {
+        self.checksum = Some(algorithm);
+        self
+    }
 }
 
 impl Default for WriteOptions {
     fn default() -> WriteOptions {
         let mut opts = OpenOptions::new();
         opts.write(true).create(true);
-        WriteOptions { open: opts }
+        WriteOptions {
+            open: opts,
+            create_parents: false,
+            atomic: false,
+            sync_on_close: false,
+            buffer_size: None,
+            max_queued_bytes: MAX_QUEUED_BYTES,
+            direct: false,
+            preallocate: None,
+            truncate_on_close: false,
+            contextual_errors: false,
+            timeout: None,
+            priority: None,
+            max_bytes_per_sec: None,
+            no_follow: false,
+            regular_only: false,
+            #[cfg(feature = "hash")]
+            checksum: None,
+        }
     }
 }
 
 impl From<OpenOptions> for WriteOptions {
     fn from(open: OpenOptions) -> WriteOptions {
-        WriteOptions { open }
+        WriteOptions {
+            open,
+            create_parents: false,
+            atomic: false,
+            sync_on_close: false,
+            buffer_size: None,
+            max_queued_bytes: MAX_QUEUED_BYTES,
+            direct: false,
+            preallocate: None,
+            truncate_on_close: false,
+            contextual_errors: false,
+            timeout: None,
+            priority: None,
+            max_bytes_per_sec: None,
+            no_follow: false,
+            regular_only: false,
+            #[cfg(feature = "hash")]
+            checksum: None,
+        }
     }
 }
 
-enum State {
-    Working(FsFuture<File>),
-    Ready(File),
+impl<B> FsWriteSink<B> {
+    /// Returns this sink, taking sends of another type of buffer.
+    ///
+    /// Any buffer of bytes can be sent, like a `Vec<u8>` or a `&'static
+    /// [u8]`, without copying it into `Bytes` first. The writer only looks
+    /// at its bytes, and drops it once they are written.
+    pub fn with_item_type<C>(mut self) -> FsWriteSink<C>
+    where
+        C: AsRef<[u8]> + Send + 'static,
+    {
+        let (_, acks) = mpsc::unbounded();
+        let sink = FsWriteSink {
+            pool: self.pool.clone(),
+            context: self.context.take(),
+            cancel: self.cancel.clone(),
+            ops: self.ops.clone(),
+            acks: mem::replace(&mut self.acks, acks),
+            buffer_size: self.buffer_size,
+            max_queued_bytes: self.max_queued_bytes,
+            timeout: self.timeout.take(),
+            buf: self.buf.take(),
+            opened: self.opened,
+            sent: self.sent,
+            written: self.written,
+            closing: self.closing.take(),
+            closed: self.closed,
+            checksum: self.checksum.take(),
+            item: PhantomData,
+        };
+        // the writer now belongs to the new sink
+        self.closed = true;
+        sink
+    }
+
+    /// The number of bytes written to the file so far.
+    ///
+    /// Only sends that have been fully written are counted, so that this is
+    /// final once the sink has been flushed or closed.
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// Flushes all outstanding writes, and resolves with the underlying
+    /// `File`.
+    ///
+    /// This finishes the sink like closing it would, including syncing or
+    /// renaming the file if configured so. If any write fails, the future
+    /// resolves with that error and the file is dropped.
+    pub fn close_into_file(mut self) -> FsFuture<File> {
+        let (tx, rx) = oneshot::channel();
+
+        // dropping this sink mustn't cancel the close
+        self.closed = true;
+        let ops = self.ops.clone();
+        let ops = if self.buf.is_empty() {
+            Either::A(ok(ops))
+        } else {
+            Either::B(ops.send(Op::Write(Chunk::Bytes(self.buf.take().freeze()))))
+        };
+        let fut = ops.and_then(|ops| ops.send(Op::IntoFile(tx))).then(|res| {
+            if let Err(e) = res {
+                if let Op::IntoFile(tx) = e.into_inner() {
+                    let _ = tx.send(Err(closed()));
+                }
+            }
+            Ok(())
+        });
+
+        if let Err(e) = self.pool.execute(Box::new(fut)) {
+            return super::failed(e).context(self.context.clone());
+        }
+
+        super::fs(rx).context(self.context.clone())
+    }
+
+    /// The digest of the bytes written, from `WriteOptions::checksum`, once
+    /// the sink has been closed.
+    pub fn checksum(&self) -> Option<&[u8]> {
+        self.checksum.as_ref().map(|checksum| &checksum[..])
+    }
+
+    /// Closes the sink, and resolves with the digest of the bytes written,
+    /// from `WriteOptions::checksum`.
+    ///
+    /// Fails with an error of kind `InvalidInput` if no checksum was
+    /// configured. Requires the `hash` feature.
+    #[cfg(feature = "hash")]
+    pub fn close_with_checksum(self) -> FsFuture<Vec<u8>> {
+        let (tx, rx) = oneshot::channel();
+
+        let pool = self.pool.clone();
+        let mut sink = self.with_item_type::<Bytes>();
+        let fut = ::futures::future::poll_fn(move || {
+            try_ready!(sink.close());
+            Ok(Async::Ready(sink.checksum.take()))
+        })
+        .then(|res| {
+            let res = res.and_then(|checksum| {
+                checksum.ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "sink has no checksum")
+                })
+            });
+            tx.send(res).map_err(|_| ())
+        });
+
+        if let Err(e) = pool.execute(Box::new(fut)) {
+            return super::failed(e);
+        }
+
+        super::fs(rx)
+    }
+
+    /// Returns a handle to cancel writing the file.
+    ///
+    /// Once canceled, the sink fails with an error, and the bytes that
+    /// haven't been written yet are discarded. With `WriteOptions::atomic`,
+    /// the temporary file is removed.
+    pub fn cancel_handle(&self) -> FsCancelHandle {
+        self.cancel.clone()
+    }
+
+    /// Returns a writer of the file, implementing `std::io::Write`, that
+    /// waits for this sink to take the bytes of each write.
+    ///
+    /// Writing blocks the calling thread, so it must not be used on a thread
+    /// that runs futures, like one of the pool.
+    pub fn into_blocking_write(self) -> ::FsBlockingWrite {
+        ::blocking::write(self.with_item_type())
+    }
+
+    /// Returns a writer of the file, implementing `AsyncWrite`, that sends
+    /// the bytes of each write to this sink.
+    ///
+    /// Requires the `tokio-io` feature.
+    #[cfg(feature = "tokio-io")]
+    pub fn into_async_write(self) -> ::FsAsyncWrite {
+        ::async_io::write(self.with_item_type())
+    }
+
+    /// Whether there is room to send to the writer.
+    fn poll_ready(&mut self) -> Poll<(), io::Error> {
+        match self.ops.poll_ready() {
+            Ok(async) => Ok(async),
+            Err(_) => Err(self.stopped()),
+        }
+    }
+
+    /// Sends `chunk` to the writer, once `poll_ready` found room for it.
+    fn send(&mut self, chunk: Chunk) -> io::Result<()> {
+        let len = chunk.as_ref().len() as u64;
+        match self.ops.start_send(Op::Write(chunk)) {
+            Ok(AsyncSink::Ready) => {
+                if self.opened && self.sent == self.written {
+                    // the sink was idle until now
+                    self.reset_timeout();
+                }
+                self.sent += len;
+                Ok(())
+            }
+            Ok(AsyncSink::NotReady(_)) => unreachable!(),
+            Err(_) => Err(self.stopped()),
+        }
+    }
+
+    /// The error the writer stopped with, if it sent one before going away.
+    fn stopped(&mut self) -> io::Error {
+        if self.cancel.is_canceled() {
+            return self.error(super::canceled());
+        }
+        while let Ok(Async::Ready(Some(ack))) = self.acks.poll() {
+            if let Err(e) = ack {
+                return self.error(e);
+            }
+        }
+        self.error(closed())
+    }
+
+    fn reset_timeout(&mut self) {
+        if let Some(ref mut timeout) = self.timeout {
+            timeout.reset();
+        }
+    }
+
+    /// Fails once the writer hasn't made progress within the timeout, and
+    /// cancels it.
+    fn poll_timeout(&mut self) -> io::Result<()> {
+        if self.timeout.as_mut().is_some_and(Timeout::poll_expired) {
+            self.cancel.cancel();
+            return Err(self.error(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "timed out writing the file",
+            )));
+        }
+        Ok(())
+    }
+
+    /// Wraps `e` in an `FsError`, with `WriteOptions::contextual_errors`.
+    fn error(&self, e: io::Error) -> io::Error {
+        match self.context {
+            Some(ref context) => context.wrap(e),
+            None => e,
+        }
+    }
+
+    /// The number of bytes accepted, but not written yet.
+    fn queued(&self) -> u64 {
+        self.sent - self.written + self.buf.len() as u64
+    }
+
+    /// Polls for acknowledgements of writes, until the file has been opened
+    /// and all sent bytes have been written.
+    fn poll_acks(&mut self) -> Poll<(), io::Error> {
+        while !self.opened || self.written < self.sent {
+            if self.cancel.is_canceled() {
+                return Err(self.error(super::canceled()));
+            }
+            let ack = match self.acks.poll().map_err(|()| self.error(closed()))? {
+                Async::Ready(ack) => ack,
+                Async::NotReady => {
+                    self.poll_timeout()?;
+                    return Ok(Async::NotReady);
+                }
+            };
+            self.reset_timeout();
+            self.opened = true;
+            match ack {
+                Some(Ok(written)) => self.written = written,
+                Some(Err(e)) => {
+                    // the bytes of the failed write won't be acknowledged
+                    self.sent = self.written;
+                    return Err(self.error(e));
+                }
+                None => return Err(self.error(closed())),
+            }
+        }
+        Ok(Async::Ready(()))
+    }
+
+    /// Fails with the error of an earlier write, if one has failed, without
+    /// waiting for the writes still in flight.
+    #[cfg(feature = "tokio-io")]
+    pub(crate) fn poll_failed(&mut self) -> io::Result<()> {
+        self.poll_acks().map(|_| ())
+    }
+
+    /// Sends the buffered bytes to be written, once there is room for them.
+    fn flush_buf(&mut self) -> Poll<(), io::Error> {
+        if self.buf.is_empty() {
+            return Ok(Async::Ready(()));
+        }
+        try_ready!(self.poll_ready());
+        let buf = self.buf.take().freeze();
+        self.send(Chunk::Bytes(buf))?;
+        Ok(Async::Ready(()))
+    }
+}
+
+impl<B> Sink for FsWriteSink<B>
+where
+    B: AsRef<[u8]> + Send + 'static,
+{
+    type SinkItem = B;
+    type SinkError = io::Error;
+
+    fn start_send(&mut self, item: Self::SinkItem) -> StartSend<Self::SinkItem, Self::SinkError> {
+        if self.closed || self.closing.is_some() {
+            return Err(self.error(closed()));
+        }
+
+        let len = item.as_ref().len();
+        if self.queued() > 0 && self.queued() + len as u64 > self.max_queued_bytes {
+            self.flush_buf()?;
+            self.poll_acks()?;
+            if self.queued() > 0 && self.queued() + len as u64 > self.max_queued_bytes {
+                return Ok(AsyncSink::NotReady(item));
+            }
+        }
+
+        if let Some(buffer_size) = self.buffer_size {
+            if self.buf.len() >= buffer_size && self.flush_buf()?.is_not_ready() {
+                return Ok(AsyncSink::NotReady(item));
+            }
+            if !self.buf.is_empty() || len < buffer_size {
+                self.buf.extend_from_slice(item.as_ref());
+                if self.buf.len() >= buffer_size {
+                    // if there's no room yet, this is retried on the next send
+                    self.flush_buf()?;
+                }
+                return Ok(AsyncSink::Ready);
+            }
+        }
+
+        if self.poll_ready()?.is_not_ready() {
+            return Ok(AsyncSink::NotReady(item));
+        }
+        self.send(Chunk::new(item))?;
+        Ok(AsyncSink::Ready)
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), Self::SinkError> {
+        try_ready!(self.flush_buf());
+        self.poll_acks()
+    }
+
+    fn close(&mut self) -> Poll<(), Self::SinkError> {
+        try_ready!(self.poll_complete());
+        loop {
+            if self.closed {
+                return Ok(Async::Ready(()));
+            }
+            if let Some(mut rx) = self.closing.take() {
+                match rx.poll() {
+                    Ok(Async::Ready(checksum)) => {
+                        self.checksum = checksum;
+                        self.closed = true;
+                    }
+                    Ok(Async::NotReady) => {
+                        self.closing = Some(rx);
+                        self.poll_timeout()?;
+                        return Ok(Async::NotReady);
+                    }
+                    Err(e) => {
+                        self.closed = true;
+                        return Err(e);
+                    }
+                }
+                continue;
+            }
+
+            let (tx, rx) = oneshot::channel();
+            match self.ops.start_send(Op::Close(tx)) {
+                Ok(AsyncSink::Ready) => {
+                    self.closing = Some(super::fs(rx).context(self.context.clone()));
+                    self.reset_timeout();
+                }
+                Ok(AsyncSink::NotReady(_)) => return Ok(Async::NotReady),
+                Err(_) => return Err(self.stopped()),
+            }
+        }
+    }
+}
+
+impl<B> Drop for FsWriteSink<B> {
+    fn drop(&mut self) {
+        if !self.closed {
+            self.cancel.cancel();
+        }
+    }
+}
+
+impl<B> fmt::Debug for FsWriteSink<B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsWriteSink").finish()
+    }
+}
+
+pub(crate) fn new_positioned<P>(pool: &FsPool, path: P, opts: WriteOptions) -> FsPositionedWriteSink
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    let atomic = if opts.atomic {
+        Some(Atomic::new(path.as_ref()))
+    } else {
+        None
+    };
+
+    let pool = &pool.prioritized(opts.priority);
+    let rate = pool.rate_limit(opts.max_bytes_per_sec);
+    let sync_on_close = opts.sync_on_close;
+    let context = context(path.as_ref(), &opts);
+    let target = match atomic {
+        Some(ref atomic) => atomic.tmp.clone(),
+        None => path.as_ref().to_owned(),
+    };
+
+    let rx = pool
+        .exec_as(Some(FsOperation::Write), move || open(&target, &opts))
+        .context(context.clone());
+
+    FsPositionedWriteSink {
+        pool: pool.clone(),
+        context,
+        rate,
+        atomic,
+        sync_on_close,
+        set_len_on_close: false,
+        end: 0,
+        in_flight: Vec::new(),
+        state: PositionedState::Opening(rx),
+    }
+}
+
+/// A `Sink` to send bytes to be written at a given offset of a target file.
+///
+/// Items are pairs of an offset and the bytes to write there. Since writes
+/// don't share a cursor, several of them run on the pool at the same time,
+/// so writes to overlapping ranges may be applied in any order.
+pub struct FsPositionedWriteSink {
+    pool: FsPool,
+    context: Option<Context>,
+    rate: Option<Arc<RateLimit>>,
+    atomic: Option<Atomic>,
+    sync_on_close: bool,
+    set_len_on_close: bool,
+    end: u64,
+    in_flight: Vec<FsFuture<()>>,
+    state: PositionedState,
+}
+
+enum PositionedState {
+    Opening(FsFuture<File>),
+    Ready(Arc<File>),
+    Closing(FsFuture<()>),
+    Closed,
     Swapping,
 }
 
-impl FsWriteSink {
-    fn poll_working(&mut self) -> Poll<(), io::Error> {
+impl FsPositionedWriteSink {
+    /// Whether to truncate or extend the file to the end of the highest
+    /// write once the sink is closed.
+    ///
+    /// Default is `false`.
+    pub fn set_len_on_close(mut self, set_len_on_close: bool) -> Self {
+        self.set_len_on_close = set_len_on_close;
+        self
+    }
+
+    fn poll_opening(&mut self) -> Poll<(), io::Error> {
         let state = match self.state {
-            State::Working(ref mut rx) => {
+            PositionedState::Opening(ref mut rx) => {
                 let file = try_ready!(rx.poll());
-                State::Ready(file)
+                PositionedState::Ready(Arc::new(file))
             }
-            State::Ready(_) => {
+            PositionedState::Ready(_) | PositionedState::Closing(_) | PositionedState::Closed => {
                 return Ok(Async::Ready(()));
             }
-            State::Swapping => unreachable!(),
+            PositionedState::Swapping => unreachable!(),
         };
         self.state = state;
         Ok(Async::Ready(()))
     }
+
+    fn poll_in_flight(&mut self) -> Poll<(), io::Error> {
+        let mut i = 0;
+        while i < self.in_flight.len() {
+            match self.in_flight[i].poll() {
+                Ok(Async::Ready(())) => {
+                    self.in_flight.swap_remove(i);
+                }
+                Ok(Async::NotReady) => i += 1,
+                Err(e) => {
+                    self.in_flight.swap_remove(i);
+                    return Err(e);
+                }
+            }
+        }
+        if self.in_flight.is_empty() {
+            Ok(Async::Ready(()))
+        } else {
+            Ok(Async::NotReady)
+        }
+    }
 }
 
-impl Sink for FsWriteSink {
-    type SinkItem = Bytes;
+impl Sink for FsPositionedWriteSink {
+    type SinkItem = (u64, Bytes);
     type SinkError = io::Error;
 
     fn start_send(&mut self, item: Self::SinkItem) -> StartSend<Self::SinkItem, Self::SinkError> {
-        let state = self.poll_working()?;
-        if state.is_ready() {
-            let mut file = match ::std::mem::replace(&mut self.state, State::Swapping) {
-                State::Ready(file) => file,
-                _ => unreachable!(),
-            };
+        if self.poll_opening()?.is_not_ready() {
+            return Ok(AsyncSink::NotReady(item));
+        }
+        let file = match self.state {
+            PositionedState::Ready(ref file) => file.clone(),
+            _ => return Err(closed()),
+        };
 
-            let (tx, rx) = oneshot::channel();
+        self.poll_in_flight()?;
+        if self.in_flight.len() >= MAX_IN_FLIGHT {
+            return Ok(AsyncSink::NotReady(item));
+        }
 
-            let fut = Box::new(lazy(move || {
-                let res = file
-                    .write_all(item.as_ref())
-                    .map(|_| file)
-                    .map_err(From::from);
+        let (offset, bytes) = item;
+        self.end = cmp::max(self.end, offset + bytes.len() as u64);
+        let metrics = self.pool.metrics.clone();
+        let rate = self.rate.clone();
+        let rx = self.pool.exec(move || {
+            if let Some(rate) = rate {
+                rate.take(bytes.len() as u64);
+            }
+            sys::write_all_at(&file, &bytes, offset)?;
+            metrics.written(bytes.len() as u64);
+            Ok(())
+        });
+        self.in_flight.push(rx.context(self.context.clone()));
+        Ok(AsyncSink::Ready)
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), Self::SinkError> {
+        try_ready!(self.poll_opening());
+        self.poll_in_flight()
+    }
 
-                tx.send(res).map_err(|_| ())
+    fn close(&mut self) -> Poll<(), Self::SinkError> {
+        try_ready!(self.poll_complete());
+        loop {
+            match mem::replace(&mut self.state, PositionedState::Swapping) {
+                PositionedState::Ready(file) => {
+                    let set_len = if self.set_len_on_close {
+                        Some(self.end)
+                    } else {
+                        None
+                    };
+                    let atomic = self.atomic.clone();
+                    let sync_on_close = self.sync_on_close;
+                    if set_len.is_none() && atomic.is_none() && !sync_on_close {
+                        self.state = PositionedState::Ready(file);
+                        return Ok(Async::Ready(()));
+                    }
+
+                    let rx = self.pool.exec(move || {
+                        if let Some(len) = set_len {
+                            file.set_len(len)?;
+                        }
+                        match atomic {
+                            Some(atomic) => atomic.commit(&file),
+                            None if sync_on_close => file.sync_all(),
+                            None => Ok(()),
+                        }
+                    });
+
+                    self.state = PositionedState::Closing(rx.context(self.context.clone()));
+                }
+                PositionedState::Closing(mut rx) => {
+                    let polled = rx.poll();
+                    self.state = PositionedState::Closing(rx);
+                    try_ready!(polled);
+
+                    self.atomic = None;
+                    self.state = PositionedState::Closed;
+                    return Ok(Async::Ready(()));
+                }
+                PositionedState::Closed => {
+                    self.state = PositionedState::Closed;
+                    return Ok(Async::Ready(()));
+                }
+                PositionedState::Opening(_) | PositionedState::Swapping => unreachable!(),
+            }
+        }
+    }
+}
+
+impl Drop for FsPositionedWriteSink {
+    fn drop(&mut self) {
+        if let Some(atomic) = self.atomic.take() {
+            // the temporary file was never renamed into place
+            let fut = Box::new(lazy(move || {
+                let _ = fs::remove_file(atomic.tmp);
+                Ok(())
             }));
+            let _ = self.pool.spawn_cleanup(fut);
+        }
+    }
+}
+
+impl fmt::Debug for FsPositionedWriteSink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsPositionedWriteSink")
+            .field("in_flight", &self.in_flight.len())
+            .finish()
+    }
+}
+
+/// Collects bytes in the aligned `buf`, writing every whole block of it.
+///
+/// A final partial block is kept in `buf`.
+fn write_direct<B>(file: &mut File, buf: &mut BytesMut, queue: Vec<B>) -> io::Result<()>
+where
+    B: AsRef<[u8]>,
+{
+    for bytes in queue {
+        let mut bytes = bytes.as_ref();
+        while !bytes.is_empty() {
+            // never grow the buffer, which would lose its alignment
+            let n = cmp::min(DIRECT_BUF_SIZE - buf.len(), bytes.len());
+            buf.extend_from_slice(&bytes[..n]);
+            bytes = &bytes[n..];
+            if buf.len() == DIRECT_BUF_SIZE {
+                file.write_all(buf)?;
+                buf.clear();
+            }
+        }
+    }
 
-            self.pool.executor.execute(fut).unwrap();
+    let whole = buf.len() - buf.len() % sys::DIRECT_ALIGN;
+    if whole > 0 {
+        file.write_all(&buf[..whole])?;
+        let rest = buf[whole..].to_vec();
+        buf.clear();
+        buf.extend_from_slice(&rest);
+    }
+    Ok(())
+}
+
+fn write_all_vectored<B>(file: &mut File, mut bufs: Vec<B>) -> io::Result<()>
+where
+    B: AsRef<[u8]>,
+{
+    bufs.retain(|bytes| !bytes.as_ref().is_empty());
+    if bufs.len() == 1 {
+        return file.write_all(bufs[0].as_ref());
+    }
 
-            self.state = State::Working(super::fs(rx));
-            Ok(AsyncSink::Ready)
+    // files that don't support vectored writes only write the first buffer,
+    // so this falls back to writing each buffer in turn
+    let mut start = 0;
+    // the bytes of the first buffer that were already written
+    let mut skip = 0;
+    while start < bufs.len() {
+        let res = {
+            let mut slices = bufs[start..]
+                .iter()
+                .map(|bytes| IoSlice::new(bytes.as_ref()))
+                .collect::<Vec<_>>();
+            slices[0] = IoSlice::new(&bufs[start].as_ref()[skip..]);
+            file.write_vectored(&slices)
+        };
+        let mut n = match res {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "failed to write whole buffer",
+                ))
+            }
+            Ok(n) => n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        n += skip;
+        while start < bufs.len() && n >= bufs[start].as_ref().len() {
+            n -= bufs[start].as_ref().len();
+            start += 1;
+        }
+        skip = n;
+    }
+    Ok(())
+}
+
+fn closed() -> io::Error {
+    io::Error::other("write sink is closed")
+}
+
+fn open(path: &Path, opts: &WriteOptions) -> io::Result<File> {
+    if opts.create_parents {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+    let file = if opts.atomic {
+        // the temporary file must always be a new one
+        let mut open = opts.open.clone();
+        open.write(true).create_new(true);
+        open.open(path)?
+    } else if opts.no_follow || opts.regular_only {
+        sys::open_checked(path, &opts.open, opts.no_follow, opts.regular_only)?
+    } else {
+        opts.open.open(path)?
+    };
+    if let Some(len) = opts.preallocate {
+        sys::preallocate(&file, len)?;
+    }
+    Ok(file)
+}
+
+/// Truncates the file to its current position.
+fn truncate(file: &mut File) -> io::Result<()> {
+    let end = file.stream_position()?;
+    file.set_len(end)
+}
+
+pub(crate) fn write_all(path: &Path, data: &[u8], opts: &WriteOptions) -> io::Result<()> {
+    if opts.atomic {
+        let atomic = Atomic::new(path);
+        let res = open(&atomic.tmp, opts).and_then(|mut file| {
+            file.write_all(data)?;
+            if opts.truncate_on_close {
+                truncate(&mut file)?;
+            }
+            atomic.commit(&file)
+        });
+        if res.is_err() {
+            let _ = fs::remove_file(&atomic.tmp);
+        }
+        res
+    } else {
+        let mut file = open(path, opts)?;
+        file.write_all(data)?;
+        if opts.truncate_on_close {
+            truncate(&mut file)?;
+        }
+        if opts.sync_on_close {
+            file.sync_all()
         } else {
-            Ok(AsyncSink::NotReady(item))
+            file.flush()
         }
     }
+}
 
-    fn poll_complete(&mut self) -> Poll<(), Self::SinkError> {
-        self.poll_working()
+/// A file written to outside of a sink, which is finished like a sink would
+/// finish it once closed.
+pub(crate) struct Target {
+    file: Option<File>,
+    atomic: Option<Atomic>,
+    truncate_on_close: bool,
+    sync_on_close: bool,
+}
+
+impl Target {
+    /// Opens the file at `path` to write with `opts`, or its temporary file
+    /// when writing atomically.
+    pub(crate) fn open(path: &Path, opts: &WriteOptions) -> io::Result<Target> {
+        let atomic = if opts.atomic {
+            Some(Atomic::new(path))
+        } else {
+            None
+        };
+        let file = match atomic {
+            Some(ref atomic) => open(&atomic.tmp, opts)?,
+            None => open(path, opts)?,
+        };
+        Ok(Target {
+            file: Some(file),
+            atomic,
+            truncate_on_close: opts.truncate_on_close,
+            sync_on_close: opts.sync_on_close,
+        })
+    }
+
+    pub(crate) fn file(&mut self) -> &mut File {
+        self.file.as_mut().unwrap()
+    }
+
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        let mut file = self.file.take().unwrap();
+        if self.truncate_on_close {
+            truncate(&mut file)?;
+        }
+        if let Some(ref atomic) = self.atomic {
+            atomic.commit(&file)?;
+        } else if self.sync_on_close {
+            file.sync_all()?;
+        }
+        self.atomic = None;
+        Ok(())
     }
 }
 
-impl fmt::Debug for FsWriteSink {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.debug_struct("FsWriteSink").finish()
+impl Drop for Target {
+    fn drop(&mut self) {
+        if let Some(atomic) = self.atomic.take() {
+            // the temporary file was never renamed into place
+            self.file = None;
+            let _ = fs::remove_file(atomic.tmp);
+        }
+    }
+}
+
+pub(crate) fn write_stream<P, S>(
+    pool: &FsPool,
+    path: P,
+    stream: S,
+    opts: WriteOptions,
+) -> FsFuture<u64>
+where
+    P: AsRef<Path> + Send + 'static,
+    S: Stream<Item = Bytes, Error = io::Error> + Send + 'static,
+{
+    let pool = &pool.prioritized(opts.priority);
+    let (tx, rx) = oneshot::channel();
+
+    let writer = StreamWriter {
+        rate: pool.rate_limit(opts.max_bytes_per_sec),
+        running: metrics::start(&pool.metrics, Some(FsOperation::Write)),
+        path: path.as_ref().to_owned(),
+        opts,
+        target: None,
+        stream,
+        written: 0,
+        tx: Some(tx),
+    };
+    if let Err(e) = pool.execute(Box::new(writer)) {
+        return super::failed(e);
+    }
+    super::fs(rx)
+}
+
+/// The task on the pool that writes the chunks of a stream to a file, for
+/// `FsPool::write_stream`.
+struct StreamWriter<S> {
+    path: PathBuf,
+    opts: WriteOptions,
+    target: Option<Target>,
+    stream: S,
+    written: u64,
+    running: Running,
+    rate: Option<Arc<RateLimit>>,
+    tx: Option<oneshot::Sender<io::Result<u64>>>,
+}
+
+impl<S> StreamWriter<S>
+where
+    S: Stream<Item = Bytes, Error = io::Error>,
+{
+    fn poll_write(&mut self) -> Poll<u64, io::Error> {
+        if self.target.is_none() {
+            let target = super::refused()
+                .and_then(|()| Target::open(&self.path, &self.opts))
+                .map_err(|e| self.file_error(e))?;
+            self.target = Some(target);
+        }
+
+        loop {
+            // every chunk that is ready is written at once
+            let mut queue = Vec::new();
+            let mut eof = false;
+            while queue.len() < MAX_QUEUED {
+                match self.stream.poll()? {
+                    Async::Ready(Some(bytes)) => queue.push(bytes),
+                    Async::Ready(None) => {
+                        eof = true;
+                        break;
+                    }
+                    Async::NotReady => break,
+                }
+            }
+
+            if queue.is_empty() && !eof {
+                return Ok(Async::NotReady);
+            }
+            let len = queue.iter().map(|bytes| bytes.len() as u64).sum::<u64>();
+            if let Some(ref rate) = self.rate {
+                rate.take(len);
+            }
+            let res = write_all_vectored(self.target.as_mut().unwrap().file(), queue);
+            res.map_err(|e| self.file_error(e))?;
+            self.written += len;
+            self.running.metrics().written(len);
+
+            if eof {
+                let target = self.target.take().unwrap();
+                target.finish().map_err(|e| self.file_error(e))?;
+                return Ok(Async::Ready(self.written));
+            }
+        }
+    }
+
+    /// Wraps an error of the file, to tell it apart from one of the stream.
+    fn file_error(&self, e: io::Error) -> io::Error {
+        FsError::new(FsOperation::Write, &*self.path, e).into()
+    }
+}
+
+impl<S> Future for StreamWriter<S>
+where
+    S: Stream<Item = Bytes, Error = io::Error>,
+{
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        // the file is left unfinished if the future was dropped
+        if let Ok(Async::Ready(())) = self.tx.as_mut().unwrap().poll_cancel() {
+            return Ok(Async::Ready(()));
+        }
+        let res = match self.poll_write() {
+            Ok(Async::NotReady) => return Ok(Async::NotReady),
+            Ok(Async::Ready(written)) => Ok(written),
+            Err(e) => Err(e),
+        };
+        self.running.set_failed(res.is_err());
+        let _ = self.tx.take().unwrap().send(res);
+        Ok(Async::Ready(()))
     }
 }
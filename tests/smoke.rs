@@ -1,8 +1,26 @@
+// newer compilers lint the unused stream that `forward` resolves with
+#![allow(unused_must_use)]
+
 extern crate futures;
+#[cfg(feature = "std-future")]
+extern crate futures_core;
 extern crate futures_fs;
+#[cfg(feature = "std-future")]
+extern crate futures_sink;
+#[cfg(feature = "tokio-blocking")]
+extern crate tokio_executor;
+#[cfg(feature = "tokio-io")]
+extern crate tokio_io;
+#[cfg(feature = "tokio-blocking")]
+extern crate tokio_threadpool;
 
-use futures::{Future, Sink, Stream};
-use futures_fs::FsPool;
+use futures::{future, stream, Future, Sink, Stream};
+use futures_fs::{
+    Advice, CopyDirOptions, CopyOptions, DeleteOptions, DirStatsOptions, FsError, FsOpCounts,
+    FsOperation, FsPool, MoveOptions, OpPriority, PipeOptions, ReadDirOptions, ReadOptions,
+    WalkOptions, WriteOptions,
+};
+use std::borrow::Cow;
 use std::{env, fs, io};
 
 #[test]
@@ -113,3 +131,3887 @@ fn test_from_file_smoke_long() {
 
     fs.delete(tmp).wait().unwrap();
 }
+
+#[test]
+fn test_metadata() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-metadata");
+
+    fs::write(&tmp, b"hello world").unwrap();
+
+    let meta = fs.metadata(tmp.clone()).wait().unwrap();
+    assert!(meta.is_file());
+    assert_eq!(meta.len(), 11);
+
+    let file = fs::File::open(&tmp).unwrap();
+    let meta = fs.metadata_file(&file).wait().unwrap();
+    assert_eq!(meta.len(), 11);
+
+    fs.delete(tmp.clone()).wait().unwrap();
+
+    let err = fs.metadata(tmp).wait().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_delete_if_exists() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-delete-if-exists");
+    fs::write(&tmp, b"hello world").unwrap();
+
+    assert!(fs.delete_if_exists(tmp.clone()).wait().unwrap());
+    assert!(!tmp.exists());
+    assert!(!fs.delete_if_exists(tmp.clone()).wait().unwrap());
+
+    // other errors are still reported
+    fs::create_dir(&tmp).unwrap();
+    fs.delete_if_exists(tmp.clone()).wait().unwrap_err();
+    fs::remove_dir(&tmp).unwrap();
+
+    let err = fs.delete(tmp).wait().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_tempfile() {
+    use std::collections::HashSet;
+    use std::io::{Read, Seek, SeekFrom, Write};
+
+    let fs = FsPool::default();
+
+    let mut dir = env::temp_dir();
+    dir.push("futures-fs-tempfile");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir(&dir).unwrap();
+
+    let futs = (0..100)
+        .map(|_| fs.tempfile_in(dir.clone()))
+        .collect::<Vec<_>>();
+    let files = futures::future::join_all(futs).wait().unwrap();
+    let paths = files
+        .iter()
+        .map(|(path, _)| path.clone())
+        .collect::<HashSet<_>>();
+    assert_eq!(paths.len(), 100);
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 100);
+
+    let (path, mut file) = files.into_iter().next().unwrap();
+    assert_eq!(path.parent(), Some(dir.as_path()));
+    file.write_all(b"hello world").unwrap();
+    file.seek(SeekFrom::Start(0)).unwrap();
+    let mut read = String::new();
+    file.read_to_string(&mut read).unwrap();
+    assert_eq!(read, "hello world");
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    let (path, _) = fs.tempfile().wait().unwrap();
+    assert_eq!(path.parent(), Some(env::temp_dir().as_path()));
+    fs::remove_file(path).unwrap();
+
+    let err = fs.tempfile_in(dir.join("missing")).wait().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_tempdir() {
+    use futures::future::{ExecuteError, ExecuteErrorKind, Executor};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    let fs = FsPool::default();
+
+    let dir = fs.tempdir().wait().unwrap();
+    assert_eq!(dir.parent(), Some(env::temp_dir().as_path()));
+    fs::write(dir.join("file"), b"hello world").unwrap();
+    fs::create_dir(dir.join("nested")).unwrap();
+    fs::write(dir.join("nested/file"), b"hello world").unwrap();
+    let path = dir.to_path_buf();
+    drop(dir);
+    for _ in 0..100 {
+        if !path.exists() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(!path.exists());
+
+    let dir = fs.tempdir_in(env::temp_dir()).wait().unwrap();
+    fs::write(dir.join("file"), b"hello world").unwrap();
+    let path = dir.to_path_buf();
+    dir.close().wait().unwrap();
+    assert!(!path.exists());
+
+    let path = fs.tempdir().wait().unwrap().into_path();
+    assert!(path.is_dir());
+    fs::remove_dir(&path).unwrap();
+
+    // dropping after the pool shut down doesn't panic
+    struct Inline(Arc<AtomicBool>);
+
+    impl Executor<Box<dyn Future<Item = (), Error = ()> + Send>> for Inline {
+        fn execute(
+            &self,
+            future: Box<dyn Future<Item = (), Error = ()> + Send>,
+        ) -> Result<(), ExecuteError<Box<dyn Future<Item = (), Error = ()> + Send>>> {
+            if self.0.load(Ordering::SeqCst) {
+                return Err(ExecuteError::new(ExecuteErrorKind::Shutdown, future));
+            }
+            let _ = future.wait();
+            Ok(())
+        }
+    }
+
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let fs = FsPool::with_executor(Inline(shutdown.clone()));
+    let dir = fs.tempdir().wait().unwrap();
+    let path = dir.to_path_buf();
+    shutdown.store(true, Ordering::SeqCst);
+    drop(dir);
+    assert!(path.is_dir());
+    fs::remove_dir(&path).unwrap();
+}
+
+#[test]
+fn test_lock() {
+    use std::io::Write;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-lock");
+    let _ = fs::remove_file(&tmp);
+
+    let lock = fs.lock_exclusive(tmp.clone()).wait().unwrap();
+    assert!(tmp.exists());
+    assert!(fs.try_lock_exclusive(tmp.clone()).wait().unwrap().is_none());
+    assert!(fs.try_lock_shared(tmp.clone()).wait().unwrap().is_none());
+
+    // another lock waits until the first is released
+    let (tx, rx) = mpsc::channel();
+    let waiting = fs.lock_exclusive(tmp.clone());
+    thread::spawn(move || tx.send(waiting.wait().unwrap()).unwrap());
+    assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+    lock.unlock().wait().unwrap();
+    let lock = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    drop(lock);
+
+    let shared = fs.lock_shared(tmp.clone()).wait().unwrap();
+    let other = fs.try_lock_shared(tmp.clone()).wait().unwrap();
+    assert!(other.is_some());
+    assert!(fs.try_lock_exclusive(tmp.clone()).wait().unwrap().is_none());
+    drop(shared);
+    drop(other);
+    assert!(fs.try_lock_exclusive(tmp.clone()).wait().unwrap().is_some());
+
+    // a shared lock only reads, so a read-only file can be locked
+    fs.set_readonly(tmp.clone(), true).wait().unwrap();
+    let shared = fs.lock_shared(tmp.clone()).wait().unwrap();
+    let mut file = shared.file();
+    assert!(file.write(b"x").is_err());
+    drop(shared);
+    fs.set_readonly(tmp.clone(), false).wait().unwrap();
+
+    fs::remove_file(&tmp).unwrap();
+}
+
+#[test]
+fn test_lock_timeout() {
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    // with a single thread, waiting for the lock can't block it
+    let fs = FsPool::new(1);
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-lock-timeout");
+    let _ = fs::remove_file(&tmp);
+
+    let lock = fs.lock_exclusive(tmp.clone()).wait().unwrap();
+
+    let start = Instant::now();
+    let waiting = fs.lock_shared_timeout(&tmp, Duration::from_millis(100));
+    fs.metadata(tmp.clone()).wait().unwrap();
+    let err = waiting.wait().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    assert!(start.elapsed() >= Duration::from_millis(100));
+
+    let waiting = fs.lock_exclusive_timeout(&tmp, Duration::from_secs(5));
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        drop(lock);
+    });
+    waiting.wait().unwrap();
+
+    fs::remove_file(&tmp).unwrap();
+}
+
+#[cfg(feature = "notify")]
+#[test]
+fn test_watch() {
+    use futures_fs::{FsWatchEvent, WatchOptions};
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    let fs = FsPool::default();
+
+    let mut dir = env::temp_dir();
+    dir.push("futures-fs-watch");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir(&dir).unwrap();
+    let dir = fs::canonicalize(dir).unwrap();
+
+    let stream = fs.watch(&dir, WatchOptions::default().coalesce(true));
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for event in stream.wait() {
+            if tx.send(event.unwrap()).is_err() {
+                break;
+            }
+        }
+    });
+    // give the watcher time to start
+    thread::sleep(Duration::from_millis(200));
+
+    let next = || rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    let file = dir.join("file");
+    let renamed = dir.join("renamed");
+
+    fs::File::create(&file).unwrap();
+    assert_eq!(next(), FsWatchEvent::Created(file.clone()));
+    fs::rename(&file, &renamed).unwrap();
+    assert_eq!(next(), FsWatchEvent::Renamed(file.clone(), renamed.clone()));
+    fs::remove_file(&renamed).unwrap();
+    assert_eq!(next(), FsWatchEvent::Removed(renamed.clone()));
+    drop(rx);
+
+    // events that can't be kept are reported
+    let mut stream = fs.watch(&dir, WatchOptions::default().capacity(1));
+    thread::sleep(Duration::from_millis(200));
+    for i in 0..5 {
+        fs::File::create(dir.join(format!("file-{}", i))).unwrap();
+    }
+    thread::sleep(Duration::from_millis(200));
+    let (event, rest) = stream.into_future().wait().map_err(|(e, _)| e).unwrap();
+    assert_eq!(event, Some(FsWatchEvent::Created(dir.join("file-0"))));
+    stream = rest;
+    let (event, _) = stream.into_future().wait().map_err(|(e, _)| e).unwrap();
+    assert_eq!(event, Some(FsWatchEvent::Overflow));
+
+    let err = fs
+        .watch(dir.join("missing"), Default::default())
+        .collect()
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_poll_watch() {
+    use futures_fs::FsWatchEvent;
+    use std::time::Duration;
+
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-poll-watch");
+    let _ = fs::remove_file(&tmp);
+
+    let mut events = fs
+        .poll_watch(&tmp, Duration::from_millis(10))
+        .wait()
+        .map(|event| event.unwrap());
+    assert_eq!(
+        events.next(),
+        Some(FsWatchEvent::Snapshot(tmp.clone(), false))
+    );
+    fs::write(&tmp, b"hello").unwrap();
+    assert_eq!(events.next(), Some(FsWatchEvent::Created(tmp.clone())));
+    fs::write(&tmp, b"hello world").unwrap();
+    assert_eq!(events.next(), Some(FsWatchEvent::Modified(tmp.clone())));
+    fs::remove_file(&tmp).unwrap();
+    assert_eq!(events.next(), Some(FsWatchEvent::Removed(tmp.clone())));
+
+    fs::write(&tmp, b"hello").unwrap();
+    let mut events = fs.poll_watch(&tmp, Duration::from_millis(10)).wait();
+    assert_eq!(
+        events.next().unwrap().unwrap(),
+        FsWatchEvent::Snapshot(tmp.clone(), true)
+    );
+    fs::remove_file(&tmp).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_xattr() {
+    use std::ffi::OsString;
+
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-xattr");
+    fs::write(&tmp, b"hello world").unwrap();
+
+    let name = OsString::from("user.futures-fs");
+    match fs
+        .set_xattr(tmp.clone(), name.clone(), b"checksum".to_vec())
+        .wait()
+    {
+        Ok(()) => (),
+        Err(e) => {
+            // not every filesystem supports extended attributes
+            eprintln!("skipping xattr test: {}", e);
+            fs::remove_file(&tmp).unwrap();
+            return;
+        }
+    }
+    assert_eq!(
+        fs.get_xattr(tmp.clone(), name.clone()).wait().unwrap(),
+        Some(b"checksum".to_vec())
+    );
+    assert!(fs.list_xattrs(tmp.clone()).wait().unwrap().contains(&name));
+
+    fs.remove_xattr(tmp.clone(), name.clone()).wait().unwrap();
+    assert_eq!(
+        fs.get_xattr(tmp.clone(), name.clone()).wait().unwrap(),
+        None
+    );
+    assert!(!fs.list_xattrs(tmp.clone()).wait().unwrap().contains(&name));
+    fs.remove_xattr(tmp.clone(), name).wait().unwrap_err();
+
+    fs::remove_file(&tmp).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_chown() {
+    use std::os::unix::fs::{symlink, MetadataExt};
+
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-chown");
+    let link = tmp.with_file_name("futures-fs-chown-link");
+    let _ = fs::remove_file(&link);
+    fs::write(&tmp, b"hello world").unwrap();
+    symlink(&tmp, &link).unwrap();
+
+    let meta = fs::metadata(&tmp).unwrap();
+    fs.chown(tmp.clone(), None, None).wait().unwrap();
+    fs.chown(link.clone(), Some(meta.uid()), Some(meta.gid()))
+        .wait()
+        .unwrap();
+    fs.lchown(link.clone(), Some(meta.uid()), None)
+        .wait()
+        .unwrap();
+    assert_eq!(fs::metadata(&tmp).unwrap().uid(), meta.uid());
+
+    if meta.uid() != 0 {
+        let err = fs.chown(tmp.clone(), Some(0), None).wait().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    }
+
+    let err = fs
+        .chown(tmp.with_file_name("futures-fs-chown-missing"), None, None)
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+    fs::remove_file(&link).unwrap();
+    fs::remove_file(&tmp).unwrap();
+}
+
+#[test]
+fn test_set_file_times() {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-set-file-times");
+    fs::write(&tmp, b"hello world").unwrap();
+
+    let accessed = UNIX_EPOCH + Duration::new(1_000_000_000, 123_456_789);
+    let modified = UNIX_EPOCH + Duration::new(1_500_000_000, 987_654_321);
+    fs.set_file_times(tmp.clone(), accessed, modified)
+        .wait()
+        .unwrap();
+    let meta = fs.metadata(tmp.clone()).wait().unwrap();
+    assert_eq!(meta.modified().unwrap(), modified);
+    assert_eq!(meta.accessed().unwrap(), accessed);
+
+    let file = fs::OpenOptions::new().write(true).open(&tmp).unwrap();
+    fs.set_file_times_file(&file, modified, accessed)
+        .wait()
+        .unwrap();
+    let meta = fs.metadata_file(&file).wait().unwrap();
+    assert_eq!(meta.modified().unwrap(), accessed);
+    assert_eq!(meta.accessed().unwrap(), modified);
+
+    let err = fs
+        .set_file_times(tmp.with_file_name("futures-fs-missing"), accessed, modified)
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+    fs::remove_file(&tmp).unwrap();
+}
+
+#[test]
+fn test_statfs() {
+    let fs = FsPool::default();
+
+    let stats = fs.statfs(env::temp_dir()).wait().unwrap();
+    assert!(stats.total_bytes() > 0);
+    assert!(stats.free_bytes() <= stats.total_bytes());
+    assert!(stats.available_bytes() <= stats.free_bytes());
+    assert!(stats.block_size() > 0);
+
+    let err = fs
+        .statfs(env::temp_dir().join("futures-fs-statfs-missing"))
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_spawn_fn() {
+    let fs = FsPool::new(1);
+
+    let n = fs.spawn_fn(|| Ok(21 * 2)).wait().unwrap();
+    assert_eq!(n, 42);
+
+    let err = fs
+        .spawn_fn(|| -> io::Result<()> { Err(io::Error::other("oh no")) })
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.to_string(), "oh no");
+
+    let err = fs
+        .spawn_fn(|| -> io::Result<()> { panic!("oh no") })
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::Other);
+
+    // the only thread of the pool still works
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-spawn-fn");
+    fs::write(&tmp, b"hello world").unwrap();
+    let meta = fs.metadata(tmp.clone()).wait().unwrap();
+    assert_eq!(meta.len(), 11);
+    fs::remove_file(&tmp).unwrap();
+}
+
+#[test]
+fn test_canceled() {
+    use futures::future::{ExecuteError, Executor};
+
+    // drops every task without running it, like a pool shutting down
+    struct Dropping;
+
+    impl Executor<Box<dyn Future<Item = (), Error = ()> + Send>> for Dropping {
+        fn execute(
+            &self,
+            future: Box<dyn Future<Item = (), Error = ()> + Send>,
+        ) -> Result<(), ExecuteError<Box<dyn Future<Item = (), Error = ()> + Send>>> {
+            drop(future);
+            Ok(())
+        }
+    }
+
+    let fs = FsPool::with_executor(Dropping);
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-canceled");
+
+    let err = fs.delete(tmp.clone()).wait().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::Other);
+    assert_eq!(err.to_string(), "fs task canceled");
+
+    let err = fs
+        .read(tmp.clone(), Default::default())
+        .collect()
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::Other);
+    fs.read(tmp.clone(), Default::default())
+        .into_file()
+        .wait()
+        .unwrap_err();
+
+    let write = fs.write(tmp.clone(), Default::default());
+    write.send("hello".into()).wait().unwrap_err();
+    assert!(!tmp.exists());
+
+    // a stream ends after the error
+    let mut dir = fs.read_dir(env::temp_dir()).wait();
+    dir.next().unwrap().unwrap_err();
+    assert!(dir.next().is_none());
+    let mut walk = fs.walk(env::temp_dir(), Default::default()).wait();
+    walk.next().unwrap().unwrap_err();
+    assert!(walk.next().is_none());
+    let mut glob = fs.glob("*").wait();
+    glob.next().unwrap().unwrap_err();
+    assert!(glob.next().is_none());
+}
+
+#[test]
+fn test_executor_refused() {
+    use futures::future::{ExecuteError, ExecuteErrorKind, Executor};
+    use std::time::Duration;
+
+    // refuses every task, like a saturated, bounded executor
+    struct Full;
+
+    impl Executor<Box<dyn Future<Item = (), Error = ()> + Send>> for Full {
+        fn execute(
+            &self,
+            future: Box<dyn Future<Item = (), Error = ()> + Send>,
+        ) -> Result<(), ExecuteError<Box<dyn Future<Item = (), Error = ()> + Send>>> {
+            Err(ExecuteError::new(ExecuteErrorKind::NoCapacity, future))
+        }
+    }
+
+    let fs = FsPool::with_executor(Full);
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-executor-refused");
+
+    let err = fs.delete(tmp.clone()).wait().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+
+    let err = fs
+        .read(tmp.clone(), Default::default())
+        .collect()
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+
+    let write = fs.write(tmp.clone(), Default::default());
+    let err = write.send("hello".into()).wait().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+
+    let err = fs.read_dir(env::temp_dir()).collect().wait().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+
+    let err = fs
+        .delete_all(vec![tmp.clone()], Default::default())
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+
+    let err = fs
+        .lock_exclusive_timeout(&tmp, Duration::from_secs(1))
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+    assert!(!tmp.exists());
+}
+
+#[test]
+fn test_contextual_errors() {
+    let fs = FsPool::default();
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-contextual-errors");
+    let _ = fs::remove_dir_all(&tmp);
+    let missing = tmp.join("missing.txt");
+
+    let err = fs
+        .read(
+            missing.clone(),
+            ReadOptions::default().contextual_errors(true),
+        )
+        .collect()
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    let fs_err = FsError::from_io(&err).unwrap();
+    assert_eq!(fs_err.operation(), FsOperation::Read);
+    assert_eq!(fs_err.path(), &*missing);
+    assert_eq!(fs_err.kind(), io::ErrorKind::NotFound);
+    let msg = err.to_string();
+    assert!(
+        msg.starts_with(&format!("read {}: ", missing.display())),
+        "{}",
+        msg
+    );
+
+    // the target is named, not the temporary file
+    let opts = WriteOptions::default().atomic(true).contextual_errors(true);
+    let err = fs
+        .write(missing.clone(), opts)
+        .send("hello".into())
+        .and_then(|sink| sink.flush())
+        .wait()
+        .unwrap_err();
+    let fs_err = FsError::from_io(&err).unwrap();
+    assert_eq!(fs_err.operation(), FsOperation::Write);
+    assert_eq!(fs_err.path(), &*missing);
+
+    let err = fs
+        .delete(missing.clone())
+        .with_context(FsOperation::Delete, missing.clone())
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    assert!(err.to_string().starts_with("delete "));
+
+    // the default stays a bare error
+    let err = fs
+        .read(missing.clone(), Default::default())
+        .collect()
+        .wait()
+        .unwrap_err();
+    assert!(FsError::from_io(&err).is_none());
+}
+
+#[test]
+fn test_cancel() {
+    use futures::future::{ExecuteError, Executor};
+    use futures::Poll;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    type Task = Box<dyn Future<Item = (), Error = ()> + Send>;
+
+    // counts every poll of the tasks it runs, each on its own thread
+    struct Counting(Arc<AtomicUsize>);
+
+    struct Counted(Task, Arc<AtomicUsize>);
+
+    impl Future for Counted {
+        type Item = ();
+        type Error = ();
+
+        fn poll(&mut self) -> Poll<(), ()> {
+            self.1.fetch_add(1, Ordering::SeqCst);
+            self.0.poll()
+        }
+    }
+
+    impl Executor<Task> for Counting {
+        fn execute(&self, future: Task) -> Result<(), ExecuteError<Task>> {
+            let counted = Counted(future, self.0.clone());
+            thread::spawn(move || counted.wait());
+            Ok(())
+        }
+    }
+
+    let polls = Arc::new(AtomicUsize::new(0));
+    let fs = FsPool::with_executor(Counting(polls.clone()));
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-cancel");
+    fs::write(&tmp, vec![0u8; 1024 * 1024]).unwrap();
+
+    // without reading ahead, the worker is polled again for every chunk
+    let stream = fs.read(tmp.clone(), ReadOptions::default().buffer_size(4096));
+    let (chunk, stream) = stream.into_future().wait().map_err(|(e, _)| e).unwrap();
+    assert_eq!(chunk.unwrap().len(), 4096);
+    drop(stream);
+
+    thread::sleep(Duration::from_millis(100));
+    let stopped = polls.load(Ordering::SeqCst);
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(polls.load(Ordering::SeqCst), stopped);
+    assert!(stopped < 8, "polled {} times", stopped);
+
+    let fs = FsPool::default();
+    let stream = fs.read(tmp.clone(), ReadOptions::default().buffer_size(4096));
+    let cancel = stream.cancel_handle();
+    let (_, stream) = stream.into_future().wait().map_err(|(e, _)| e).unwrap();
+    cancel.cancel();
+    assert!(cancel.is_canceled());
+    assert!(stream.collect().wait().is_err());
+
+    let sink = fs.write(tmp.clone(), Default::default());
+    sink.cancel_handle().cancel();
+    assert!(sink.send("hello".into()).wait().is_err());
+
+    fs::remove_file(&tmp).unwrap();
+}
+
+#[test]
+fn test_timeout() {
+    use futures::future::{ExecuteError, Executor};
+    use futures::sync::oneshot;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    type Task = Box<dyn Future<Item = (), Error = ()> + Send>;
+
+    // the timer of the caller
+    fn sleep(duration: Duration) -> oneshot::Receiver<()> {
+        let (tx, rx) = oneshot::channel();
+        thread::spawn(move || {
+            thread::sleep(duration);
+            let _ = tx.send(());
+        });
+        rx
+    }
+
+    // holds on to every task without running it, like a hung pool
+    #[derive(Clone, Default)]
+    struct Stalled(Arc<Mutex<Vec<Task>>>);
+
+    impl Executor<Task> for Stalled {
+        fn execute(&self, future: Task) -> Result<(), ExecuteError<Task>> {
+            self.0.lock().unwrap().push(future);
+            Ok(())
+        }
+    }
+
+    let stalled = Stalled::default();
+    let fs = FsPool::with_executor(stalled.clone());
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-timeout");
+    fs::write(&tmp, b"hello").unwrap();
+
+    let timeout = Duration::from_millis(50);
+    let err = fs
+        .delete(tmp.clone())
+        .timeout(timeout, sleep)
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+    // the work is skipped once the pool gets to it
+    for task in stalled.0.lock().unwrap().drain(..) {
+        let _ = task.wait();
+    }
+    assert!(tmp.exists());
+
+    let opts = ReadOptions::default().chunk_timeout(timeout, sleep);
+    let err = fs.read(tmp.clone(), opts).collect().wait().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+    let opts = WriteOptions::default().timeout(timeout, sleep);
+    let err = fs
+        .write(tmp.clone(), opts)
+        .send("hello".into())
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+    // a followed file that stops growing times out once its chunks run out,
+    // with the pool waiting on the same timer in between checks
+    let fs = FsPool::default().with_timer(sleep);
+    let opts = ReadOptions::default()
+        .follow(true)
+        .chunk_timeout(timeout, sleep);
+    let (chunk, stream) = fs
+        .read(tmp.clone(), opts)
+        .into_future()
+        .wait()
+        .map_err(|(e, _)| e)
+        .unwrap();
+    assert_eq!(&chunk.unwrap()[..], b"hello");
+    let err = stream.collect().wait().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+    fs::remove_file(&tmp).unwrap();
+}
+
+#[test]
+fn test_close() {
+    let fs = FsPool::default();
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-close");
+    let _ = fs::remove_dir_all(&tmp);
+    fs::create_dir_all(&tmp).unwrap();
+
+    let data = vec![7u8; 256 * 1024];
+    let mut writes = Vec::new();
+    for i in 0..8 {
+        let chunks = data.chunks(4096).map(|chunk| chunk.to_vec().into());
+        let bytes = futures::stream::iter_ok::<_, io::Error>(chunks.collect::<Vec<_>>());
+        let sink = fs.write(tmp.join(i.to_string()), Default::default());
+        writes.push(bytes.forward(sink));
+    }
+    // the writes run on another thread, while the pool is closed
+    let writer = std::thread::spawn(move || futures::future::join_all(writes).wait());
+
+    let clone = fs.clone();
+    clone.close().wait().unwrap();
+    writer.join().unwrap().unwrap();
+    for i in 0..8 {
+        assert_eq!(fs::read(tmp.join(i.to_string())).unwrap(), data);
+    }
+
+    // every clone is closed
+    let err = fs.delete(tmp.join("0")).wait().unwrap_err();
+    assert_eq!(err.to_string(), "fs pool is closed");
+    assert!(tmp.join("0").exists());
+
+    fs::remove_dir_all(&tmp).unwrap();
+}
+
+#[test]
+fn test_metrics() {
+    let fs = FsPool::default();
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-metrics");
+    let _ = fs::remove_file(&tmp);
+
+    let metrics = fs.metrics();
+    assert_eq!(metrics.total(), FsOpCounts::default());
+    assert_eq!(metrics.in_flight(), 0);
+
+    let sink = fs.write(tmp.clone(), Default::default());
+    let _ = futures::stream::iter_ok::<_, io::Error>(vec!["hello".into(), " world".into()])
+        .forward(sink)
+        .wait()
+        .unwrap();
+    let data = fs
+        .read(tmp.clone(), Default::default())
+        .concat2()
+        .wait()
+        .unwrap();
+    assert_eq!(&data[..], b"hello world");
+    fs.metadata(tmp.clone()).wait().unwrap();
+    // setting the metadata counts like getting it
+    fs.set_len(tmp.clone(), 11).wait().unwrap();
+    // opening a file counts by whether it is opened for writing
+    let mut open = fs::OpenOptions::new();
+    fs.open(tmp.clone(), open.read(true).clone())
+        .wait()
+        .unwrap();
+    fs.open(tmp.clone(), open.write(true).clone())
+        .wait()
+        .unwrap();
+    // a copy counts the bytes it reads and writes
+    let mut copy = env::temp_dir();
+    copy.push("futures-fs-metrics-copy");
+    fs.copy_with(tmp.clone(), copy.clone(), Default::default())
+        .wait()
+        .unwrap();
+    fs::remove_file(copy).unwrap();
+    fs.delete(tmp.clone()).wait().unwrap();
+    // a clone shares the counters
+    fs.clone().delete(tmp.clone()).wait().unwrap_err();
+    // the tasks of the streams may still be finishing
+    fs.close().wait().unwrap();
+
+    let metrics = fs.metrics();
+    assert_eq!(metrics.bytes_written(), 22);
+    assert_eq!(metrics.bytes_read(), 22);
+    assert_eq!(metrics.operation(FsOperation::Write).completed(), 2);
+    assert_eq!(metrics.operation(FsOperation::Read).completed(), 2);
+    assert_eq!(metrics.operation(FsOperation::Metadata).completed(), 2);
+    let deletes = metrics.operation(FsOperation::Delete);
+    assert_eq!(
+        (deletes.started(), deletes.completed(), deletes.failed()),
+        (2, 1, 1)
+    );
+    assert_eq!(metrics.total().started(), 9);
+    assert_eq!(metrics.in_flight(), 0);
+    assert_eq!(metrics.queued(), 0);
+}
+
+#[test]
+fn test_builder() {
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    let names = Arc::new(Mutex::new(Vec::new()));
+    let started = names.clone();
+    let fs = FsPool::builder()
+        .pool_size(2)
+        .name_prefix("fs-test-")
+        .stack_size(4 * 1024 * 1024)
+        .after_start(move || {
+            let name = thread::current().name().map(String::from);
+            started.lock().unwrap().push(name);
+        })
+        .build();
+
+    let name = fs
+        .spawn_fn(|| Ok(thread::current().name().map(String::from)))
+        .wait()
+        .unwrap();
+    assert!(name.unwrap().starts_with("fs-test-"));
+
+    // the other thread may still be starting
+    for _ in 0..100 {
+        if names.lock().unwrap().len() == 2 {
+            break;
+        }
+        thread::sleep(std::time::Duration::from_millis(10));
+    }
+    let mut names = names.lock().unwrap().clone();
+    names.sort();
+    assert_eq!(
+        names,
+        vec![Some("fs-test-0".to_owned()), Some("fs-test-1".to_owned())]
+    );
+}
+
+#[test]
+#[should_panic(expected = "pool size must be larger than 0")]
+fn test_zero_threads() {
+    FsPool::new(0);
+}
+
+#[test]
+fn test_priority() {
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    let fs = FsPool::new(1);
+    let low = fs.with_priority(OpPriority::Low);
+    let order = Arc::new(Mutex::new(Vec::new()));
+
+    let mut futures = Vec::new();
+    for i in 0..20 {
+        let order = order.clone();
+        futures.push(low.spawn_fn(move || {
+            thread::sleep(Duration::from_millis(5));
+            order.lock().unwrap().push(i);
+            Ok(())
+        }));
+    }
+    let high = {
+        let order = order.clone();
+        fs.with_priority(OpPriority::High).spawn_fn(move || {
+            order.lock().unwrap().push(100);
+            Ok(())
+        })
+    };
+    futures.push(high);
+    futures::future::join_all(futures).wait().unwrap();
+
+    let order = order.lock().unwrap();
+    let position = order.iter().position(|&i| i == 100).unwrap();
+    assert!(position < 5, "ran at {} of {:?}", position, order);
+    // the low operations still ran in the order they were queued
+    let lows = order
+        .iter()
+        .filter(|&&i| i != 100)
+        .cloned()
+        .collect::<Vec<_>>();
+    assert_eq!(lows, (0..20).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_rate_limit() {
+    use std::time::{Duration, Instant};
+
+    let fs = FsPool::builder().max_bytes_per_sec(1 << 20).build();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-rate-limit");
+
+    // the bucket starts empty, so both take about a quarter of a second
+    let start = Instant::now();
+    fs.write_all(tmp.clone(), vec![7; 256 << 10].into(), Default::default())
+        .wait()
+        .unwrap();
+    let written = start.elapsed();
+
+    let start = Instant::now();
+    let len = fs
+        .read(tmp.clone(), ReadOptions::default().buffer_size(16 << 10))
+        .fold(0, |len, chunk| Ok::<_, io::Error>(len + chunk.len()))
+        .wait()
+        .unwrap();
+    let read = start.elapsed();
+    assert_eq!(len, 256 << 10);
+
+    // copies are limited as well
+    let mut copy = env::temp_dir();
+    copy.push("futures-fs-rate-limit-copy");
+    let start = Instant::now();
+    fs.copy_with(tmp.clone(), copy.clone(), Default::default())
+        .wait()
+        .unwrap();
+    let copied = start.elapsed();
+    fs::remove_file(copy).unwrap();
+
+    for elapsed in &[written, read, copied] {
+        assert!(*elapsed >= Duration::from_millis(200), "{:?}", elapsed);
+        assert!(*elapsed < Duration::from_secs(2), "{:?}", elapsed);
+    }
+
+    // a limit of the stream's own replaces that of the pool
+    let start = Instant::now();
+    fs.read(
+        tmp.clone(),
+        ReadOptions::default().max_bytes_per_sec(u64::MAX),
+    )
+    .for_each(|_| Ok(()))
+    .wait()
+    .unwrap();
+    assert!(start.elapsed() < Duration::from_millis(200));
+
+    fs::remove_file(tmp).unwrap();
+}
+
+#[cfg(feature = "tokio-io")]
+#[test]
+fn test_async_read() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-async-read");
+    let data = (0..10_000).map(|i| i as u8).collect::<Vec<_>>();
+    fs::write(&tmp, &data).unwrap();
+
+    let reader = fs
+        .read(tmp.clone(), ReadOptions::default().buffer_size(1000))
+        .into_async_read();
+    let (_, read) = tokio_io::io::read_to_end(reader, Vec::new())
+        .wait()
+        .unwrap();
+    assert_eq!(read, data);
+
+    fs::remove_file(&tmp).unwrap();
+
+    let reader = fs.read(tmp, Default::default()).into_async_read();
+    let e = tokio_io::io::read_to_end(reader, Vec::new())
+        .wait()
+        .unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::NotFound);
+}
+
+#[cfg(feature = "tokio-io")]
+#[test]
+fn test_async_write() {
+    let fs = FsPool::default();
+
+    let mut from = env::temp_dir();
+    from.push("futures-fs-async-write-from");
+    let mut to = env::temp_dir();
+    to.push("futures-fs-async-write-to");
+    let data = (0..100_000).map(|i| i as u8).collect::<Vec<_>>();
+    fs::write(&from, &data).unwrap();
+
+    let reader = fs.read(from.clone(), Default::default()).into_async_read();
+    let writer = fs.write(to.clone(), Default::default()).into_async_write();
+    let (copied, _, writer) = tokio_io::io::copy(reader, writer).wait().unwrap();
+    assert_eq!(copied, data.len() as u64);
+    let writer = tokio_io::io::shutdown(writer).wait().unwrap();
+    assert_eq!(writer.written(), data.len() as u64);
+    assert_eq!(fs::read(&to).unwrap(), data);
+
+    fs::remove_file(&from).unwrap();
+    fs::remove_file(&to).unwrap();
+
+    // the failure to open the file is returned by a later write or flush
+    let mut missing = env::temp_dir();
+    missing.push("futures-fs-async-write-missing");
+    missing.push("file");
+    let writer = fs.write(missing, Default::default()).into_async_write();
+    let e = tokio_io::io::write_all(writer, b"hello")
+        .and_then(|(writer, _)| tokio_io::io::flush(writer))
+        .wait()
+        .unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_blocking_io() {
+    let fs = FsPool::default();
+
+    let mut from = env::temp_dir();
+    from.push("futures-fs-blocking-from");
+    let mut to = env::temp_dir();
+    to.push("futures-fs-blocking-to");
+    let data = (0..100_000).map(|i| i as u8).collect::<Vec<_>>();
+    fs::write(&from, &data).unwrap();
+
+    let mut reader = fs
+        .read(from.clone(), ReadOptions::default().buffer_size(4096))
+        .into_blocking_read();
+    let mut writer = fs
+        .write(to.clone(), Default::default())
+        .into_blocking_write();
+    let copied = io::copy(&mut reader, &mut writer).unwrap();
+    assert_eq!(copied, data.len() as u64);
+    writer.close().unwrap();
+    assert_eq!(fs::read(&to).unwrap(), data);
+    fs::remove_file(&to).unwrap();
+
+    // dropping the writer still finishes the file
+    {
+        let mut writer = fs
+            .write(to.clone(), Default::default())
+            .into_blocking_write();
+        io::Write::write_all(&mut writer, b"hello").unwrap();
+    }
+    assert_eq!(fs::read(&to).unwrap(), &b"hello"[..]);
+
+    fs::remove_file(&from).unwrap();
+    fs::remove_file(&to).unwrap();
+
+    let mut reader = fs.read(from, Default::default()).into_blocking_read();
+    let e = io::copy(&mut reader, &mut io::sink()).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::NotFound);
+}
+
+#[cfg(feature = "std-future")]
+#[test]
+fn test_std_future() {
+    use futures_core::Stream as Stream03;
+    use futures_sink::Sink as Sink03;
+    use std::future::{poll_fn, Future as StdFuture};
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake};
+    use std::thread::{self, Thread};
+
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: StdFuture>(fut: F) -> F::Output {
+        let waker = Arc::new(Unpark(thread::current())).into();
+        let mut cx = Context::from_waker(&waker);
+        let mut fut = Box::pin(fut);
+        loop {
+            match fut.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-std-future");
+
+    let mut sink = fs.write03(tmp.clone(), Default::default());
+    for piece in &["hello", " ", "world"] {
+        block_on(poll_fn(|cx| Pin::new(&mut sink).poll_ready(cx))).unwrap();
+        Pin::new(&mut sink)
+            .start_send(piece.as_bytes().into())
+            .unwrap();
+    }
+    block_on(poll_fn(|cx| Pin::new(&mut sink).poll_close(cx))).unwrap();
+
+    let mut stream = fs.read03(tmp.clone(), ReadOptions::default().buffer_size(4));
+    let mut data = Vec::new();
+    while let Some(chunk) = block_on(poll_fn(|cx| Pin::new(&mut stream).poll_next(cx))) {
+        data.extend_from_slice(&chunk.unwrap());
+    }
+    assert_eq!(data, b"hello world");
+
+    block_on(fs.delete03(tmp.clone())).unwrap();
+    let e = block_on(fs.delete03(tmp)).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::NotFound);
+}
+
+#[cfg(feature = "tokio-blocking")]
+#[test]
+fn test_tokio_blocking() {
+    use futures::future::lazy;
+    use std::thread;
+    use tokio_executor::{Executor, SpawnError};
+
+    let fs = FsPool::with_tokio_blocking();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-tokio-blocking");
+
+    let pool = tokio_threadpool::ThreadPool::new();
+    let (fs2, path) = (fs.clone(), tmp.clone());
+    let data = pool
+        .spawn_handle(lazy(move || {
+            fs2.write_all(path.clone(), "hello".into(), Default::default())
+                .and_then(move |()| fs2.read(path, Default::default()).concat2())
+        }))
+        .wait()
+        .unwrap();
+    assert_eq!(data, "hello");
+
+    // the threads of this executor are no tokio threadpool to block in
+    struct Threads;
+
+    impl Executor for Threads {
+        fn spawn(
+            &mut self,
+            fut: Box<dyn Future<Item = (), Error = ()> + Send>,
+        ) -> Result<(), SpawnError> {
+            thread::spawn(move || fut.wait());
+            Ok(())
+        }
+    }
+
+    let mut enter = tokio_executor::enter().unwrap();
+    let e = tokio_executor::with_default(&mut Threads, &mut enter, |_| {
+        fs.read_to_end(tmp.clone(), None).wait()
+    })
+    .unwrap_err();
+    assert!(e.to_string().contains("tokio threadpool"), "{}", e);
+
+    fs::remove_file(tmp).unwrap();
+}
+
+#[cfg(feature = "hash")]
+#[test]
+fn test_hash() {
+    use futures_fs::HashAlgorithm;
+
+    fn hex(digest: Vec<u8>) -> String {
+        digest.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-hash");
+    fs::write(&tmp, "hello world").unwrap();
+
+    let sha256 = fs.hash(tmp.clone(), HashAlgorithm::Sha256, None).wait();
+    assert_eq!(
+        hex(sha256.unwrap()),
+        "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+    );
+    let sha1 = fs.hash(tmp.clone(), HashAlgorithm::Sha1, None).wait();
+    assert_eq!(
+        hex(sha1.unwrap()),
+        "2aae6c35c94fcfb415dbe95f408b9ce91ee846ed"
+    );
+
+    // a range over several reads of the buffer
+    let data = (0..200_000).map(|i| i as u8).collect::<Vec<_>>();
+    fs::write(&tmp, &data).unwrap();
+    let range = fs.hash(tmp.clone(), HashAlgorithm::Sha256, Some(1000..150_000));
+    assert_eq!(
+        hex(range.wait().unwrap()),
+        "73313a8c1f85fd6bd0e65c5669f7eab33c462089bcb840a0eedc3fd5df45f6aa"
+    );
+
+    fs::remove_file(&tmp).unwrap();
+    let e = fs
+        .hash(tmp, HashAlgorithm::Sha256, None)
+        .wait()
+        .unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::NotFound);
+}
+
+#[cfg(feature = "hash")]
+#[test]
+fn test_write_checksum() {
+    use futures_fs::HashAlgorithm;
+
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-write-checksum");
+    let _ = fs::remove_file(&tmp);
+
+    let chunks = vec!["hel", "lo", " ", "wor", "ld"];
+    let bytes = futures::stream::iter_ok::<_, io::Error>(chunks.into_iter().map(|c| c.into()));
+    // the small chunks are coalesced into the buffer before being written
+    let opts = WriteOptions::default()
+        .checksum(HashAlgorithm::Sha256)
+        .buffer_size(4);
+    let (_, sink) = bytes.forward(fs.write(tmp.clone(), opts)).wait().unwrap();
+    let digest = sink.close_with_checksum().wait().unwrap();
+    // the reference digest of "hello world"
+    let hashed = fs.hash(tmp.clone(), HashAlgorithm::Sha256, None).wait();
+    assert_eq!(digest, hashed.unwrap());
+    assert_eq!(digest[..4], [0xb9, 0x4d, 0x27, 0xb9]);
+
+    // closing as a sink keeps the digest on it
+    fs::remove_file(&tmp).unwrap();
+    let opts = WriteOptions::default().checksum(HashAlgorithm::Sha1);
+    let mut sink = fs.write(tmp.clone(), opts);
+    sink = sink.send("hello world".into()).wait().unwrap();
+    futures::future::poll_fn(|| sink.close()).wait().unwrap();
+    assert_eq!(sink.checksum().unwrap()[..4], [0x2a, 0xae, 0x6c, 0x35]);
+
+    let sink = fs.write(tmp.clone(), Default::default());
+    let e = sink.close_with_checksum().wait().unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+
+    fs::remove_file(tmp).unwrap();
+}
+
+#[test]
+fn test_compare() {
+    let fs = FsPool::default();
+
+    let mut a = env::temp_dir();
+    a.push("futures-fs-compare-a");
+    let mut b = env::temp_dir();
+    b.push("futures-fs-compare-b");
+
+    // both empty
+    fs::write(&a, "").unwrap();
+    fs::write(&b, "").unwrap();
+    assert!(fs.compare(a.clone(), b.clone()).wait().unwrap());
+
+    let data = (0..200_000).map(|i| i as u8).collect::<Vec<_>>();
+    fs::write(&a, &data).unwrap();
+    fs::write(&b, &data).unwrap();
+    assert!(fs.compare(a.clone(), b.clone()).wait().unwrap());
+    let diff = fs.first_difference(a.clone(), b.clone()).wait();
+    assert_eq!(diff.unwrap(), None);
+
+    let mut changed = data.clone();
+    changed[150_000] ^= 1;
+    fs::write(&b, &changed).unwrap();
+    assert!(!fs.compare(a.clone(), b.clone()).wait().unwrap());
+    let diff = fs.first_difference(a.clone(), b.clone()).wait();
+    assert_eq!(diff.unwrap(), Some(150_000));
+
+    // a prefix differs where it ends
+    fs::write(&b, &data[..100_000]).unwrap();
+    assert!(!fs.compare(a.clone(), b.clone()).wait().unwrap());
+    let diff = fs.first_difference(a.clone(), b.clone()).wait();
+    assert_eq!(diff.unwrap(), Some(100_000));
+
+    fs::remove_file(&b).unwrap();
+    let e = fs.compare(a.clone(), b.clone()).wait().unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::NotFound);
+    fs::remove_file(&a).unwrap();
+    let e = fs.compare(a, b).wait().unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_read_concat() {
+    let fs = FsPool::default();
+
+    let parts = (0..3)
+        .map(|i| {
+            let mut part = env::temp_dir();
+            part.push(format!("futures-fs-concat-{}", i));
+            fs::write(&part, format!("part {};", i)).unwrap();
+            part
+        })
+        .collect::<Vec<_>>();
+
+    let chunks = fs
+        .read_concat(parts.clone(), ReadOptions::default().buffer_size(4))
+        .with_offsets()
+        .collect()
+        .wait()
+        .unwrap();
+    let mut data = Vec::new();
+    for (offset, chunk) in chunks {
+        // the offsets continue over the files
+        assert_eq!(offset, data.len() as u64);
+        data.extend_from_slice(&chunk);
+    }
+    assert_eq!(data, b"part 0;part 1;part 2;");
+
+    // a missing part fails the stream once it is reached
+    let mut missing = parts.clone();
+    missing.insert(1, env::temp_dir().join("futures-fs-concat-missing"));
+    let mut stream = fs.read_concat(missing.clone(), Default::default()).wait();
+    assert_eq!(stream.next().unwrap().unwrap(), "part 0;");
+    let e = stream.next().unwrap().unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::NotFound);
+    assert!(stream.next().is_none());
+
+    // or before anything is read, with eager_open
+    let mut stream = fs
+        .read_concat(missing, Default::default())
+        .eager_open(true)
+        .wait();
+    let e = stream.next().unwrap().unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::NotFound);
+
+    for part in parts {
+        fs::remove_file(part).unwrap();
+    }
+}
+
+#[test]
+fn test_read_many() {
+    let fs = FsPool::new(3);
+
+    let mut paths = (0..8)
+        .map(|i| {
+            let mut path = env::temp_dir();
+            path.push(format!("futures-fs-many-{}", i));
+            fs::write(&path, vec![b'a'; i * 10]).unwrap();
+            path
+        })
+        .collect::<Vec<_>>();
+    paths.insert(3, env::temp_dir().join("futures-fs-many-missing"));
+
+    // every file is yielded, in any order
+    let mut read = fs.read_many(paths.clone()).collect().wait().unwrap();
+    assert_eq!(read.len(), paths.len());
+    read.sort_by(|a, b| a.0.cmp(&b.0));
+    for (path, res) in read {
+        if path.ends_with("futures-fs-many-missing") {
+            assert_eq!(res.unwrap_err().kind(), io::ErrorKind::NotFound);
+        } else {
+            assert_eq!(res.unwrap(), fs::read(&path).unwrap());
+        }
+    }
+
+    // or in the order of the paths, with files over max_size failing
+    let read = fs
+        .read_many(paths.clone())
+        .ordered(true)
+        .max_size(40)
+        .collect()
+        .wait()
+        .unwrap();
+    let order = read.iter().map(|r| r.0.clone()).collect::<Vec<_>>();
+    assert_eq!(order, paths);
+    assert!(read[3].1.is_err());
+    assert_eq!(read[5].1.as_ref().unwrap().len(), 40);
+    assert!(read[6].1.is_err());
+
+    for path in paths {
+        let _ = fs::remove_file(path);
+    }
+}
+
+#[test]
+fn test_write_stream() {
+    let fs = FsPool::default();
+
+    let mut path = env::temp_dir();
+    path.push("futures-fs-write-stream");
+    let _ = fs::remove_file(&path);
+
+    let chunks = (0..100).map(|i| Ok(vec![i as u8; 100].into()));
+    let written = fs
+        .write_stream(
+            path.clone(),
+            stream::iter_result(chunks),
+            WriteOptions::default(),
+        )
+        .wait()
+        .unwrap();
+    assert_eq!(written, 10_000);
+    let data = fs::read(&path).unwrap();
+    assert_eq!(data.len(), 10_000);
+    assert_eq!(data[9_999], 99);
+
+    // an error of the stream is passed through as is
+    let chunks = vec![Ok(b"ok".to_vec().into()), Err(io::Error::other("stream"))];
+    let e = fs
+        .write_stream(
+            path.clone(),
+            stream::iter_result(chunks),
+            Default::default(),
+        )
+        .wait()
+        .unwrap_err();
+    assert!(FsError::from_io(&e).is_none());
+    assert_eq!(e.to_string(), "stream");
+
+    // while one of the file names the path
+    let opts = WriteOptions::from(fs::OpenOptions::new().write(true).create_new(true).clone());
+    let e = fs
+        .write_stream(path.clone(), stream::empty(), opts)
+        .wait()
+        .unwrap_err();
+    let fs_error = FsError::from_io(&e).unwrap();
+    assert_eq!(fs_error.operation(), FsOperation::Write);
+    assert_eq!(fs_error.path(), &*path);
+    assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_pipe() {
+    use std::io::{Seek, SeekFrom};
+
+    let fs = FsPool::default();
+
+    let mut from = env::temp_dir();
+    from.push("futures-fs-pipe-from");
+    let mut to = env::temp_dir();
+    to.push("futures-fs-pipe-to");
+    let data = (0..100_000u32).map(|i| i as u8).collect::<Vec<_>>();
+    fs::write(&from, &data).unwrap();
+    let _ = fs::remove_file(&to);
+
+    let piped = fs
+        .pipe(from.clone(), to.clone(), Default::default())
+        .wait()
+        .unwrap();
+    assert_eq!(piped, data.len() as u64);
+    assert_eq!(fs::read(&to).unwrap(), data);
+
+    // a range of the source, appended to the destination
+    let opts = PipeOptions::default()
+        .with_read_options(ReadOptions::default().range(1000, 500).buffer_size(64))
+        .with_write_options(WriteOptions::default().append(true));
+    let piped = fs.pipe(from.clone(), to.clone(), opts).wait().unwrap();
+    assert_eq!(piped, 500);
+    let written = fs::read(&to).unwrap();
+    assert_eq!(&written[data.len()..], &data[1000..1500]);
+
+    // or between already opened files, from their positions
+    let mut src = fs::File::open(&from).unwrap();
+    src.seek(SeekFrom::Start(99_000)).unwrap();
+    let dst = fs::File::create(&to).unwrap();
+    let piped = fs.pipe_files(src, dst).wait().unwrap();
+    assert_eq!(piped, 1000);
+    assert_eq!(fs::read(&to).unwrap(), &data[99_000..]);
+
+    fs::remove_file(from).unwrap();
+    fs::remove_file(to).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_no_follow() {
+    use std::os::unix::fs::symlink;
+
+    let fs = FsPool::default();
+
+    let mut dir = env::temp_dir();
+    dir.push("futures-fs-no-follow");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir(&dir).unwrap();
+    let mut outside = env::temp_dir();
+    outside.push("futures-fs-no-follow-outside");
+    fs::write(&outside, b"secret").unwrap();
+    let link = dir.join("link");
+    symlink(&outside, &link).unwrap();
+
+    // the link is followed by default
+    let data = fs
+        .read(link.clone(), Default::default())
+        .concat2()
+        .wait()
+        .unwrap();
+    assert_eq!(data, b"secret"[..]);
+
+    let opts = ReadOptions::default().no_follow(true);
+    let e = fs.read(link.clone(), opts).concat2().wait().unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+    assert!(e.to_string().contains("symbolic link"));
+
+    let opts = WriteOptions::default().no_follow(true);
+    let e = fs
+        .write(link.clone(), opts)
+        .send(b"overwritten".to_vec().into())
+        .wait()
+        .unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+    assert_eq!(fs::read(&outside).unwrap(), b"secret");
+
+    // a regular file is still opened
+    let file = dir.join("file");
+    fs::write(&file, b"ok").unwrap();
+    let opts = ReadOptions::default().no_follow(true).regular_only(true);
+    let data = fs.read(file, opts).concat2().wait().unwrap();
+    assert_eq!(data, b"ok"[..]);
+
+    // while a device isn't
+    let opts = ReadOptions::default().regular_only(true);
+    let e = fs.read("/dev/null", opts).concat2().wait().unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+    let opts = WriteOptions::default().regular_only(true);
+    let e = fs
+        .write("/dev/null", opts)
+        .send(b"data".to_vec().into())
+        .wait()
+        .unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+
+    fs::remove_dir_all(dir).unwrap();
+    fs::remove_file(outside).unwrap();
+}
+
+#[test]
+fn test_open_file() {
+    let fs = FsPool::new(4);
+
+    let mut path = env::temp_dir();
+    path.push("futures-fs-open-file");
+    let data = (0..64 * 1024u32)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<_>>();
+    fs::write(&path, &data).unwrap();
+
+    let mut opts = fs::OpenOptions::new();
+    opts.read(true).write(true);
+    let file = fs.open(path.clone(), opts).wait().unwrap();
+    assert_eq!(file.len().wait().unwrap(), data.len() as u64);
+
+    // overlapping reads, all at once from clones of the handle
+    let reads = (0..32u64)
+        .map(|i| {
+            let offset = i * 1500;
+            file.clone()
+                .read_at(offset, 4096)
+                .map(move |bytes| (offset, bytes))
+        })
+        .collect::<Vec<_>>();
+    for (offset, bytes) in future::join_all(reads).wait().unwrap() {
+        let start = offset as usize;
+        assert_eq!(&bytes[..], &data[start..start + 4096]);
+    }
+
+    // a read past the end is short
+    let end = file.read_at(data.len() as u64 - 10, 100).wait().unwrap();
+    assert_eq!(&end[..], &data[data.len() - 10..]);
+
+    let writes = (0..4u64)
+        .map(|i| file.write_at(i * 10, vec![b'a' + i as u8; 10].into()))
+        .collect::<Vec<_>>();
+    let written = future::join_all(writes).wait().unwrap();
+    assert_eq!(written, vec![10; 4]);
+    file.sync_all().wait().unwrap();
+    let start = file.read_at(0, 40).wait().unwrap();
+    assert_eq!(&start[..], &b"aaaaaaaaaabbbbbbbbbbccccccccccdddddddddd"[..]);
+
+    drop(file);
+    fs::remove_file(path).unwrap();
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_mmap_read() {
+    let fs = FsPool::default();
+
+    let mut path = env::temp_dir();
+    path.push("futures-fs-mmap-read");
+    let data = (0..100_000u32).map(|i| i as u8).collect::<Vec<_>>();
+    fs::write(&path, &data).unwrap();
+
+    let map = fs.mmap_read(path.clone()).wait().unwrap();
+    assert_eq!(&map[..], &data[..]);
+
+    // the stream reads a range of the map, in chunks of the buffer size
+    let opts = ReadOptions::default().range(1000, 20_000).buffer_size(4096);
+    let chunks = map.into_read_stream(opts).collect().wait().unwrap();
+    assert_eq!(chunks[0].len(), 4096);
+    assert_eq!(chunks.concat(), &data[1000..21_000]);
+
+    // an empty file maps to no bytes
+    fs::write(&path, b"").unwrap();
+    let map = fs.mmap_read(path.clone()).wait().unwrap();
+    assert!(map.is_empty());
+    assert!(map
+        .into_read_stream(Default::default())
+        .collect()
+        .wait()
+        .unwrap()
+        .is_empty());
+
+    fs::remove_file(path).unwrap();
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_mmap_write() {
+    let fs = FsPool::default();
+
+    let mut path = env::temp_dir();
+    path.push("futures-fs-mmap-write");
+    fs::write(&path, b"old contents, longer than the map").unwrap();
+
+    let map = fs.mmap_write(path.clone(), 16).wait().unwrap();
+    assert_eq!(map.len(), 16);
+    map.write_at(0, b"ring").unwrap();
+    map.write_at(12, b"end!").unwrap();
+
+    // a write past the end fails, without writing anything
+    let e = map.write_at(14, b"over").unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+    assert!(map.write_at(u64::MAX, b"x").is_err());
+    assert!(map.flush(Some(8..17)).wait().is_err());
+
+    map.flush(Some(0..4)).wait().unwrap();
+    map.flush(None).wait().unwrap();
+
+    let data = fs
+        .read(path.clone(), Default::default())
+        .concat2()
+        .wait()
+        .unwrap();
+    assert_eq!(&data[..], &b"ring\0\0\0\0\0\0\0\0end!"[..]);
+
+    drop(map);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_copy_to_writer() {
+    #[derive(Debug)]
+    struct Broken;
+
+    impl io::Write for Broken {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("broken"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let fs = FsPool::default();
+
+    let mut path = env::temp_dir();
+    path.push("futures-fs-copy-to-writer");
+    let data = (0..100_000u32).map(|i| i as u8).collect::<Vec<_>>();
+    fs::write(&path, &data).unwrap();
+
+    let (copied, out) = fs.copy_to_writer(path.clone(), Vec::new()).wait().unwrap();
+    assert_eq!(copied, data.len() as u64);
+    assert_eq!(out, data);
+
+    // the errors of the writer aren't those of the file
+    let err = fs.copy_to_writer(path.clone(), Broken).wait().unwrap_err();
+    assert!(FsError::from_io(&err).is_none());
+    assert!(err.to_string().contains("writer"), "{}", err);
+
+    let mut missing = env::temp_dir();
+    missing.push("futures-fs-copy-to-writer-missing");
+    let err = fs.copy_to_writer(missing, Vec::new()).wait().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    let fs_err = FsError::from_io(&err).unwrap();
+    assert_eq!(fs_err.operation(), FsOperation::Read);
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_write_item_type() {
+    let fs = FsPool::default();
+
+    let mut path = env::temp_dir();
+    path.push("futures-fs-write-item-type");
+    let mut cow_path = env::temp_dir();
+    cow_path.push("futures-fs-write-item-type-cow");
+
+    let sink = fs
+        .write(path.clone(), Default::default())
+        .with_item_type::<Vec<u8>>();
+    let chunks = vec![b"hello".to_vec(), Vec::new(), b" world".to_vec()];
+    let (_, sink) = stream::iter_ok::<_, io::Error>(chunks)
+        .forward(sink)
+        .wait()
+        .unwrap();
+    assert_eq!(sink.written(), 11);
+    drop(sink);
+    assert_eq!(fs::read(&path).unwrap(), b"hello world");
+
+    // borrowed and owned sends, collected in the buffer of the sink
+    let sink = fs
+        .write(cow_path.clone(), WriteOptions::default().buffer_size(4))
+        .with_item_type::<Cow<'static, [u8]>>();
+    let chunks = vec![
+        Cow::Borrowed(&b"ab"[..]),
+        Cow::Owned(b"cdefgh".to_vec()),
+        Cow::Borrowed(&b"i"[..]),
+    ];
+    let (_, sink) = stream::iter_ok::<_, io::Error>(chunks)
+        .forward(sink)
+        .wait()
+        .unwrap();
+    assert_eq!(sink.written(), 9);
+    drop(sink);
+    assert_eq!(fs::read(&cow_path).unwrap(), b"abcdefghi");
+
+    fs::remove_file(path).unwrap();
+    fs::remove_file(cow_path).unwrap();
+}
+
+#[test]
+fn test_read_backpressure() {
+    use std::thread;
+    use std::time::Duration;
+
+    const CHUNK: u64 = 4096;
+    const READ_AHEAD: u64 = 2;
+    // the chunks the channel holds, along with the one sent to it last and
+    // the one being read
+    const AHEAD: u64 = READ_AHEAD + 3;
+
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-backpressure");
+    let data = (0..256 * CHUNK as u32)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<_>>();
+    fs::write(&tmp, &data).unwrap();
+
+    let opts = ReadOptions::default()
+        .buffer_size(CHUNK as usize)
+        .read_ahead(READ_AHEAD as usize);
+    let mut stream = fs.read(tmp.clone(), opts).wait();
+
+    // a consumer that hasn't pulled yet only has a few chunks read for it
+    thread::sleep(Duration::from_millis(100));
+    let read = fs.metrics().bytes_read();
+    assert!(read > 0 && read <= AHEAD * CHUNK, "{}", read);
+
+    // and a slow one stays the same few chunks behind
+    let mut received = Vec::new();
+    for pulled in 1..=8 {
+        received.extend_from_slice(&stream.next().unwrap().unwrap());
+        thread::sleep(Duration::from_millis(20));
+        let read = fs.metrics().bytes_read();
+        assert!(
+            read <= (pulled + AHEAD) * CHUNK,
+            "{} after {}",
+            read,
+            pulled
+        );
+    }
+
+    for chunk in stream {
+        received.extend_from_slice(&chunk.unwrap());
+    }
+    assert_eq!(received, data);
+
+    fs::remove_file(tmp).unwrap();
+}
+
+#[test]
+fn test_rename() {
+    let fs = FsPool::default();
+
+    let mut from = env::temp_dir();
+    from.push("futures-fs-rename-from");
+    let mut to = env::temp_dir();
+    to.push("futures-fs-rename-to");
+
+    fs::write(&from, b"new").unwrap();
+    fs::write(&to, b"old").unwrap();
+
+    fs.rename(from.clone(), to.clone()).wait().unwrap();
+    assert_eq!(fs::read(&to).unwrap(), b"new");
+
+    let err = fs.rename(from, to.clone()).wait().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+    fs.delete(to).wait().unwrap();
+}
+
+#[test]
+fn test_copy() {
+    let fs = FsPool::default();
+
+    let mut from = env::temp_dir();
+    from.push("futures-fs-copy-from");
+    let mut to = env::temp_dir();
+    to.push("futures-fs-copy-to");
+
+    fs::write(&from, b"hello world").unwrap();
+
+    let n = fs.copy(from.clone(), to.clone()).wait().unwrap();
+    assert_eq!(n, 11);
+    assert_eq!(fs::read(&to).unwrap(), b"hello world");
+
+    let err = fs.copy(env::temp_dir(), to.clone()).wait().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    fs.delete(from).wait().unwrap();
+    fs.delete(to).wait().unwrap();
+}
+
+#[test]
+fn test_create_dir() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-create-dir");
+
+    fs.create_dir(tmp.clone()).wait().unwrap();
+    assert!(fs.metadata(tmp.clone()).wait().unwrap().is_dir());
+
+    let err = fs.create_dir(tmp.clone()).wait().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+
+    fs::remove_dir(tmp).unwrap();
+}
+
+#[test]
+fn test_create_dir_all() {
+    let fs = FsPool::default();
+
+    let mut root = env::temp_dir();
+    root.push("futures-fs-create-dir-all");
+    let nested = root.join("a").join("b");
+
+    fs.create_dir_all(nested.clone()).wait().unwrap();
+    assert!(fs.metadata(nested).wait().unwrap().is_dir());
+
+    let file = root.join("c").join("d").join("out.txt");
+    let bytes = futures::stream::iter_ok::<_, io::Error>(vec!["hello".into()]);
+    let opts = WriteOptions::default().create_parents(true);
+    let _ = bytes.forward(fs.write(file.clone(), opts)).wait().unwrap();
+    assert_eq!(fs::read(&file).unwrap(), b"hello");
+
+    let err = fs.create_dir_all(file.join("e")).wait().unwrap_err();
+    assert_ne!(err.kind(), io::ErrorKind::NotFound);
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_remove_dir() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-remove-dir");
+
+    fs.create_dir(tmp.clone()).wait().unwrap();
+    fs::write(tmp.join("file"), b"").unwrap();
+
+    assert!(fs.remove_dir(tmp.clone()).wait().is_err());
+
+    fs.delete(tmp.join("file")).wait().unwrap();
+    fs.remove_dir(tmp.clone()).wait().unwrap();
+
+    let err = fs.metadata(tmp).wait().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_remove_dir_all() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-remove-dir-all");
+
+    fs::create_dir_all(tmp.join("a").join("b")).unwrap();
+    fs::create_dir_all(tmp.join("c")).unwrap();
+    fs::write(tmp.join("a").join("one"), b"1").unwrap();
+    fs::write(tmp.join("a").join("b").join("two"), b"2").unwrap();
+
+    fs.remove_dir_all(tmp.clone()).wait().unwrap();
+
+    let err = fs.metadata(tmp).wait().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_read_dir() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-read-dir");
+
+    fs::create_dir_all(tmp.join("sub")).unwrap();
+    for i in 0..100 {
+        fs::write(tmp.join(format!("file-{}", i)), b"").unwrap();
+    }
+
+    let entries = fs.read_dir(tmp.clone()).collect().wait().unwrap();
+    assert_eq!(entries.len(), 101);
+
+    let sub = entries
+        .iter()
+        .find(|entry| entry.file_name() == "sub")
+        .unwrap();
+    assert!(sub.file_type().is_dir());
+    assert_eq!(sub.path(), tmp.join("sub"));
+
+    let err = fs
+        .read_dir(tmp.join("missing"))
+        .collect()
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+    fs.remove_dir_all(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_read_dir_with_metadata() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-read-dir-metadata");
+
+    fs::create_dir_all(tmp.join("sub")).unwrap();
+    for i in 0..100 {
+        fs::write(tmp.join(format!("file-{}", i)), vec![0; i]).unwrap();
+    }
+
+    let opts = ReadDirOptions::default().with_metadata(true);
+    let entries = fs
+        .read_dir_with(tmp.clone(), opts)
+        .collect()
+        .wait()
+        .unwrap();
+    assert_eq!(entries.len(), 101);
+    for entry in &entries {
+        let metadata = entry.metadata().unwrap();
+        assert!(entry.metadata_error().is_none());
+        assert_eq!(metadata.is_dir(), entry.file_name() == "sub");
+        if let Some(i) = entry.file_name().to_str().unwrap().strip_prefix("file-") {
+            assert_eq!(metadata.len(), i.parse::<u64>().unwrap());
+        }
+    }
+
+    let entries = fs.read_dir(tmp.clone()).collect().wait().unwrap();
+    assert!(entries.iter().all(|entry| entry.metadata().is_none()));
+
+    fs.remove_dir_all(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_read_dir_filter() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-read-dir-filter");
+
+    fs::create_dir_all(&tmp).unwrap();
+    for i in 0..10_000 {
+        let ext = if i % 1000 == 0 { "log" } else { "txt" };
+        fs::write(tmp.join(format!("file-{}.{}", i, ext)), b"").unwrap();
+    }
+
+    let opts = ReadDirOptions::default()
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "log"));
+    let mut names = fs
+        .read_dir_with(tmp.clone(), opts)
+        .map(|entry| entry.file_name().to_owned())
+        .collect()
+        .wait()
+        .unwrap();
+    names.sort();
+    let mut expected = (0..10)
+        .map(|i| format!("file-{}.log", i * 1000).into())
+        .collect::<Vec<std::ffi::OsString>>();
+    expected.sort();
+    assert_eq!(names, expected);
+
+    // a panic fails the stream, and leaves the pool working
+    let opts = ReadDirOptions::default().filter(|_| panic!("filter panicked"));
+    assert!(fs
+        .read_dir_with(tmp.clone(), opts)
+        .collect()
+        .wait()
+        .is_err());
+
+    fs.remove_dir_all(tmp).wait().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_symlink() {
+    let fs = FsPool::default();
+
+    let mut src = env::temp_dir();
+    src.push("futures-fs-symlink-src");
+    let mut dst = env::temp_dir();
+    dst.push("futures-fs-symlink-dst");
+
+    fs::write(&src, b"hello").unwrap();
+
+    fs.symlink(src.clone(), dst.clone()).wait().unwrap();
+    assert_eq!(fs::read(&dst).unwrap(), b"hello");
+
+    let err = fs.symlink(src.clone(), dst.clone()).wait().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+
+    fs.delete(dst).wait().unwrap();
+    fs.delete(src).wait().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_read_link() {
+    let fs = FsPool::default();
+
+    let mut link = env::temp_dir();
+    link.push("futures-fs-read-link");
+
+    fs.symlink("some/relative/target", link.clone())
+        .wait()
+        .unwrap();
+
+    let target = fs.read_link(link.clone()).wait().unwrap();
+    assert_eq!(target, std::path::Path::new("some/relative/target"));
+
+    fs.delete(link).wait().unwrap();
+
+    let err = fs.read_link(env::temp_dir()).wait().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_hard_link() {
+    let fs = FsPool::default();
+
+    let mut src = env::temp_dir();
+    src.push("futures-fs-hard-link-src");
+    let mut dst = env::temp_dir();
+    dst.push("futures-fs-hard-link-dst");
+
+    fs::write(&src, b"").unwrap();
+
+    fs.hard_link(src.clone(), dst.clone()).wait().unwrap();
+
+    let bytes = futures::stream::iter_ok::<_, io::Error>(vec!["linked".into()]);
+    let _ = bytes
+        .forward(fs.write(src.clone(), Default::default()))
+        .wait()
+        .unwrap();
+
+    let data = fs
+        .read(dst.clone(), Default::default())
+        .collect()
+        .wait()
+        .unwrap()
+        .concat();
+    assert_eq!(data, b"linked");
+
+    fs.delete(src).wait().unwrap();
+    fs.delete(dst).wait().unwrap();
+}
+
+#[test]
+fn test_set_permissions() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-set-permissions");
+
+    fs::write(&tmp, b"#!/bin/sh").unwrap();
+
+    fs.set_readonly(tmp.clone(), true).wait().unwrap();
+    assert!(fs
+        .metadata(tmp.clone())
+        .wait()
+        .unwrap()
+        .permissions()
+        .readonly());
+
+    fs.set_readonly(tmp.clone(), false).wait().unwrap();
+    assert!(!fs
+        .metadata(tmp.clone())
+        .wait()
+        .unwrap()
+        .permissions()
+        .readonly());
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let perms = fs::Permissions::from_mode(0o755);
+        fs.set_permissions(tmp.clone(), perms).wait().unwrap();
+        let meta = fs.metadata(tmp.clone()).wait().unwrap();
+        assert_eq!(meta.permissions().mode() & 0o777, 0o755);
+    }
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_canonicalize() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-canonicalize");
+
+    fs::create_dir_all(tmp.join("a")).unwrap();
+
+    let path = fs.canonicalize(tmp.join("a").join("..")).wait().unwrap();
+    assert_eq!(path, fs::canonicalize(&tmp).unwrap());
+
+    let err = fs
+        .canonicalize(tmp.join("missing").join(".."))
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+    fs.remove_dir_all(tmp).wait().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_symlink_metadata() {
+    let fs = FsPool::default();
+
+    let mut link = env::temp_dir();
+    link.push("futures-fs-symlink-metadata");
+
+    fs.symlink("dangling-target", link.clone()).wait().unwrap();
+
+    let meta = fs.symlink_metadata(link.clone()).wait().unwrap();
+    assert!(meta.file_type().is_symlink());
+
+    let err = fs.metadata(link.clone()).wait().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+    fs.delete(link).wait().unwrap();
+}
+
+#[test]
+fn test_read_range() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-read-range");
+
+    let contents = (0..100u8).collect::<Vec<_>>();
+    fs::write(&tmp, &contents).unwrap();
+
+    let read = |opts: ReadOptions| {
+        fs.read(tmp.clone(), opts)
+            .collect()
+            .wait()
+            .unwrap()
+            .concat()
+    };
+
+    let opts = ReadOptions::default().buffer_size(16).range(10, 40);
+    assert_eq!(read(opts), &contents[10..50]);
+
+    let opts = ReadOptions::default().range(90, 40);
+    assert_eq!(read(opts), &contents[90..]);
+
+    let opts = ReadOptions::default().range(10, 0);
+    assert!(read(opts).is_empty());
+
+    let opts = ReadOptions::default().range(200, 10);
+    assert!(read(opts).is_empty());
+
+    let file = fs::File::open(&tmp).unwrap();
+    let opts = ReadOptions::default().buffer_size(16).range(20, 5);
+    let data = fs.read_file(file, opts).collect().wait().unwrap().concat();
+    assert_eq!(data, &contents[20..25]);
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_read_from_end() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-read-from-end");
+
+    let contents = (0..100u8).collect::<Vec<_>>();
+    fs::write(&tmp, &contents).unwrap();
+
+    let read = |opts: ReadOptions| {
+        fs.read(tmp.clone(), opts)
+            .collect()
+            .wait()
+            .unwrap()
+            .concat()
+    };
+
+    let opts = ReadOptions::default().buffer_size(16).from_end(30);
+    assert_eq!(read(opts), &contents[70..]);
+
+    let opts = ReadOptions::default().from_end(100);
+    assert_eq!(read(opts), &contents[..]);
+
+    let opts = ReadOptions::default().from_end(1000);
+    assert_eq!(read(opts), &contents[..]);
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_write_append() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-append");
+
+    for &piece in &["hello", " world"] {
+        let bytes = futures::stream::iter_ok::<_, io::Error>(vec![piece.into()]);
+        let opts = WriteOptions::default().append(true);
+        let _ = bytes.forward(fs.write(tmp.clone(), opts)).wait().unwrap();
+    }
+
+    assert_eq!(fs::read(&tmp).unwrap(), b"hello world");
+
+    let mut open = fs::OpenOptions::new();
+    open.write(true).truncate(true);
+    let opts = WriteOptions::from(open).append(true);
+    let err = fs
+        .write(tmp.clone(), opts)
+        .send("oops".into())
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_write_create_new() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-create-new");
+
+    let sinks = (0..2)
+        .map(|_| fs.write(tmp.clone(), WriteOptions::default().create_new(true)))
+        .collect::<Vec<_>>();
+
+    let results = sinks
+        .into_iter()
+        .map(|sink| sink.send("hello".into()).wait())
+        .collect::<Vec<_>>();
+
+    assert_eq!(results.iter().filter(|res| res.is_ok()).count(), 1);
+    let err = results.into_iter().find(|res| res.is_err()).unwrap();
+    assert_eq!(err.unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_with_open_options() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-open-options");
+
+    let mut open = fs::OpenOptions::new();
+    open.write(true).truncate(true);
+
+    // the default of creating the file doesn't apply
+    let opts = WriteOptions::default().with_open_options(open.clone());
+    let err = fs
+        .write(tmp.clone(), opts)
+        .send("hello".into())
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+    fs::write(&tmp, b"hello world").unwrap();
+
+    let opts = WriteOptions::default().with_open_options(open);
+    let _ = fs
+        .write(tmp.clone(), opts)
+        .send("bye".into())
+        .wait()
+        .unwrap();
+    assert_eq!(fs::read(&tmp).unwrap(), b"bye");
+
+    let mut open = fs::OpenOptions::new();
+    open.read(true);
+    let opts = ReadOptions::default().with_open_options(open);
+    let data = fs
+        .read(tmp.clone(), opts)
+        .collect()
+        .wait()
+        .unwrap()
+        .concat();
+    assert_eq!(data, b"bye");
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_write_mode() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-mode");
+
+    let opts = WriteOptions::default().mode(0o600);
+    let _ = fs
+        .write(tmp.clone(), opts)
+        .send("secret".into())
+        .wait()
+        .unwrap();
+
+    let meta = fs.metadata(tmp.clone()).wait().unwrap();
+    assert_eq!(meta.permissions().mode() & 0o777, 0o600);
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_read_to_end() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-read-to-end");
+
+    fs::write(&tmp, vec![7u8; 10_000]).unwrap();
+
+    let data = fs.read_to_end(tmp.clone(), None).wait().unwrap();
+    assert_eq!(data, vec![7u8; 10_000]);
+
+    let data = fs.read_to_end(tmp.clone(), Some(10_000)).wait().unwrap();
+    assert_eq!(data.len(), 10_000);
+
+    let err = fs.read_to_end(tmp.clone(), Some(9_999)).wait().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_write_all() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-write-all");
+
+    fs.write_all(tmp.clone(), "hello".into(), Default::default())
+        .wait()
+        .unwrap();
+    let opts = WriteOptions::default().append(true);
+    fs.write_all(tmp.clone(), " world".into(), opts)
+        .wait()
+        .unwrap();
+
+    let data = fs.read_to_end(tmp.clone(), None).wait().unwrap();
+    assert_eq!(data, "hello world");
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_read_to_string() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-read-to-string");
+
+    fs::write(&tmp, "hello world").unwrap();
+    let data = fs.read_to_string(tmp.clone()).wait().unwrap();
+    assert_eq!(data, "hello world");
+
+    fs::write(&tmp, b"\xff\xfe").unwrap();
+    let err = fs.read_to_string(tmp.clone()).wait().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_append() {
+    let fs1 = FsPool::default();
+    let fs2 = fs1.clone();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-append-sink");
+
+    let mut sink1 = fs1.append(tmp.clone());
+    let mut sink2 = fs2.append(tmp.clone());
+    for _ in 0..5 {
+        sink1 = sink1.send("a".into()).wait().unwrap();
+        sink2 = sink2.send("b".into()).wait().unwrap();
+    }
+    drop((sink1, sink2));
+
+    assert_eq!(fs::read(&tmp).unwrap(), b"ababababab");
+
+    let file = fs::OpenOptions::new().write(true).open(&tmp).unwrap();
+    let _ = fs1.append_file(file).send("c".into()).wait().unwrap();
+
+    assert_eq!(fs::read(&tmp).unwrap(), b"abababababc");
+
+    fs1.delete(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_write_atomic() {
+    let fs = FsPool::default();
+
+    let mut dir = env::temp_dir();
+    dir.push("futures-fs-atomic");
+    fs::create_dir_all(&dir).unwrap();
+    let tmp = dir.join("config");
+
+    fs::write(&tmp, b"original").unwrap();
+
+    let opts = WriteOptions::default().atomic(true);
+    let sink = fs.write(tmp.clone(), opts);
+    let sink = sink.send("partial".into()).wait().unwrap();
+    drop(sink);
+
+    assert_eq!(fs::read(&tmp).unwrap(), b"original");
+
+    // the temporary file is removed on the pool
+    let mut entries = 0;
+    for _ in 0..100 {
+        entries = fs::read_dir(&dir).unwrap().count();
+        if entries == 1 {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    assert_eq!(entries, 1);
+
+    let bytes = futures::stream::iter_ok::<_, io::Error>(vec!["new".into(), " config".into()]);
+    let opts = WriteOptions::default().atomic(true);
+    let _ = bytes.forward(fs.write(tmp.clone(), opts)).wait().unwrap();
+
+    assert_eq!(fs::read(&tmp).unwrap(), b"new config");
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+    fs.remove_dir_all(dir).wait().unwrap();
+}
+
+#[test]
+fn test_set_len() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-set-len");
+
+    fs::write(&tmp, b"hello world").unwrap();
+
+    fs.set_len(tmp.clone(), 5).wait().unwrap();
+    assert_eq!(fs::read(&tmp).unwrap(), b"hello");
+
+    let file = fs::OpenOptions::new().write(true).open(&tmp).unwrap();
+    fs.set_len_file(&file, 4096).wait().unwrap();
+    assert_eq!(fs.metadata(tmp.clone()).wait().unwrap().len(), 4096);
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_sync() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-sync");
+
+    fs::write(&tmp, b"durable").unwrap();
+
+    fs.sync_all(tmp.clone()).wait().unwrap();
+    fs.sync_data(tmp.clone()).wait().unwrap();
+
+    let file = fs::OpenOptions::new().write(true).open(&tmp).unwrap();
+    fs.sync_all_file(&file).wait().unwrap();
+    fs.sync_data_file(&file).wait().unwrap();
+
+    fs.delete(tmp.clone()).wait().unwrap();
+
+    let err = fs.sync_all(tmp).wait().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_write_sync_on_close() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-sync-on-close");
+
+    let bytes = futures::stream::iter_ok::<_, io::Error>(vec!["synced".into()]);
+    let opts = WriteOptions::default().sync_on_close(true);
+    let (_, mut sink) = bytes.forward(fs.write(tmp.clone(), opts)).wait().unwrap();
+    assert_eq!(fs::read(&tmp).unwrap(), b"synced");
+
+    // closing again is a no-op, but further sends are errors
+    futures::future::poll_fn(|| sink.close()).wait().unwrap();
+    assert!(sink.send("more".into()).wait().is_err());
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_write_written() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-written");
+
+    let bytes =
+        futures::stream::iter_ok::<_, io::Error>(vec!["hello".into(), " ".into(), "world".into()]);
+    let (_, sink) = bytes
+        .forward(fs.write(tmp.clone(), Default::default()))
+        .wait()
+        .unwrap();
+    assert_eq!(sink.written(), 11);
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_write_written_error() {
+    let fs = FsPool::default();
+
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .open("/dev/full")
+        .unwrap();
+    let mut sink = fs.write_file(file);
+    sink.start_send("full".into()).unwrap();
+    assert!(futures::future::poll_fn(|| sink.poll_complete())
+        .wait()
+        .is_err());
+    assert_eq!(sink.written(), 0);
+}
+
+#[test]
+fn test_write_close_into_file() {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-close-into-file");
+
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&tmp)
+        .unwrap();
+    let mut sink = fs.write_file(file);
+    for _ in 0..4 {
+        sink = sink.send("data".into()).wait().unwrap();
+    }
+    sink.start_send("last".into()).unwrap();
+
+    let mut file = sink.close_into_file().wait().unwrap();
+    let mut data = String::new();
+    file.seek(SeekFrom::Start(0)).unwrap();
+    file.read_to_string(&mut data).unwrap();
+    assert_eq!(data, "datadatadatadatalast");
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_read_into_file() {
+    use std::io::{Read, Seek};
+
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-read-into-file");
+
+    let contents = (0..1000).map(|i| i as u8).collect::<Vec<_>>();
+    fs::write(&tmp, &contents).unwrap();
+
+    let opts = ReadOptions::default().buffer_size(100);
+    let stream = fs.read(tmp.clone(), opts);
+    let (chunk, stream) = stream.into_future().wait().ok().unwrap();
+    assert_eq!(chunk.unwrap(), &contents[..100]);
+
+    // a read may be in flight, but its bytes were never yielded
+    let mut file = stream.into_file().wait().unwrap();
+    assert_eq!(file.stream_position().unwrap(), 100);
+
+    let mut rest = Vec::new();
+    file.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, &contents[100..]);
+
+    let file = fs::File::open(&tmp).unwrap();
+    let stream = fs.read_file(file, Default::default());
+    let data = stream.collect().wait().unwrap().concat();
+    assert_eq!(data, contents);
+
+    let opts = ReadOptions::default().range(40, 20);
+    let mut file = fs.read(tmp.clone(), opts).into_file().wait().unwrap();
+    assert_eq!(file.stream_position().unwrap(), 40);
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_read_seek() {
+    use std::io::SeekFrom;
+
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-read-seek");
+
+    let contents = (0..1000).map(|i| i as u8).collect::<Vec<_>>();
+    fs::write(&tmp, &contents).unwrap();
+
+    let opts = ReadOptions::default().buffer_size(100);
+    let mut stream = fs.read(tmp.clone(), opts);
+
+    let pos = stream.seek(SeekFrom::Start(500)).wait().unwrap();
+    assert_eq!(pos, 500);
+    let (chunk, mut stream) = stream.into_future().wait().ok().unwrap();
+    assert_eq!(chunk.unwrap(), &contents[500..600]);
+
+    let pos = stream.seek(SeekFrom::Current(-50)).wait().unwrap();
+    assert_eq!(pos, 550);
+    let data = stream.collect().wait().unwrap().concat();
+    assert_eq!(data, &contents[550..]);
+
+    let mut stream = fs.read(tmp.clone(), Default::default());
+    stream.seek(SeekFrom::Start(2000)).wait().unwrap();
+    assert!(stream.collect().wait().unwrap().is_empty());
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_read_follow() {
+    use std::io::Write;
+    use std::time::Duration;
+
+    // a single thread, which following a file mustn't keep busy
+    let fs = FsPool::new(1);
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-follow");
+
+    fs::write(&tmp, b"first").unwrap();
+
+    let opts = ReadOptions::default()
+        .follow(true)
+        .follow_interval(Duration::from_millis(10));
+    let mut stream = fs.read(tmp.clone(), opts).wait();
+    assert_eq!(stream.next().unwrap().unwrap(), "first");
+    fs.metadata(tmp.clone()).wait().unwrap();
+
+    let mut file = fs::OpenOptions::new().append(true).open(&tmp).unwrap();
+    file.write_all(b" second").unwrap();
+    assert_eq!(stream.next().unwrap().unwrap(), " second");
+
+    // truncating the file reads it again from the start
+    fs::write(&tmp, b"new").unwrap();
+    assert_eq!(stream.next().unwrap().unwrap(), "new");
+    drop(stream);
+
+    let opts = ReadOptions::default()
+        .follow(true)
+        .follow_interval(Duration::from_millis(10))
+        .error_on_truncate(true);
+    let mut stream = fs.read(tmp.clone(), opts).wait();
+    assert_eq!(stream.next().unwrap().unwrap(), "new");
+    fs::write(&tmp, b"").unwrap();
+    let err = stream.next().unwrap().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_read_lines() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-lines");
+
+    let long = "x".repeat(100);
+    fs::write(&tmp, format!("one\r\ntwo\n\n{}\nlast", long)).unwrap();
+
+    let opts = ReadOptions::default().buffer_size(64);
+    let lines = fs.read(tmp.clone(), opts).lines().collect().wait().unwrap();
+    assert_eq!(lines, vec!["one", "two", "", &long, "last"]);
+
+    fs::write(&tmp, b"valid\n\xff\n").unwrap();
+    let mut lines = fs.read(tmp.clone(), Default::default()).lines().wait();
+    assert_eq!(lines.next().unwrap().unwrap(), "valid");
+    let err = lines.next().unwrap().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_read_split() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-split");
+
+    let long = vec![b'x'; 100];
+    let mut contents = b"one\0\0".to_vec();
+    contents.extend_from_slice(&long);
+    contents.extend_from_slice(b"\0last");
+    fs::write(&tmp, &contents).unwrap();
+
+    let opts = ReadOptions::default().buffer_size(64);
+    let frames = fs
+        .read(tmp.clone(), opts)
+        .split(0)
+        .collect()
+        .wait()
+        .unwrap();
+    assert_eq!(frames, vec![&b"one"[..], b"", &long, b"last"]);
+
+    let mut frames = fs
+        .read(tmp.clone(), Default::default())
+        .split(0)
+        .max_frame_len(50)
+        .wait();
+    assert_eq!(frames.next().unwrap().unwrap(), "one");
+    assert_eq!(frames.next().unwrap().unwrap(), "");
+    let err = frames.next().unwrap().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(frames.next().is_none());
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_read_chunks_exact() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-chunks-exact");
+
+    let contents = (0..1300).map(|i| i as u8).collect::<Vec<_>>();
+    fs::write(&tmp, &contents).unwrap();
+
+    let opts = ReadOptions::default().buffer_size(300);
+    let chunks = fs
+        .read(tmp.clone(), opts)
+        .chunks_exact(512)
+        .collect()
+        .wait()
+        .unwrap();
+    assert_eq!(chunks.len(), 3);
+    assert_eq!(chunks[0], &contents[..512]);
+    assert_eq!(chunks[1], &contents[512..1024]);
+    assert_eq!(chunks[2], &contents[1024..]);
+
+    let opts = ReadOptions::default().buffer_size(1000);
+    let chunks = fs
+        .read(tmp.clone(), opts)
+        .chunks_exact(100)
+        .collect()
+        .wait()
+        .unwrap();
+    assert_eq!(chunks.len(), 13);
+    assert_eq!(chunks.concat(), contents);
+
+    let err = fs
+        .read(tmp.clone(), Default::default())
+        .chunks_exact(512)
+        .error_on_partial(true)
+        .collect()
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_read_with_offsets() {
+    use std::io::SeekFrom;
+
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-read-offsets");
+
+    let contents = (0..1000).map(|i| i as u8).collect::<Vec<_>>();
+    fs::write(&tmp, &contents).unwrap();
+
+    let opts = ReadOptions::default().buffer_size(300);
+    let chunks = fs
+        .read(tmp.clone(), opts)
+        .with_offsets()
+        .collect()
+        .wait()
+        .unwrap();
+    let offsets = chunks.iter().map(|&(pos, _)| pos).collect::<Vec<_>>();
+    assert_eq!(offsets, [0, 300, 600, 900]);
+    for (pos, chunk) in chunks {
+        assert_eq!(chunk, &contents[pos as usize..pos as usize + chunk.len()]);
+    }
+
+    let opts = ReadOptions::default().buffer_size(100).range(250, 150);
+    let chunks = fs
+        .read(tmp.clone(), opts)
+        .with_offsets()
+        .collect()
+        .wait()
+        .unwrap();
+    let offsets = chunks.iter().map(|&(pos, _)| pos).collect::<Vec<_>>();
+    assert_eq!(offsets, [250, 350]);
+
+    let opts = ReadOptions::default().buffer_size(100).from_end(150);
+    let chunks = fs
+        .read(tmp.clone(), opts)
+        .with_offsets()
+        .collect()
+        .wait()
+        .unwrap();
+    let offsets = chunks.iter().map(|&(pos, _)| pos).collect::<Vec<_>>();
+    assert_eq!(offsets, [850, 950]);
+
+    let opts = ReadOptions::default().buffer_size(100);
+    let mut stream = fs.read(tmp.clone(), opts);
+    stream.seek(SeekFrom::Start(420)).wait().unwrap();
+    let (chunk, _) = stream.with_offsets().into_future().wait().ok().unwrap();
+    let (pos, chunk) = chunk.unwrap();
+    assert_eq!(pos, 420);
+    assert_eq!(chunk, &contents[420..520]);
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_write_at() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-write-at");
+    fs::write(&tmp, vec![0xff; 13_000]).unwrap();
+
+    let contents = (0..10_000).map(|i| i as u8).collect::<Vec<_>>();
+    let pieces = contents
+        .chunks(100)
+        .enumerate()
+        .rev()
+        .map(|(i, piece)| ((i * 100) as u64, piece.to_vec().into()))
+        .collect::<Vec<_>>();
+
+    let sink = fs
+        .write_at(tmp.clone(), Default::default())
+        .set_len_on_close(true);
+    let _ = futures::stream::iter_ok::<_, io::Error>(pieces)
+        .forward(sink)
+        .wait()
+        .unwrap();
+    assert_eq!(fs::read(&tmp).unwrap(), contents);
+
+    let sink = fs.write_at(tmp.clone(), Default::default());
+    let _ = sink.send((10u64, "hello".into())).wait().unwrap();
+    let data = fs::read(&tmp).unwrap();
+    assert_eq!(data.len(), contents.len());
+    assert_eq!(&data[10..15], b"hello");
+    assert_eq!(&data[15..], &contents[15..]);
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_write_many_small() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-write-many-small");
+
+    let contents = (0..64_000).map(|i| (i / 64) as u8).collect::<Vec<_>>();
+    let pieces = contents
+        .chunks(64)
+        .map(|piece| piece.to_vec().into())
+        .collect::<Vec<_>>();
+
+    let opts = WriteOptions::default().create_new(true);
+    let sink = fs.write(tmp.clone(), opts);
+    let (_, sink) = futures::stream::iter_ok::<_, io::Error>(pieces)
+        .forward(sink)
+        .wait()
+        .unwrap();
+    assert_eq!(sink.written(), contents.len() as u64);
+    assert_eq!(fs::read(&tmp).unwrap(), contents);
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_read_ahead() {
+    use std::io::SeekFrom;
+    use std::thread;
+    use std::time::Duration;
+
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-read-ahead");
+
+    let contents = (0..1000).map(|i| i as u8).collect::<Vec<_>>();
+    fs::write(&tmp, &contents).unwrap();
+
+    let opts = ReadOptions::default().buffer_size(100).read_ahead(3);
+    let chunks = fs.read(tmp.clone(), opts).collect().wait().unwrap();
+    assert_eq!(chunks.len(), 10);
+    assert_eq!(chunks.concat(), contents);
+
+    let opts = ReadOptions::default().buffer_size(100).read_ahead(3);
+    let (chunk, mut stream) = fs
+        .read(tmp.clone(), opts)
+        .into_future()
+        .wait()
+        .ok()
+        .unwrap();
+    assert_eq!(chunk.unwrap(), &contents[..100]);
+    // give the pool time to read ahead
+    thread::sleep(Duration::from_millis(50));
+    let pos = stream.seek(SeekFrom::Current(0)).wait().unwrap();
+    assert_eq!(pos, 100);
+    let data = stream.collect().wait().unwrap().concat();
+    assert_eq!(data, &contents[100..]);
+
+    let opts = ReadOptions::default()
+        .buffer_size(100)
+        .range(0, 250)
+        .read_ahead(2);
+    let (_, mut stream) = fs
+        .read(tmp.clone(), opts)
+        .into_future()
+        .wait()
+        .ok()
+        .unwrap();
+    thread::sleep(Duration::from_millis(50));
+    stream.seek(SeekFrom::Current(0)).wait().unwrap();
+    let data = stream.collect().wait().unwrap().concat();
+    assert_eq!(data, &contents[100..250]);
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_write_buffer_size() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-write-buffer-size");
+
+    let contents = (0..2500).map(|i| (i / 100) as u8).collect::<Vec<_>>();
+    let pieces = contents
+        .chunks(100)
+        .map(|piece| piece.to_vec().into())
+        .collect::<Vec<_>>();
+
+    let opts = WriteOptions::default().create_new(true).buffer_size(1000);
+    let sink = fs.write(tmp.clone(), opts);
+    let (_, sink) = futures::stream::iter_ok::<_, io::Error>(pieces)
+        .forward(sink)
+        .wait()
+        .unwrap();
+    assert_eq!(sink.written(), contents.len() as u64);
+    assert_eq!(fs::read(&tmp).unwrap(), contents);
+
+    let opts = WriteOptions::default().buffer_size(1000);
+    let mut sink = fs.write(tmp.clone(), opts);
+    sink.start_send("buffered".into()).unwrap();
+    let file = sink.close_into_file().wait().unwrap();
+    assert_eq!(file.metadata().unwrap().len(), contents.len() as u64);
+    assert_eq!(&fs::read(&tmp).unwrap()[..8], b"buffered");
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_write_buffer_size_error() {
+    let fs = FsPool::default();
+
+    let opts = WriteOptions::default().buffer_size(1000);
+    let mut sink = fs.write("/dev/full", opts);
+    sink.start_send("full".into()).unwrap();
+    // the error is only seen once the buffer has been flushed
+    assert!(futures::future::poll_fn(|| sink.poll_complete())
+        .wait()
+        .is_err());
+    assert_eq!(sink.written(), 0);
+}
+
+#[test]
+fn test_write_max_queued_bytes() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-write-max-queued-bytes");
+
+    const ITEM: u64 = 1024 * 1024;
+    const LIMIT: u64 = 4 * ITEM;
+
+    let path = tmp.clone();
+    let mut produced = 0;
+    let items = futures::stream::iter_ok::<_, io::Error>((0..100).map(move |_| {
+        // items are only produced once the sink has room for them
+        let on_disk = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        assert!(produced - on_disk <= LIMIT + ITEM);
+        produced += ITEM;
+        vec![0u8; ITEM as usize].into()
+    }));
+
+    let opts = WriteOptions::default()
+        .create_new(true)
+        .max_queued_bytes(LIMIT);
+    let (_, sink) = items.forward(fs.write(tmp.clone(), opts)).wait().unwrap();
+    assert_eq!(sink.written(), 100 * ITEM);
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_read_buffer_reuse() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-buffer-reuse");
+
+    let contents = (0..100_000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    fs::write(&tmp, &contents).unwrap();
+
+    // chunks that are dropped right away may be reused
+    let opts = ReadOptions::default().buffer_size(1000);
+    let expected = contents.clone();
+    fs.read(tmp.clone(), opts)
+        .with_offsets()
+        .for_each(move |(pos, chunk)| {
+            let pos = pos as usize;
+            assert_eq!(chunk, &expected[pos..pos + chunk.len()]);
+            Ok(())
+        })
+        .wait()
+        .unwrap();
+
+    // chunks that are held on to are never written to
+    let opts = ReadOptions::default().buffer_size(1000);
+    let chunks = fs.read(tmp.clone(), opts).collect().wait().unwrap();
+    assert_eq!(chunks.concat(), contents);
+
+    let opts = ReadOptions::default().buffer_size(1000).buffer_reuse(false);
+    let chunks = fs.read(tmp.clone(), opts).collect().wait().unwrap();
+    assert_eq!(chunks.concat(), contents);
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[cfg(all(target_os = "linux", feature = "direct-io-tests"))]
+#[test]
+fn test_direct_io() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-direct-io");
+
+    let contents = (0..100_000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    let bytes = futures::stream::iter_ok::<_, io::Error>(
+        contents
+            .chunks(3000)
+            .map(|piece| piece.to_vec().into())
+            .collect::<Vec<_>>(),
+    );
+
+    // the sink is closed at the end, writing the final partial block
+    let opts = WriteOptions::default().direct(true);
+    let _ = bytes.forward(fs.write(tmp.clone(), opts)).wait().unwrap();
+    assert_eq!(fs::read(&tmp).unwrap(), contents);
+
+    // an unaligned range is read through the page cache
+    let opts = ReadOptions::default()
+        .direct(true)
+        .buffer_size(5000)
+        .range(100, 90_000);
+    let chunks = fs.read(tmp.clone(), opts).collect().wait().unwrap();
+    assert_eq!(chunks.concat(), &contents[100..90_100]);
+
+    let opts = ReadOptions::default().direct(true).buffer_size(8192);
+    let chunks = fs.read(tmp.clone(), opts).collect().wait().unwrap();
+    assert_eq!(chunks.concat(), contents);
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_read_advise() {
+    use std::io::SeekFrom;
+
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-read-advise");
+
+    let contents = (0..100_000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    fs::write(&tmp, &contents).unwrap();
+
+    for &advice in &[
+        Advice::Sequential,
+        Advice::Random,
+        Advice::WillNeed,
+        Advice::DontNeed,
+    ] {
+        let opts = ReadOptions::default().buffer_size(5000).advise(advice);
+        let chunks = fs.read(tmp.clone(), opts).collect().wait().unwrap();
+        assert_eq!(chunks.concat(), contents);
+    }
+
+    // the bytes are dropped from the page cache relative to the range
+    let opts = ReadOptions::default()
+        .buffer_size(3000)
+        .range(1000, 50_000)
+        .advise(Advice::DontNeed);
+    let (chunk, mut stream) = fs
+        .read(tmp.clone(), opts)
+        .into_future()
+        .wait()
+        .ok()
+        .unwrap();
+    assert_eq!(chunk.unwrap(), &contents[1000..4000]);
+    stream.seek(SeekFrom::Start(500)).wait().unwrap();
+    let data = stream.collect().wait().unwrap().concat();
+    assert_eq!(data, &contents[500..47_500]);
+
+    fs.delete(tmp).wait().unwrap();
+}
+
+#[test]
+fn test_write_preallocate() {
+    let fs = FsPool::default();
+
+    let mut tmp = env::temp_dir();
+    tmp.push("futures-fs-write-preallocate");
+
+    let bytes = || futures::stream::iter_ok::<_, io::Error>(vec!["hello", " ", "world"]);
+
+    // without truncating, the reserved space is kept
+    let opts = WriteOptions::default().preallocate(1_000_000);
+    let _ = bytes()
+        .map(|piece| piece.into())
+        .forward(fs.write(tmp.clone(), opts))
+        .wait()
+        .unwrap();
+    let data = fs::read(&tmp).unwrap();
+    assert_eq!(data.len(), 1_000_000);
+    assert_eq!(&data[..11], b"hello world");
+    assert!(data[11..].iter().all(|&b| b == 0));
+
+    let opts = WriteOptions::default()
+        .preallocate(1_000_000)
+        .truncate_on_close(true);
+    let _ = bytes()
+        .map(|piece| piece.into())
+        .forward(fs.write(tmp.clone(), opts))
+        .wait()
+        .unwrap();
+    assert_eq!(fs::read(&tmp).unwrap(), b"hello world");
+
+    fs.delete(tmp.clone()).wait().unwrap();
+
+    // a failure to reserve the space is returned from the first flush
+    let opts = WriteOptions::default().preallocate(u64::MAX);
+    let mut sink = fs.write(tmp.clone(), opts);
+    assert!(futures::future::poll_fn(|| sink.poll_complete())
+        .wait()
+        .is_err());
+
+    let _ = fs.delete(tmp).wait();
+}
+
+#[test]
+fn test_copy_sparse() {
+    use std::io::{Seek, SeekFrom, Write};
+
+    let fs = FsPool::default();
+
+    let mut src = env::temp_dir();
+    src.push("futures-fs-copy-sparse-src");
+    let mut dst = env::temp_dir();
+    dst.push("futures-fs-copy-sparse-dst");
+
+    const LEN: u64 = 64 * 1024 * 1024;
+    {
+        let mut file = fs::File::create(&src).unwrap();
+        file.set_len(LEN).unwrap();
+        file.seek(SeekFrom::Start(LEN - 4096)).unwrap();
+        file.write_all(&[1; 4096]).unwrap();
+        file.seek(SeekFrom::Start(1024 * 1024)).unwrap();
+        file.write_all(b"hello").unwrap();
+    }
+
+    let opts = CopyOptions::default().sparse(true);
+    let copied = fs.copy_with(src.clone(), dst.clone(), opts).wait().unwrap();
+    assert_eq!(copied, LEN);
+    assert_eq!(fs::metadata(&dst).unwrap().len(), LEN);
+    assert!(fs::read(&src).unwrap() == fs::read(&dst).unwrap());
+
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        // only check the copy if the filesystem kept the source sparse
+        if fs::metadata(&src).unwrap().blocks() * 512 < LEN / 2 {
+            assert!(fs::metadata(&dst).unwrap().blocks() * 512 < LEN / 2);
+        }
+    }
+
+    // the hole at the end is recreated as well
+    fs::File::create(&src).unwrap().set_len(LEN).unwrap();
+    let opts = CopyOptions::default().sparse(true);
+    fs.copy_with(src.clone(), dst.clone(), opts).wait().unwrap();
+    assert_eq!(fs::metadata(&dst).unwrap().len(), LEN);
+
+    fs.delete(src).wait().unwrap();
+    fs.delete(dst).wait().unwrap();
+}
+
+#[test]
+fn test_move_file() {
+    let fs = FsPool::default();
+
+    let mut from = env::temp_dir();
+    from.push("futures-fs-move-file-from");
+    let mut to = env::temp_dir();
+    to.push("futures-fs-move-file-to");
+    fs::write(&from, b"hello world").unwrap();
+
+    fs.move_file(from.clone(), to.clone()).wait().unwrap();
+    assert!(!from.exists());
+    assert_eq!(fs::read(&to).unwrap(), b"hello world");
+    fs::remove_file(&to).unwrap();
+
+    // /dev/shm is usually on another filesystem
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let shm = std::path::Path::new("/dev/shm");
+        let other = shm.metadata().map(|metadata| metadata.dev()).ok();
+        if other.is_some() && other != Some(env::temp_dir().metadata().unwrap().dev()) {
+            let to = shm.join("futures-fs-move-file-to");
+            fs::write(&from, b"hello world").unwrap();
+
+            let err = fs
+                .move_file_with(
+                    from.clone(),
+                    to.clone(),
+                    MoveOptions::default().rename_only(true),
+                )
+                .wait()
+                .unwrap_err();
+            assert_eq!(err.raw_os_error(), Some(18)); // EXDEV
+            assert!(from.exists());
+            assert!(!to.exists());
+
+            fs.move_file(from.clone(), to.clone()).wait().unwrap();
+            assert!(!from.exists());
+            assert_eq!(fs::read(&to).unwrap(), b"hello world");
+            let leftover = fs::read_dir(shm).unwrap().any(|entry| {
+                entry
+                    .unwrap()
+                    .file_name()
+                    .to_string_lossy()
+                    .ends_with(".tmp")
+            });
+            assert!(!leftover);
+            fs::remove_file(&to).unwrap();
+        }
+    }
+
+    let err = fs.move_file(from, to).wait().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_copy_procfs() {
+    let fs = FsPool::default();
+
+    let mut dst = env::temp_dir();
+    dst.push("futures-fs-copy-procfs");
+
+    // procfs reports a length of 0, and the kernel copies nothing from it
+    let expected = fs::read("/proc/version").unwrap();
+    let n = fs.copy("/proc/version", dst.clone()).wait().unwrap();
+    assert!(n > 0);
+    assert_eq!(fs::read(&dst).unwrap(), expected);
+
+    fs::remove_file(dst).unwrap();
+}
+
+#[test]
+fn test_copy_with() {
+    let fs = FsPool::default();
+
+    let mut src = env::temp_dir();
+    src.push("futures-fs-copy-with-src");
+    let mut dst = env::temp_dir();
+    dst.push("futures-fs-copy-with-dst");
+
+    let contents = (0..1_000_000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    fs::write(&src, &contents).unwrap();
+
+    for &allow_reflink in &[false, true] {
+        let opts = CopyOptions::default().allow_reflink(allow_reflink);
+        let copied = fs.copy_with(src.clone(), dst.clone(), opts).wait().unwrap();
+        assert_eq!(copied, contents.len() as u64);
+        assert!(fs::read(&dst).unwrap() == contents);
+    }
+
+    // errors are the same as those of a plain copy
+    let mut missing = env::temp_dir();
+    missing.push("futures-fs-copy-with-missing");
+    let err = fs
+        .copy_with(missing.clone(), dst.clone(), Default::default())
+        .wait()
+        .unwrap_err();
+    let plain = fs.copy(missing, dst.clone()).wait().unwrap_err();
+    assert_eq!(err.kind(), plain.kind());
+
+    fs.delete(src).wait().unwrap();
+    fs.delete(dst).wait().unwrap();
+}
+
+#[test]
+fn test_copy_parallel() {
+    let fs = FsPool::default();
+
+    let mut src = env::temp_dir();
+    src.push("futures-fs-copy-parallel-src");
+    let mut dst = env::temp_dir();
+    dst.push("futures-fs-copy-parallel-dst");
+
+    let contents = (0..5_000_003).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    fs::write(&src, &contents).unwrap();
+
+    for &segments in &[1, 3, 4, 16] {
+        let opts = CopyOptions::default().segments(segments);
+        let copied = fs
+            .copy_parallel(src.clone(), dst.clone(), opts)
+            .wait()
+            .unwrap();
+        assert_eq!(copied, contents.len() as u64);
+        assert!(fs::read(&dst).unwrap() == contents);
+    }
+
+    // more segments than bytes
+    fs::write(&src, b"hi").unwrap();
+    let opts = CopyOptions::default().segments(8);
+    let copied = fs
+        .copy_parallel(src.clone(), dst.clone(), opts)
+        .wait()
+        .unwrap();
+    assert_eq!(copied, 2);
+    assert_eq!(fs::read(&dst).unwrap(), b"hi");
+
+    fs.delete(src.clone()).wait().unwrap();
+    fs.delete(dst.clone()).wait().unwrap();
+
+    let err = fs
+        .copy_parallel(src, dst.clone(), Default::default())
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    assert!(!dst.exists());
+}
+
+#[test]
+fn test_copy_with_progress() {
+    let fs = FsPool::default();
+
+    let mut src = env::temp_dir();
+    src.push("futures-fs-copy-progress-src");
+    let mut dst = env::temp_dir();
+    dst.push("futures-fs-copy-progress-dst");
+
+    let contents = (0..1_000_003).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    fs::write(&src, &contents).unwrap();
+
+    let opts = CopyOptions::default().progress_bytes(100_000);
+    let (copy, progress) = fs.copy_with_progress(src.clone(), dst.clone(), opts);
+    let (copied, progress) = copy.join(progress.collect()).wait().unwrap();
+    assert_eq!(copied, contents.len() as u64);
+    assert!(fs::read(&dst).unwrap() == contents);
+
+    let last = *progress.last().unwrap();
+    assert_eq!(last.copied(), contents.len() as u64);
+    assert_eq!(last.total(), Some(contents.len() as u64));
+    let copied = progress.iter().map(|p| p.copied()).collect::<Vec<_>>();
+    let mut sorted = copied.clone();
+    sorted.sort();
+    assert_eq!(copied, sorted);
+
+    // nobody polling the progress doesn't hold up the copy
+    let (copy, _progress) = fs.copy_with_progress(src.clone(), dst.clone(), Default::default());
+    copy.wait().unwrap();
+
+    // the progress ends when the copy fails
+    fs.delete(src.clone()).wait().unwrap();
+    let (copy, progress) = fs.copy_with_progress(src, dst.clone(), Default::default());
+    assert!(copy.wait().is_err());
+    assert!(progress.collect().wait().unwrap().is_empty());
+
+    fs.delete(dst).wait().unwrap();
+}
+
+#[test]
+fn test_copy_dir_all() {
+    let fs = FsPool::default();
+
+    let mut src = env::temp_dir();
+    src.push("futures-fs-copy-dir-all-src");
+    let mut dst = env::temp_dir();
+    dst.push("futures-fs-copy-dir-all-dst");
+    let _ = fs::remove_dir_all(&src);
+    let _ = fs::remove_dir_all(&dst);
+
+    fs::create_dir_all(src.join("a/b")).unwrap();
+    fs::create_dir(src.join("empty")).unwrap();
+    fs::write(src.join("top"), b"top").unwrap();
+    fs::write(src.join("a/one"), b"one").unwrap();
+    fs::write(src.join("a/b/two"), b"two!").unwrap();
+
+    let summary = fs
+        .copy_dir_all(src.clone(), dst.clone(), Default::default())
+        .wait()
+        .unwrap();
+    assert_eq!(summary.files_copied(), 3);
+    assert_eq!(summary.bytes_copied(), 10);
+    assert_eq!(summary.dirs_created(), 4);
+    assert!(summary.failures().is_empty());
+    assert_eq!(fs::read(dst.join("top")).unwrap(), b"top");
+    assert_eq!(fs::read(dst.join("a/one")).unwrap(), b"one");
+    assert_eq!(fs::read(dst.join("a/b/two")).unwrap(), b"two!");
+    assert!(dst.join("empty").is_dir());
+
+    // copying into itself is refused
+    let err = fs
+        .copy_dir_all(src.clone(), src.join("a/inner"), Default::default())
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    assert!(!src.join("a/inner").exists());
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::symlink;
+
+        fs::remove_dir_all(&dst).unwrap();
+        symlink("a/one", src.join("link")).unwrap();
+        symlink("missing", src.join("dangling")).unwrap();
+
+        // links are recreated as they are
+        let summary = fs
+            .copy_dir_all(src.clone(), dst.clone(), Default::default())
+            .wait()
+            .unwrap();
+        assert_eq!(summary.files_copied(), 3);
+        assert_eq!(
+            fs::read_link(dst.join("link")).unwrap(),
+            std::path::Path::new("a/one")
+        );
+        assert!(fs::symlink_metadata(dst.join("dangling")).is_ok());
+
+        // following the dangling link fails, naming its path
+        fs::remove_dir_all(&dst).unwrap();
+        let opts = CopyDirOptions::default().follow_symlinks(true);
+        let err = fs
+            .copy_dir_all(src.clone(), dst.clone(), opts)
+            .wait()
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err.to_string().contains("dangling"));
+
+        fs::remove_dir_all(&dst).unwrap();
+        let opts = CopyDirOptions::default()
+            .follow_symlinks(true)
+            .continue_on_error(true);
+        let summary = fs
+            .copy_dir_all(src.clone(), dst.clone(), opts)
+            .wait()
+            .unwrap();
+        assert_eq!(summary.files_copied(), 4);
+        assert_eq!(fs::read(dst.join("link")).unwrap(), b"one");
+        assert_eq!(summary.failures().len(), 1);
+        assert_eq!(summary.failures()[0].0, src.join("dangling"));
+    }
+
+    fs.remove_dir_all(src).wait().unwrap();
+    fs.remove_dir_all(dst).wait().unwrap();
+}
+
+#[test]
+fn test_delete_all() {
+    let fs = FsPool::default();
+
+    let mut dir = env::temp_dir();
+    dir.push("futures-fs-delete-all");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("subdir")).unwrap();
+
+    let mut paths = Vec::new();
+    for i in 0..200 {
+        let path = dir.join(format!("file-{}", i));
+        fs::write(&path, b"delete me").unwrap();
+        paths.push(path);
+    }
+    paths.push(dir.join("missing"));
+    paths.push(dir.join("subdir"));
+
+    let summary = fs
+        .delete_all(paths.clone(), Default::default())
+        .wait()
+        .unwrap();
+    assert_eq!(summary.deleted(), 200);
+    assert_eq!(summary.missing(), 1);
+    assert_eq!(summary.errors().len(), 2);
+    assert_eq!(summary.errors()[0].0, dir.join("missing"));
+    assert_eq!(summary.errors()[0].1.kind(), io::ErrorKind::NotFound);
+    assert_eq!(summary.errors()[1].0, dir.join("subdir"));
+    assert!(dir.join("subdir").is_dir());
+
+    // everything but the directory is missing now
+    let summary = fs
+        .delete_all(&paths, DeleteOptions::default().ignore_missing(true))
+        .wait()
+        .unwrap();
+    assert_eq!(summary.deleted(), 0);
+    assert_eq!(summary.missing(), 201);
+    assert_eq!(summary.errors().len(), 1);
+    assert_eq!(summary.errors()[0].0, dir.join("subdir"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_walk() {
+    use std::path::PathBuf;
+
+    let fs = FsPool::default();
+
+    let mut root = env::temp_dir();
+    root.push("futures-fs-walk");
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("a/b/c")).unwrap();
+    fs::write(root.join("top"), b"").unwrap();
+    fs::write(root.join("a/one"), b"").unwrap();
+    fs::write(root.join("a/b/two"), b"").unwrap();
+    for i in 0..100 {
+        fs::write(root.join(format!("a/b/c/{}", i)), b"").unwrap();
+    }
+
+    let walk = |opts: WalkOptions| {
+        let root = root.clone();
+        fs.walk(root.clone(), opts)
+            .map(move |entry| {
+                let path = entry.path().strip_prefix(&root).unwrap().to_owned();
+                (path, entry.depth())
+            })
+            .collect()
+            .wait()
+            .unwrap()
+    };
+    let position = |entries: &[(PathBuf, usize)], path: &str| {
+        entries
+            .iter()
+            .position(|(p, _)| p == std::path::Path::new(path))
+            .unwrap()
+    };
+
+    let entries = walk(Default::default());
+    assert_eq!(entries.len(), 106);
+    assert!(position(&entries, "a") < position(&entries, "a/b"));
+    assert!(position(&entries, "a/b") < position(&entries, "a/b/c/42"));
+    assert_eq!(entries[position(&entries, "a/b/c/42")].1, 4);
+
+    let entries = walk(
+        WalkOptions::default()
+            .contents_first(true)
+            .include_root(true),
+    );
+    assert_eq!(entries.len(), 107);
+    assert!(position(&entries, "a/b/c/42") < position(&entries, "a/b"));
+    assert_eq!(entries.last().unwrap(), &(PathBuf::new(), 0));
+
+    let entries = walk(WalkOptions::default().max_depth(2));
+    let mut paths = entries
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect::<Vec<_>>();
+    paths.sort();
+    let expected = ["a", "a/b", "a/one", "top"]
+        .iter()
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+    assert_eq!(paths, expected);
+
+    // an error is yielded without ending the walk
+    let mut missing = root.clone();
+    missing.push("missing");
+    let mut stream = fs.walk(missing.clone(), Default::default());
+    let err = futures::future::poll_fn(|| stream.poll())
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    assert!(stream.collect().wait().unwrap().is_empty());
+    let opts = WalkOptions::default().skip_errors(true);
+    assert!(fs.walk(missing, opts).collect().wait().unwrap().is_empty());
+
+    fs.remove_dir_all(root).wait().unwrap();
+}
+
+#[test]
+fn test_glob() {
+    use std::path::PathBuf;
+
+    let fs = FsPool::default();
+
+    let mut root = env::temp_dir();
+    root.push("futures-fs-glob");
+    let _ = fs::remove_dir_all(&root);
+    for dir in &["logs/2018/01", "logs/2018/02", "logs/2019", "other"] {
+        fs::create_dir_all(root.join(dir)).unwrap();
+    }
+    for file in &[
+        "logs/a.gz",
+        "logs/b.txt",
+        "logs/2018/01/x.gz",
+        "logs/2018/01/y.gz",
+        "logs/2018/02/z.log",
+        "logs/2019/c.gz",
+        "other/d.gz",
+        "file1",
+        "file2",
+        "file10",
+    ] {
+        fs::write(root.join(file), b"").unwrap();
+    }
+
+    let glob = |pattern: &str| {
+        let pattern = format!("{}/{}", root.display(), pattern);
+        fs.glob(&pattern)
+            .map(|path| path.strip_prefix(&root).unwrap().to_owned())
+            .collect()
+            .wait()
+            .unwrap()
+    };
+    let paths = |paths: &[&str]| paths.iter().map(PathBuf::from).collect::<Vec<_>>();
+
+    assert_eq!(
+        glob("logs/**/*.gz"),
+        paths(&[
+            "logs/a.gz",
+            "logs/2018/01/x.gz",
+            "logs/2018/01/y.gz",
+            "logs/2019/c.gz",
+        ])
+    );
+    assert_eq!(glob("*/*.gz"), paths(&["logs/a.gz", "other/d.gz"]));
+    assert_eq!(glob("file?"), paths(&["file1", "file2"]));
+    assert_eq!(glob("file[!2]*"), paths(&["file1", "file10"]));
+    assert_eq!(
+        glob("logs/20[0-9][0-9]/0?/*"),
+        paths(&[
+            "logs/2018/01/x.gz",
+            "logs/2018/01/y.gz",
+            "logs/2018/02/z.log"
+        ])
+    );
+    assert_eq!(
+        glob("logs/2018/**"),
+        paths(&[
+            "logs/2018/01",
+            "logs/2018/01/x.gz",
+            "logs/2018/01/y.gz",
+            "logs/2018/02",
+            "logs/2018/02/z.log",
+        ])
+    );
+    assert_eq!(glob("logs/b.txt"), paths(&["logs/b.txt"]));
+    assert!(glob("missing/**/*.gz").is_empty());
+    assert!(glob("logs/b.txt/*").is_empty());
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::symlink;
+
+        // a link is only followed the first time its target is found, and a
+        // link back up the tree isn't followed at all
+        symlink("../2019", root.join("logs/2018/next")).unwrap();
+        symlink("../2018", root.join("logs/2019/prev")).unwrap();
+        symlink("..", root.join("logs/2019/up")).unwrap();
+        assert_eq!(
+            glob("logs/2019/**/*.gz"),
+            paths(&[
+                "logs/2019/c.gz",
+                "logs/2019/prev/01/x.gz",
+                "logs/2019/prev/01/y.gz",
+                "logs/2019/prev/next/c.gz",
+            ])
+        );
+        // the links are still matched themselves
+        let all = glob("logs/2019/**");
+        assert_eq!(all.len(), 12);
+        assert!(all.contains(&PathBuf::from("logs/2019/up")));
+    }
+
+    fs.remove_dir_all(root).wait().unwrap();
+}
+
+#[test]
+fn test_dir_size() {
+    let fs = FsPool::default();
+
+    let mut root = env::temp_dir();
+    root.push("futures-fs-dir-size");
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("a/b")).unwrap();
+    fs::create_dir(root.join("empty")).unwrap();
+    fs::write(root.join("top"), vec![0; 100]).unwrap();
+    fs::write(root.join("a/one"), vec![0; 2000]).unwrap();
+    fs::write(root.join("a/b/two"), vec![0; 30_000]).unwrap();
+
+    assert_eq!(fs.dir_size(root.clone()).wait().unwrap(), 32_100);
+
+    let stats = fs
+        .dir_stats(root.clone(), Default::default())
+        .wait()
+        .unwrap();
+    assert_eq!(stats.files(), 3);
+    assert_eq!(stats.dirs(), 3);
+    assert_eq!(stats.bytes(), 32_100);
+    assert_eq!(
+        stats.largest(),
+        Some((root.join("a/b/two").as_path(), 30_000))
+    );
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::symlink;
+
+        // links are skipped, unless followed
+        symlink(root.join("a"), root.join("link")).unwrap();
+        assert_eq!(fs.dir_size(root.clone()).wait().unwrap(), 32_100);
+        let opts = DirStatsOptions::default().follow_symlinks(true);
+        let stats = fs.dir_stats(root.clone(), opts).wait().unwrap();
+        assert_eq!(stats.bytes(), 64_100);
+        assert_eq!(stats.dirs(), 5);
+
+        // a link back up the tree isn't followed forever
+        symlink(&root, root.join("a/up")).unwrap();
+        let opts = DirStatsOptions::default().follow_symlinks(true);
+        assert!(fs.dir_stats(root.clone(), opts).wait().is_err());
+        fs::remove_file(root.join("a/up")).unwrap();
+
+        // sparse files take up less space than their length
+        fs::File::create(root.join("sparse"))
+            .unwrap()
+            .set_len(10_000_000)
+            .unwrap();
+        let opts = DirStatsOptions::default().allocated(true);
+        let stats = fs.dir_stats(root.clone(), opts).wait().unwrap();
+        assert!(stats.bytes() < 10_000_000);
+        assert_eq!(fs.dir_size(root.clone()).wait().unwrap(), 10_032_100);
+    }
+
+    fs.remove_dir_all(root).wait().unwrap();
+}